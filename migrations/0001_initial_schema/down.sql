@@ -0,0 +1,7 @@
+DROP TABLE participation;
+DROP TABLE affiliation;
+DROP TABLE plan;
+DROP TABLE organization;
+DROP TABLE person;
+DROP TABLE event;
+DROP TABLE planner;
@@ -0,0 +1,47 @@
+CREATE TABLE planner (
+    planner_id SERIAL PRIMARY KEY
+);
+
+CREATE TABLE event (
+    event_id SERIAL PRIMARY KEY,
+    event_name TEXT NOT NULL,
+    event_location TEXT NOT NULL,
+    event_description TEXT NOT NULL
+);
+
+CREATE TABLE person (
+    person_id SERIAL PRIMARY KEY,
+    person_name TEXT NOT NULL,
+    planner_id INTEGER REFERENCES planner(planner_id)
+);
+
+CREATE TABLE organization (
+    organization_id SERIAL PRIMARY KEY,
+    organization_name TEXT NOT NULL,
+    planner_id INTEGER REFERENCES planner(planner_id)
+);
+
+CREATE TABLE plan (
+    plan_id SERIAL PRIMARY KEY,
+    event_id INTEGER NOT NULL REFERENCES event(event_id) ON DELETE CASCADE,
+    planner_id INTEGER NOT NULL REFERENCES planner(planner_id) ON DELETE CASCADE,
+    UNIQUE (event_id, planner_id)
+);
+
+CREATE TABLE affiliation (
+    affiliation_id SERIAL PRIMARY KEY,
+    person_id INTEGER NOT NULL REFERENCES person(person_id) ON DELETE CASCADE,
+    organization_id INTEGER NOT NULL REFERENCES organization(organization_id) ON DELETE CASCADE,
+    UNIQUE (person_id, organization_id)
+);
+
+CREATE TABLE participation (
+    participation_id SERIAL PRIMARY KEY,
+    event_id INTEGER NOT NULL REFERENCES event(event_id) ON DELETE CASCADE,
+    person_id INTEGER NOT NULL REFERENCES person(person_id) ON DELETE CASCADE,
+    UNIQUE (event_id, person_id)
+);
+
+CREATE INDEX plan_planner_id_idx ON plan(planner_id);
+CREATE INDEX affiliation_organization_id_idx ON affiliation(organization_id);
+CREATE INDEX participation_person_id_idx ON participation(person_id);
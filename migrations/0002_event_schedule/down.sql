@@ -0,0 +1,6 @@
+ALTER TABLE event
+    DROP CONSTRAINT event_ends_after_start,
+    DROP COLUMN all_day,
+    DROP COLUMN time_zone,
+    DROP COLUMN ends_at,
+    DROP COLUMN starts_at;
@@ -0,0 +1,10 @@
+ALTER TABLE event
+    ADD COLUMN starts_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN ends_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC',
+    ADD COLUMN all_day BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE event
+    ALTER COLUMN starts_at DROP DEFAULT,
+    ALTER COLUMN ends_at DROP DEFAULT,
+    ADD CONSTRAINT event_ends_after_start CHECK (ends_at >= starts_at);
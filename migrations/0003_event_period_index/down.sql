@@ -0,0 +1,2 @@
+DROP INDEX event_starts_at_idx;
+DROP INDEX event_period_idx;
@@ -0,0 +1,2 @@
+CREATE INDEX event_period_idx ON event USING gist (tstzrange(starts_at, ends_at, '[]'));
+CREATE INDEX event_starts_at_idx ON event (starts_at);
@@ -0,0 +1,10 @@
+DROP TABLE event_override;
+
+DROP INDEX event_period_idx;
+CREATE INDEX event_period_idx ON event USING gist (tstzrange(starts_at, ends_at, '[]'));
+
+ALTER TABLE event
+    DROP COLUMN series_ends_at,
+    DROP COLUMN exception_dates,
+    DROP COLUMN recurrence_dates,
+    DROP COLUMN recurrence_rule;
@@ -0,0 +1,26 @@
+ALTER TABLE event
+    ADD COLUMN recurrence_rule TEXT,
+    ADD COLUMN recurrence_dates TIMESTAMPTZ[] NOT NULL DEFAULT '{}',
+    ADD COLUMN exception_dates TIMESTAMPTZ[] NOT NULL DEFAULT '{}',
+    ADD COLUMN series_ends_at TIMESTAMPTZ;
+
+-- A NULL series_ends_at means the series never ends.
+UPDATE event SET series_ends_at = ends_at;
+
+DROP INDEX event_period_idx;
+CREATE INDEX event_period_idx ON event USING gist (tstzrange(starts_at, series_ends_at, '[]'));
+
+CREATE TABLE event_override (
+    override_id SERIAL PRIMARY KEY,
+    event_id INTEGER NOT NULL REFERENCES event(event_id) ON DELETE CASCADE,
+    occurrence_starts_at TIMESTAMPTZ NOT NULL,
+    cancelled BOOLEAN NOT NULL DEFAULT false,
+    event_name TEXT,
+    event_location TEXT,
+    event_description TEXT,
+    starts_at TIMESTAMPTZ,
+    ends_at TIMESTAMPTZ,
+    UNIQUE (event_id, occurrence_starts_at)
+);
+
+CREATE INDEX event_override_period_idx ON event_override USING gist (tstzrange(starts_at, ends_at, '[]'));
@@ -0,0 +1,3 @@
+DROP INDEX event_ical_uid_idx;
+
+ALTER TABLE event DROP COLUMN ical_uid;
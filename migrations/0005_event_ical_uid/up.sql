@@ -0,0 +1,3 @@
+ALTER TABLE event ADD COLUMN ical_uid TEXT;
+
+CREATE UNIQUE INDEX event_ical_uid_idx ON event (ical_uid) WHERE ical_uid IS NOT NULL;
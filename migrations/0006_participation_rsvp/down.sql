@@ -0,0 +1,7 @@
+DROP INDEX participation_event_id_idx;
+
+ALTER TABLE participation
+    DROP COLUMN responded_at,
+    DROP COLUMN rsvp_status;
+
+DROP TYPE rsvp_status;
@@ -0,0 +1,7 @@
+CREATE TYPE rsvp_status AS ENUM ('invited', 'accepted', 'declined', 'tentative');
+
+ALTER TABLE participation
+    ADD COLUMN rsvp_status rsvp_status NOT NULL DEFAULT 'invited',
+    ADD COLUMN responded_at TIMESTAMPTZ;
+
+CREATE INDEX participation_event_id_idx ON participation(event_id);
@@ -0,0 +1,19 @@
+CREATE TABLE account (
+    account_id SERIAL PRIMARY KEY,
+    person_id INTEGER NOT NULL UNIQUE REFERENCES person(person_id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX account_email_idx ON account (lower(email));
+
+CREATE TABLE session (
+    session_id TEXT PRIMARY KEY,
+    person_id INTEGER NOT NULL REFERENCES person(person_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX session_person_id_idx ON session(person_id);
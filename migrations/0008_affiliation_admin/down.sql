@@ -0,0 +1,3 @@
+DROP INDEX affiliation_person_id_idx;
+
+ALTER TABLE affiliation DROP COLUMN is_admin;
@@ -0,0 +1,3 @@
+ALTER TABLE affiliation ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX affiliation_person_id_idx ON affiliation(person_id);
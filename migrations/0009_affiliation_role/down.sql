@@ -0,0 +1,8 @@
+DROP TRIGGER affiliation_keep_owner ON affiliation;
+DROP FUNCTION affiliation_keep_owner();
+
+ALTER TABLE affiliation ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT false;
+UPDATE affiliation SET is_admin = role IN ('owner', 'admin');
+ALTER TABLE affiliation DROP COLUMN role;
+
+DROP TYPE organization_role;
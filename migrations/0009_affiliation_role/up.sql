@@ -0,0 +1,36 @@
+CREATE TYPE organization_role AS ENUM ('owner', 'admin', 'member', 'viewer');
+
+ALTER TABLE affiliation ADD COLUMN role organization_role NOT NULL DEFAULT 'member';
+
+-- The first admin of each organization is the one who created it.
+UPDATE affiliation SET role = CASE
+    WHEN affiliation_id = (
+        SELECT min(first_admin.affiliation_id) FROM affiliation first_admin
+        WHERE first_admin.organization_id = affiliation.organization_id AND first_admin.is_admin
+    ) THEN 'owner'::organization_role
+    ELSE 'admin'::organization_role
+END
+WHERE is_admin;
+
+ALTER TABLE affiliation DROP COLUMN is_admin;
+
+CREATE FUNCTION affiliation_keep_owner() RETURNS trigger AS $$
+BEGIN
+    -- Serializes owner changes per organization. When the organization itself is being
+    -- deleted the row is already gone and its affiliations may all go with it.
+    PERFORM 1 FROM organization WHERE organization_id = OLD.organization_id FOR UPDATE;
+    IF FOUND AND NOT EXISTS (
+        SELECT 1 FROM affiliation WHERE organization_id = OLD.organization_id AND role = 'owner'
+    ) THEN
+        RAISE EXCEPTION 'organization % must keep at least one owner', OLD.organization_id
+            USING ERRCODE = 'integrity_constraint_violation';
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER affiliation_keep_owner
+    AFTER UPDATE OF role OR DELETE ON affiliation
+    FOR EACH ROW
+    WHEN (OLD.role = 'owner')
+    EXECUTE FUNCTION affiliation_keep_owner();
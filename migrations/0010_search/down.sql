@@ -0,0 +1,3 @@
+ALTER TABLE organization DROP COLUMN search_vector;
+ALTER TABLE person DROP COLUMN search_vector;
+ALTER TABLE event DROP COLUMN search_vector;
@@ -0,0 +1,23 @@
+-- Names and places are indexed both as written (`simple`) and stemmed (`french`), so that searches
+-- match exact words as well as their French inflections. Weights rank names above locations,
+-- and locations above descriptions.
+ALTER TABLE event ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', event_name), 'A') ||
+    setweight(to_tsvector('french', event_name), 'A') ||
+    setweight(to_tsvector('simple', event_location), 'B') ||
+    setweight(to_tsvector('french', event_location), 'B') ||
+    setweight(to_tsvector('french', event_description), 'C')
+) STORED;
+
+ALTER TABLE person ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    to_tsvector('simple', person_name)
+) STORED;
+
+ALTER TABLE organization ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    to_tsvector('simple', organization_name) ||
+    to_tsvector('french', organization_name)
+) STORED;
+
+CREATE INDEX event_search_idx ON event USING gin (search_vector);
+CREATE INDEX person_search_idx ON person USING gin (search_vector);
+CREATE INDEX organization_search_idx ON organization USING gin (search_vector);
@@ -0,0 +1,12 @@
+DROP TRIGGER participation_bump_version ON participation;
+DROP TRIGGER affiliation_bump_version ON affiliation;
+DROP TRIGGER organization_bump_version ON organization;
+DROP TRIGGER person_bump_version ON person;
+DROP TRIGGER event_bump_version ON event;
+DROP FUNCTION bump_version();
+
+ALTER TABLE participation DROP COLUMN version;
+ALTER TABLE affiliation DROP COLUMN version;
+ALTER TABLE organization DROP COLUMN version;
+ALTER TABLE person DROP COLUMN version;
+ALTER TABLE event DROP COLUMN version;
@@ -0,0 +1,20 @@
+-- Rows that clients change are versioned for optimistic concurrency; the version is served as the
+-- ETag of the row and every update bumps it.
+ALTER TABLE event ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE person ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE organization ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE affiliation ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE participation ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE FUNCTION bump_version() RETURNS trigger AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER event_bump_version BEFORE UPDATE ON event FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER person_bump_version BEFORE UPDATE ON person FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER organization_bump_version BEFORE UPDATE ON organization FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER affiliation_bump_version BEFORE UPDATE ON affiliation FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER participation_bump_version BEFORE UPDATE ON participation FOR EACH ROW EXECUTE FUNCTION bump_version();
@@ -0,0 +1,22 @@
+CREATE TABLE invitation (
+    invitation_id SERIAL PRIMARY KEY,
+    token TEXT NOT NULL UNIQUE,
+    event_id INTEGER REFERENCES event(event_id) ON DELETE CASCADE,
+    organization_id INTEGER REFERENCES organization(organization_id) ON DELETE CASCADE,
+    rsvp_status rsvp_status,
+    role organization_role,
+    expires_at TIMESTAMPTZ NOT NULL,
+    max_uses INTEGER NOT NULL CHECK (max_uses > 0),
+    uses INTEGER NOT NULL DEFAULT 0 CHECK (uses <= max_uses),
+    created_by INTEGER NOT NULL REFERENCES person(person_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ,
+    -- An invitation is to either an event, as an RSVP, or an organization, as a role.
+    CHECK (
+        (event_id IS NOT NULL AND rsvp_status IS NOT NULL AND organization_id IS NULL AND role IS NULL)
+        OR (organization_id IS NOT NULL AND role IS NOT NULL AND event_id IS NULL AND rsvp_status IS NULL)
+    )
+);
+
+CREATE INDEX invitation_event_id_idx ON invitation(event_id);
+CREATE INDEX invitation_organization_id_idx ON invitation(organization_id);
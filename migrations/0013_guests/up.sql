@@ -0,0 +1,19 @@
+-- Answers to an event from people without an account, given through one of its invitations.
+CREATE TABLE guest (
+    guest_id SERIAL PRIMARY KEY,
+    -- The guest's own secret, to change their answer later.
+    token TEXT NOT NULL UNIQUE,
+    event_id INTEGER NOT NULL REFERENCES event(event_id) ON DELETE CASCADE,
+    invitation_id INTEGER REFERENCES invitation(invitation_id) ON DELETE SET NULL,
+    guest_name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    rsvp_status rsvp_status NOT NULL,
+    plus_ones INTEGER NOT NULL DEFAULT 0 CHECK (plus_ones >= 0),
+    responded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- Set once the guest signs up and their answer moves to a participation.
+    person_id INTEGER REFERENCES person(person_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX guest_event_email_idx ON guest (event_id, lower(email));
+CREATE INDEX guest_email_idx ON guest (lower(email)) WHERE person_id IS NULL;
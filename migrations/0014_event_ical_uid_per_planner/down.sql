@@ -0,0 +1,3 @@
+DROP INDEX event_ical_uid_idx;
+ALTER TABLE event DROP COLUMN ical_planner_id;
+CREATE UNIQUE INDEX event_ical_uid_idx ON event (ical_uid) WHERE ical_uid IS NOT NULL;
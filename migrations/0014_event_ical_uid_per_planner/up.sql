@@ -0,0 +1,9 @@
+-- A UID only identifies an event within the calendar it was imported from, so that several
+-- planners can import the same shared calendar. The planner an event was imported into scopes it.
+ALTER TABLE event ADD COLUMN ical_planner_id INTEGER REFERENCES planner(planner_id) ON DELETE SET NULL;
+
+UPDATE event SET ical_planner_id = (SELECT min(plan.planner_id) FROM plan WHERE plan.event_id = event.event_id)
+WHERE ical_uid IS NOT NULL;
+
+DROP INDEX event_ical_uid_idx;
+CREATE UNIQUE INDEX event_ical_uid_idx ON event (ical_planner_id, ical_uid) WHERE ical_uid IS NOT NULL;
@@ -1,5 +1,20 @@
 pub mod models;
+pub mod dto;
 pub mod query;
 pub mod config;
 pub mod handlers;
-pub mod errors;
\ No newline at end of file
+pub mod errors;
+pub mod auth;
+pub mod permissions;
+pub mod migrations;
+pub mod recurrence;
+pub mod ical;
+pub mod pagination;
+pub mod filter;
+pub mod search;
+pub mod validation;
+pub mod patch;
+pub mod etag;
+pub mod repository;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,189 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::db::{errors::MyError, repository::Repository};
+
+/// How long a session token stays valid after login.
+pub const SESSION_TTL_HOURS: i64 = 24 * 7;
+
+/// How long an invitation stays valid when its host does not say.
+pub const INVITATION_TTL_DAYS: i64 = 14;
+
+pub fn hash_password(password: &str) -> Result<String, MyError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| MyError::BadRequest("password cannot be hashed".to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+/// A random, URL-safe secret of 256 bits.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn new_session_id() -> String {
+    random_token()
+}
+
+/// Claims carried by a session token.
+#[derive(Debug, PartialEq)]
+pub struct TokenClaims {
+    pub session_id: String,
+    pub person_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Signs and verifies bearer tokens of the form `base64(claims).base64(hmac-sha256(claims))`.
+pub struct TokenSigner {
+    secret: Vec<u8>,
+}
+
+impl TokenSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        TokenSigner { secret: secret.to_vec() }
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn sign(&self, claims: &TokenClaims) -> String {
+        let payload = format!("{}:{}:{}", claims.session_id, claims.person_id, claims.expires_at.timestamp());
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    pub fn verify(&self, token: &str, now: DateTime<Utc>) -> Option<TokenClaims> {
+        let (payload, signature) = token.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature).ok()?;
+
+        let payload = String::from_utf8(payload).ok()?;
+        let mut parts = payload.split(':');
+        let claims = TokenClaims {
+            session_id: parts.next()?.to_string(),
+            person_id: parts.next()?.parse().ok()?,
+            expires_at: Utc.timestamp_opt(parts.next()?.parse().ok()?, 0).single()?,
+        };
+
+        if parts.next().is_some() || claims.expires_at <= now {
+            return None;
+        }
+        Some(claims)
+    }
+}
+
+pub fn session_expiry(now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::hours(SESSION_TTL_HOURS)
+}
+
+pub fn invitation_expiry(now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::days(INVITATION_TTL_DAYS)
+}
+
+/// The person behind the bearer token of the request, backed by a live session.
+pub struct AuthenticatedPerson {
+    pub person_id: i32,
+    pub session_id: String,
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim().to_string())
+}
+
+impl FromRequest for AuthenticatedPerson {
+    type Error = MyError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = bearer_token(req);
+        let signer = req.app_data::<web::Data<TokenSigner>>().cloned();
+        let repository = req.app_data::<web::Data<dyn Repository>>().cloned();
+
+        Box::pin(async move {
+            let (token, signer, repository) = match (token, signer, repository) {
+                (Some(token), Some(signer), Some(repository)) => (token, signer, repository),
+                _ => return Err(MyError::Unauthorized),
+            };
+
+            let claims = signer.verify(&token, Utc::now()).ok_or(MyError::Unauthorized)?;
+
+            let client = repository.connect().await?;
+            if !client.is_session_active(&claims.session_id, claims.person_id).await? {
+                return Err(MyError::Unauthorized);
+            }
+
+            Ok(AuthenticatedPerson { person_id: claims.person_id, session_id: claims.session_id })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::{hash_password, verify_password, TokenClaims, TokenSigner};
+
+    #[test]
+    fn password_hash_roundtrip() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("battery staple", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+    }
+
+    #[test]
+    fn token_roundtrip_and_tampering() {
+        let now = Utc::now();
+        let signer = TokenSigner::new(b"secret");
+        let claims = TokenClaims {
+            session_id: "abc".to_string(),
+            person_id: 7,
+            expires_at: now + Duration::hours(1),
+        };
+        let token = signer.sign(&claims);
+
+        let verified = signer.verify(&token, now).unwrap();
+        assert_eq!(verified.session_id, "abc");
+        assert_eq!(verified.person_id, 7);
+
+        assert!(TokenSigner::new(b"other").verify(&token, now).is_none());
+        assert!(signer.verify(&token, now + Duration::hours(2)).is_none());
+
+        let forged = TokenSigner::new(b"other").sign(&TokenClaims { person_id: 1, ..claims });
+        let (payload, _) = forged.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        assert!(signer.verify(&format!("{}.{}", payload, signature), now).is_none());
+    }
+}
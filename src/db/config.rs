@@ -1,6 +1,491 @@
-use serde::Deserialize;
+//! Server configuration.
+//!
+//! Settings are layered: built-in defaults, then a TOML file, then environment variables, then
+//! command-line flags, each overriding the ones before. Environment variables spell out the dotted
+//! path of a setting, such as `PG.HOST` or `SERVER.BIND`; lists may be given comma-separated. The
+//! loaded configuration is validated as a whole, so that every mistake is reported at once.
+
+use std::{fmt::Display, fs, io::BufReader, path::PathBuf};
+
+use ::config::{Config, Environment, File, FileFormat, Value};
+use serde::{Deserialize, Deserializer};
+
+use crate::db::{pagination::PageSettings, validation::FieldErrors};
+
+/// Read when `--config` is not given, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "praecipio.toml";
+
+/// Tokens are signed with HMAC-SHA256, whose key should be at least as long as its output.
+pub const AUTH_SECRET_MIN: usize = 32;
+
+const SECTIONS: [&str; 8] = ["auth_secret", "server", "pg", "pagination", "cors", "log", "tls", "features"];
+
 #[derive(Debug, Default, Deserialize)]
-pub struct ExampleConfig {
-    pub server_addr: String,
+pub struct ServerConfig {
+    #[serde(default)]
+    pub server: ServerSettings,
+    #[serde(default)]
+    pub auth_secret: String,
+    #[serde(default)]
     pub pg: deadpool_postgres::Config,
+    #[serde(default)]
+    pub pagination: PageSettings,
+    #[serde(default)]
+    pub cors: CorsSettings,
+    #[serde(default)]
+    pub log: LogSettings,
+    #[serde(default)]
+    pub tls: TlsSettings,
+    #[serde(default)]
+    pub features: Features,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    /// `host:port` pairs to listen on.
+    #[serde(deserialize_with = "list")]
+    pub bind: Vec<String>,
+    /// Defaults to the number of CPUs.
+    pub workers: Option<usize>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings { bind: vec!["127.0.0.1:8080".to_string()], workers: None }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    /// Origins allowed to call the API from a browser, or `*` for any.
+    #[serde(deserialize_with = "list")]
+    pub allowed_origins: Vec<String>,
+    pub max_age: usize,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        CorsSettings { allowed_origins: vec!["*".to_string()], max_age: 3600 }
+    }
+}
+
+impl CorsSettings {
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSettings {
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`; `RUST_LOG` still refines it.
+    pub level: String,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings { level: "info".to_string() }
+    }
+}
+
+impl LogSettings {
+    pub fn level_filter(&self) -> Option<log::LevelFilter> {
+        self.level.parse().ok()
+    }
+}
+
+/// HTTPS on every bind address. Certificates and keys are read as PEM.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSettings {
+    pub enabled: bool,
+    pub cert_file: Option<PathBuf>,
+    pub key_file: Option<PathBuf>,
+}
+
+impl TlsSettings {
+    pub fn server_config(&self) -> Result<rustls::ServerConfig, String> {
+        let (cert_file, key_file) = match (&self.cert_file, &self.key_file) {
+            (Some(cert_file), Some(key_file)) => (cert_file, key_file),
+            _ => return Err("tls.cert_file and tls.key_file must both be set".to_string()),
+        };
+        let open = |path: &PathBuf| {
+            fs::File::open(path)
+                .map(BufReader::new)
+                .map_err(|err| format!("cannot read {}: {}", path.display(), err))
+        };
+
+        let certs = rustls_pemfile::certs(&mut open(cert_file)?)
+            .map_err(|err| format!("{} is not a PEM certificate chain: {}", cert_file.display(), err))?
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect::<Vec<_>>();
+        if certs.is_empty() {
+            return Err(format!("{} holds no certificate", cert_file.display()));
+        }
+
+        let key = rustls_pemfile::pkcs8_private_keys(&mut open(key_file)?)
+            .map_err(|err| format!("{} is not a PEM private key: {}", key_file.display(), err))?
+            .into_iter()
+            .next()
+            .map(rustls::PrivateKey)
+            .ok_or_else(|| format!("{} holds no PKCS #8 private key", key_file.display()))?;
+
+        rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|err| format!("the TLS certificate and key do not work together: {}", err))
+    }
+}
+
+/// Parts of the API that deployments may switch off.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// The deprecated routes that take ids in the request body, see `handlers::legacy`.
+    pub legacy_routes: bool,
+    pub calendar_import: bool,
+    pub search: bool,
+    /// Apply pending migrations before serving.
+    pub migrate_on_start: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features { legacy_routes: true, calendar_import: true, search: true, migrate_on_start: true }
+    }
+}
+
+/// Lists come as arrays from files and as comma-separated strings from the environment.
+fn list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match List::deserialize(deserializer)? {
+        List::One(items) => items.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect(),
+        List::Many(items) => items,
+    })
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The sources could not be read or do not have the expected shape.
+    Load(String),
+    Invalid(FieldErrors),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Load(msg) => write!(f, "could not load the configuration: {}", msg),
+            ConfigError::Invalid(errors) => {
+                write!(f, "the configuration is invalid:")?;
+                for message in errors.messages() {
+                    write!(f, "\n  - {}", message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<::config::ConfigError> for ConfigError {
+    fn from(err: ::config::ConfigError) -> Self {
+        ConfigError::Load(err.to_string())
+    }
+}
+
+/// The command line: which file to read, the settings it overrides and the command to run.
+#[derive(Debug, Default, PartialEq)]
+pub struct CommandLine {
+    pub config_file: Option<PathBuf>,
+    pub overrides: Vec<(String, Vec<String>)>,
+    pub command: Vec<String>,
+}
+
+pub const USAGE: &str = "usage: praecipio_server [--config <file>] [--bind <host:port>]... [--workers <n>] [--log-level <level>] [--set <key>=<value>]... [serve | migrate <up|down [steps]|status> | config check]";
+
+impl CommandLine {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut command_line = CommandLine::default();
+        let mut binds = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "-c" | "--config" => command_line.config_file = Some(PathBuf::from(value()?)),
+                "--bind" => binds.push(value()?),
+                "--workers" => command_line.overrides.push(("server.workers".to_string(), vec![value()?])),
+                "--log-level" => command_line.overrides.push(("log.level".to_string(), vec![value()?])),
+                "--set" => {
+                    let setting = value()?;
+                    let (key, value) = setting.split_once('=')
+                        .ok_or_else(|| format!("--set takes key=value, not {}", setting))?;
+                    command_line.overrides.push((key.trim().to_string(), vec![value.to_string()]));
+                }
+                flag if flag.starts_with('-') && command_line.command.is_empty() => return Err(format!("unknown flag {}", flag)),
+                _ => command_line.command.push(arg),
+            }
+        }
+
+        // Several --bind flags add up to one list, which replaces the configured one.
+        if !binds.is_empty() {
+            command_line.overrides.push(("server.bind".to_string(), binds));
+        }
+
+        Ok(command_line)
+    }
+}
+
+impl ServerConfig {
+    pub fn load(command_line: &CommandLine) -> Result<Self, ConfigError> {
+        ServerConfig::load_from(command_line, Environment::default())
+    }
+
+    fn load_from(command_line: &CommandLine, environment: Environment) -> Result<Self, ConfigError> {
+        let file = match &command_line.config_file {
+            Some(path) => Some(path.clone()),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+        };
+
+        let mut builder = Config::builder();
+        if let Some(path) = file {
+            let contents = fs::read_to_string(&path)
+                .map_err(|err| ConfigError::Load(format!("cannot read {}: {}", path.display(), err)))?;
+            check_sections(&contents)?;
+            builder = builder.add_source(File::from_str(&contents, FileFormat::Toml));
+        }
+        builder = builder.add_source(environment);
+        for (key, values) in &command_line.overrides {
+            builder = match values.as_slice() {
+                [value] => builder.set_override(key.as_str(), value.as_str())?,
+                values => builder.set_override(key.as_str(), Value::from(values.to_vec()))?,
+            };
+        }
+
+        let config: ServerConfig = builder.build()?.try_deserialize()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = FieldErrors::default();
+
+        if self.auth_secret.is_empty() {
+            errors.add("auth_secret", "must be set");
+        } else if self.auth_secret.len() < AUTH_SECRET_MIN {
+            errors.add("auth_secret", format!("must be at least {} bytes long", AUTH_SECRET_MIN));
+        }
+
+        if self.server.bind.is_empty() {
+            errors.add("server.bind", "must list at least one address");
+        }
+        for address in &self.server.bind {
+            let valid = address.rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+            if !valid {
+                errors.add("server.bind", format!("{} is not a host:port address", address));
+            }
+        }
+        if self.server.workers == Some(0) {
+            errors.add("server.workers", "must be at least 1");
+        }
+
+        if self.pg.pool.as_ref().is_some_and(|pool| pool.max_size == 0) {
+            errors.add("pg.pool.max_size", "must be at least 1");
+        }
+
+        if self.pagination.page_size < 1 {
+            errors.add("pagination.page_size", "must be at least 1");
+        }
+        if self.pagination.max_page_size < self.pagination.page_size {
+            errors.add("pagination.max_page_size", "must not be less than pagination.page_size");
+        }
+
+        for origin in &self.cors.allowed_origins {
+            let valid = origin == "*" || ["http://", "https://"].iter().any(|scheme| {
+                origin.strip_prefix(scheme).is_some_and(|host| !host.is_empty() && !host.contains('/'))
+            });
+            if !valid {
+                errors.add("cors.allowed_origins", format!("{} is neither * nor an http(s)://host[:port] origin", origin));
+            }
+        }
+
+        if self.log.level_filter().is_none() {
+            errors.add("log.level", format!("{} is not one of off, error, warn, info, debug or trace", self.log.level));
+        }
+
+        if self.tls.enabled {
+            for (field, path) in [("tls.cert_file", &self.tls.cert_file), ("tls.key_file", &self.tls.key_file)] {
+                match path {
+                    None => errors.add(field, "must be set when TLS is enabled"),
+                    Some(path) if !path.is_file() => errors.add(field, format!("{} does not exist", path.display())),
+                    Some(_) => {}
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+/// Sections are strict about their keys, but the top level also receives every environment
+/// variable; unknown top-level keys are therefore only looked for in the file.
+fn check_sections(contents: &str) -> Result<(), ConfigError> {
+    let table = Config::builder()
+        .add_source(File::from_str(contents, FileFormat::Toml))
+        .build()?
+        .try_deserialize::<std::collections::HashMap<String, Value>>()?;
+
+    let mut errors = FieldErrors::default();
+    for key in table.keys().filter(|key| !SECTIONS.contains(&key.as_str())) {
+        errors.add(key, "is not a known setting");
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use ::config::Environment;
+
+    use super::{CommandLine, ConfigError, ServerConfig};
+
+    const SECRET: &str = "a-secret-that-is-at-least-32-bytes-long";
+
+    fn environment(vars: &[(&str, &str)]) -> Environment {
+        let vars = vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>();
+        Environment::default().source(Some(vars))
+    }
+
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("praecipio-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn command_line_splits_flags_from_the_command() {
+        let command_line = CommandLine::parse(args(&["--config", "prod.toml", "--bind", "0.0.0.0:80", "--bind", "[::]:80", "--set", "features.search=false", "migrate", "down", "2"])).unwrap();
+        assert_eq!(command_line.config_file, Some(PathBuf::from("prod.toml")));
+        assert_eq!(command_line.command, args(&["migrate", "down", "2"]));
+        assert_eq!(command_line.overrides, vec![
+            ("features.search".to_string(), args(&["false"])),
+            ("server.bind".to_string(), args(&["0.0.0.0:80", "[::]:80"])),
+        ]);
+
+        assert!(CommandLine::parse(args(&["--bind"])).is_err());
+        assert!(CommandLine::parse(args(&["--verbose"])).is_err());
+        assert!(CommandLine::parse(args(&["--set", "log.level"])).is_err());
+    }
+
+    #[test]
+    fn layers_override_each_other() {
+        let path = config_file("layers", &format!(r#"
+            auth_secret = "{}"
+
+            [server]
+            bind = ["127.0.0.1:9000"]
+            workers = 2
+
+            [pg]
+            host = "db.internal"
+            dbname = "praecipio"
+            pool = {{ max_size = 8 }}
+
+            [pagination]
+            page_size = 20
+
+            [cors]
+            allowed_origins = ["https://app.example.org"]
+        "#, SECRET));
+
+        let command_line = CommandLine::parse(args(&["--config", path.to_str().unwrap(), "--log-level", "debug"])).unwrap();
+        let config = ServerConfig::load_from(&command_line, environment(&[
+            ("PG.HOST", "127.0.0.1"),
+            ("SERVER.BIND", "0.0.0.0:8080, 0.0.0.0:8081"),
+            ("FEATURES.SEARCH", "false"),
+        ])).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.server.bind, args(&["0.0.0.0:8080", "0.0.0.0:8081"]));
+        assert_eq!(config.server.workers, Some(2));
+        assert_eq!(config.pg.host.as_deref(), Some("127.0.0.1"));
+        assert_eq!(config.pg.dbname.as_deref(), Some("praecipio"));
+        assert_eq!(config.pg.pool.map(|pool| pool.max_size), Some(8));
+        assert_eq!((config.pagination.page_size, config.pagination.max_page_size), (20, 100));
+        assert_eq!(config.cors.allowed_origins, args(&["https://app.example.org"]));
+        assert!(!config.cors.allows_any_origin());
+        assert_eq!(config.log.level_filter(), Some(log::LevelFilter::Debug));
+        assert!(!config.features.search);
+        assert!(config.features.legacy_routes);
+    }
+
+    #[test]
+    fn every_mistake_is_reported() {
+        let command_line = CommandLine::parse(args(&["--bind", "localhost", "--set", "tls.enabled=true"])).unwrap();
+        let err = ServerConfig::load_from(&command_line, environment(&[
+            ("AUTH_SECRET", "short"),
+            ("PAGINATION.PAGE_SIZE", "0"),
+            ("CORS.ALLOWED_ORIGINS", "https://app.example.org/,*"),
+            ("LOG.LEVEL", "loud"),
+        ])).unwrap_err();
+
+        let message = err.to_string();
+        for expected in [
+            "auth_secret must be at least 32 bytes long",
+            "server.bind localhost is not a host:port address",
+            "pagination.page_size must be at least 1",
+            "cors.allowed_origins https://app.example.org/ is neither",
+            "log.level loud is not one of",
+            "tls.cert_file must be set when TLS is enabled",
+            "tls.key_file must be set when TLS is enabled",
+        ] {
+            assert!(message.contains(expected), "{} in {}", expected, message);
+        }
+    }
+
+    #[test]
+    fn unknown_and_mistyped_settings_are_refused() {
+        let path = config_file("typos", &format!("auth_secret = \"{}\"\nauth_sercet = \"x\"\n", SECRET));
+        let command_line = CommandLine { config_file: Some(path.clone()), ..CommandLine::default() };
+        let err = ServerConfig::load_from(&command_line, environment(&[])).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid(_)));
+        assert!(err.to_string().contains("auth_sercet is not a known setting"));
+
+        std::fs::write(&path, format!("auth_secret = \"{}\"\n[server]\nbinds = [\"127.0.0.1:80\"]\n", SECRET)).unwrap();
+        let err = ServerConfig::load_from(&command_line, environment(&[])).unwrap_err();
+        assert!(err.to_string().contains("binds"), "{}", err);
+
+        std::fs::write(&path, format!("auth_secret = \"{}\"\n[pagination]\npage_size = \"many\"\n", SECRET)).unwrap();
+        let err = ServerConfig::load_from(&command_line, environment(&[])).unwrap_err();
+        assert!(matches!(err, ConfigError::Load(_)));
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = CommandLine { config_file: Some(PathBuf::from("/nonexistent/praecipio.toml")), ..CommandLine::default() };
+        assert!(ServerConfig::load_from(&missing, environment(&[])).is_err());
+    }
 }
@@ -0,0 +1,376 @@
+//! Request and response bodies of the event, person, organization, affiliation, participation and guest
+//! endpoints.
+//!
+//! The row models of `db::models` follow the schema and these types follow the API; handlers
+//! convert between the two explicitly, so that either side can change without the other. Request
+//! bodies only hold what a client may set: ids come from the path and planners are assigned by the
+//! server. Update bodies are merge patches, see `db::patch`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{
+    models::{Event, EventOccurrence, Guest, Invitation, Organization, OrganizationRole, Person, RsvpStatus},
+    patch::Patch
+};
+
+fn default_time_zone() -> String {
+    "UTC".to_string()
+}
+
+/// Body of `POST /events`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CreateEvent {
+    pub event_name: String,
+    pub event_location: String,
+    pub event_description: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    #[serde(default = "default_time_zone")]
+    pub time_zone: String,
+    #[serde(default)]
+    pub all_day: bool,
+    pub recurrence_rule: Option<String>,
+    #[serde(default)]
+    pub recurrence_dates: Vec<DateTime<Utc>>,
+    #[serde(default)]
+    pub exception_dates: Vec<DateTime<Utc>>,
+}
+
+impl From<CreateEvent> for Event {
+    fn from(event: CreateEvent) -> Self {
+        Event {
+            event_id: None,
+            event_name: event.event_name,
+            event_location: event.event_location,
+            event_description: event.event_description,
+            starts_at: event.starts_at,
+            ends_at: event.ends_at,
+            time_zone: event.time_zone,
+            all_day: event.all_day,
+            recurrence_rule: event.recurrence_rule,
+            recurrence_dates: event.recurrence_dates,
+            exception_dates: event.exception_dates,
+            version: 1,
+        }
+    }
+}
+
+/// Body of `PATCH /events/{event_id}`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UpdateEvent {
+    pub event_name: Patch<String>,
+    pub event_location: Patch<String>,
+    pub event_description: Patch<String>,
+    pub starts_at: Patch<DateTime<Utc>>,
+    pub ends_at: Patch<DateTime<Utc>>,
+    pub time_zone: Patch<String>,
+    pub all_day: Patch<bool>,
+    pub recurrence_rule: Patch<String>,
+    pub recurrence_dates: Patch<Vec<DateTime<Utc>>>,
+    pub exception_dates: Patch<Vec<DateTime<Utc>>>,
+}
+
+impl UpdateEvent {
+    pub fn apply(&self, event: &mut Event) {
+        self.event_name.merge(&mut event.event_name);
+        self.event_location.merge(&mut event.event_location);
+        self.event_description.merge(&mut event.event_description);
+        self.starts_at.merge(&mut event.starts_at);
+        self.ends_at.merge(&mut event.ends_at);
+        self.time_zone.merge(&mut event.time_zone);
+        self.all_day.merge(&mut event.all_day);
+        self.recurrence_rule.merge_nullable(&mut event.recurrence_rule);
+        self.recurrence_dates.merge_or_default(&mut event.recurrence_dates);
+        self.exception_dates.merge_or_default(&mut event.exception_dates);
+    }
+
+    /// Whether the patch moves the event or changes how it repeats.
+    pub fn changes_schedule(&self) -> bool {
+        self.starts_at.is_supplied()
+            || self.ends_at.is_supplied()
+            || self.time_zone.is_supplied()
+            || self.all_day.is_supplied()
+            || self.recurrence_rule.is_supplied()
+            || self.recurrence_dates.is_supplied()
+            || self.exception_dates.is_supplied()
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct EventResponse {
+    pub event_id: i32,
+    pub event_name: String,
+    pub event_location: String,
+    pub event_description: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub time_zone: String,
+    pub all_day: bool,
+    pub recurrence_rule: Option<String>,
+    pub recurrence_dates: Vec<DateTime<Utc>>,
+    pub exception_dates: Vec<DateTime<Utc>>,
+    pub version: i32,
+}
+
+// Rows read back from the database always carry their id; only rows about to be inserted lack one.
+impl From<Event> for EventResponse {
+    fn from(event: Event) -> Self {
+        EventResponse {
+            event_id: event.event_id.unwrap_or_default(),
+            event_name: event.event_name,
+            event_location: event.event_location,
+            event_description: event.event_description,
+            starts_at: event.starts_at,
+            ends_at: event.ends_at,
+            time_zone: event.time_zone,
+            all_day: event.all_day,
+            recurrence_rule: event.recurrence_rule,
+            recurrence_dates: event.recurrence_dates,
+            exception_dates: event.exception_dates,
+            version: event.version,
+        }
+    }
+}
+
+/// One occurrence of an event, with the overrides of that occurrence applied.
+#[derive(Deserialize, Serialize)]
+pub struct OccurrenceResponse {
+    #[serde(flatten)]
+    pub event: EventResponse,
+    pub occurrence_starts_at: DateTime<Utc>,
+}
+
+impl From<EventOccurrence> for OccurrenceResponse {
+    fn from(occurrence: EventOccurrence) -> Self {
+        OccurrenceResponse {
+            event: occurrence.event.into(),
+            occurrence_starts_at: occurrence.occurrence_starts_at,
+        }
+    }
+}
+
+/// Body of `PATCH /users/{person_id}`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UpdatePerson {
+    pub person_name: Patch<String>,
+}
+
+impl UpdatePerson {
+    pub fn apply(&self, person: &mut Person) {
+        self.person_name.merge(&mut person.person_name);
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PersonResponse {
+    pub person_id: i32,
+    pub person_name: String,
+    pub planner_id: Option<i32>,
+    pub version: i32,
+}
+
+impl From<Person> for PersonResponse {
+    fn from(person: Person) -> Self {
+        PersonResponse {
+            person_id: person.person_id.unwrap_or_default(),
+            person_name: person.person_name,
+            planner_id: person.planner_id,
+            version: person.version,
+        }
+    }
+}
+
+/// Body of `POST /organizations`; the organization's planner is created along with it.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CreateOrganization {
+    pub organization_name: String,
+}
+
+impl CreateOrganization {
+    pub fn into_organization(self, planner_id: i32) -> Organization {
+        Organization {
+            organization_id: None,
+            organization_name: self.organization_name,
+            planner_id: Some(planner_id),
+            version: 1,
+        }
+    }
+}
+
+/// Body of `PATCH /organizations/{organization_id}`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UpdateOrganization {
+    pub organization_name: Patch<String>,
+}
+
+impl UpdateOrganization {
+    pub fn apply(&self, organization: &mut Organization) {
+        self.organization_name.merge(&mut organization.organization_name);
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct OrganizationResponse {
+    pub organization_id: i32,
+    pub organization_name: String,
+    pub planner_id: Option<i32>,
+    pub version: i32,
+}
+
+impl From<Organization> for OrganizationResponse {
+    fn from(organization: Organization) -> Self {
+        OrganizationResponse {
+            organization_id: organization.organization_id.unwrap_or_default(),
+            organization_name: organization.organization_name,
+            planner_id: organization.planner_id,
+            version: organization.version,
+        }
+    }
+}
+
+/// Body of `PATCH /affiliations/{affiliation_id}`: who is affiliated where never changes, only the role.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct UpdateAffiliation {
+    pub role: OrganizationRole,
+}
+
+/// Body of `PATCH /participations/{participation_id}`: a participant only ever changes their answer.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct UpdateParticipation {
+    pub rsvp_status: RsvpStatus,
+}
+
+/// What the public RSVP page of an invitation shows: the event and until when it can be answered.
+#[derive(Deserialize, Serialize)]
+pub struct RsvpPage {
+    #[serde(flatten)]
+    pub event: EventResponse,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RsvpPage {
+    pub fn new(event: Event, invitation: &Invitation) -> Self {
+        RsvpPage {
+            event: event.into(),
+            expires_at: invitation.expires_at,
+        }
+    }
+}
+
+/// Body of `POST /rsvp/{token}` and `PUT /rsvp/guests/{guest_token}`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct GuestRsvp {
+    pub guest_name: String,
+    pub email: String,
+    pub rsvp_status: RsvpStatus,
+    #[serde(default)]
+    pub plus_ones: i32,
+}
+
+impl GuestRsvp {
+    pub fn into_guest(self, event_id: i32, invitation_id: Option<i32>, token: String) -> Guest {
+        Guest {
+            guest_id: None,
+            token,
+            event_id,
+            invitation_id,
+            guest_name: self.guest_name,
+            email: self.email,
+            rsvp_status: self.rsvp_status,
+            plus_ones: self.plus_ones,
+            responded_at: Utc::now(),
+            person_id: None,
+        }
+    }
+}
+
+/// A guest as hosts see them; only the guest themselves are told their `guest_token`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct GuestResponse {
+    pub guest_id: i32,
+    pub event_id: i32,
+    pub guest_name: String,
+    pub email: String,
+    pub rsvp_status: RsvpStatus,
+    pub plus_ones: i32,
+    pub responded_at: DateTime<Utc>,
+    pub person_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_token: Option<String>,
+}
+
+impl GuestResponse {
+    /// The response to the guest themselves, carrying the token to change their answer with.
+    pub fn with_token(guest: Guest) -> Self {
+        let token = guest.token.clone();
+        GuestResponse { guest_token: Some(token), ..guest.into() }
+    }
+}
+
+impl From<Guest> for GuestResponse {
+    fn from(guest: Guest) -> Self {
+        GuestResponse {
+            guest_id: guest.guest_id.unwrap_or_default(),
+            event_id: guest.event_id,
+            guest_name: guest.guest_name,
+            email: guest.email,
+            rsvp_status: guest.rsvp_status,
+            plus_ones: guest.plus_ones,
+            responded_at: guest.responded_at,
+            person_id: guest.person_id,
+            guest_token: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{CreateEvent, CreateOrganization, UpdateEvent, UpdatePerson};
+    use crate::db::models::{Event, Person};
+
+    #[test]
+    fn requests_only_carry_what_clients_may_set() {
+        // A planner sent by the client is not part of the contract and never reaches the row.
+        let organization: CreateOrganization = serde_json::from_value(serde_json::json!({ "organization_name": "festival_a", "planner_id": 1 })).unwrap();
+        assert_eq!(organization.into_organization(7).planner_id, Some(7));
+
+        let mut person = Person { person_id: Some(3), person_name: "GDVCB".to_string(), planner_id: Some(7), version: 1 };
+        let patch: UpdatePerson = serde_json::from_value(serde_json::json!({ "person_name": "Camille", "planner_id": 1 })).unwrap();
+        patch.apply(&mut person);
+        assert_eq!((person.person_id, person.person_name.as_str(), person.planner_id), (Some(3), "Camille", Some(7)));
+    }
+
+    #[test]
+    fn patches_merge_supplied_fields_only() {
+        let create: CreateEvent = serde_json::from_value(serde_json::json!({
+            "event_name": "anniv GDVCB",
+            "event_location": "Paris",
+            "event_description": "Petit anniv",
+            "starts_at": "2022-06-18T19:00:00Z",
+            "ends_at": "2022-06-19T02:00:00Z",
+            "recurrence_rule": "FREQ=YEARLY",
+        })).unwrap();
+        let mut event = Event { event_id: Some(4), ..Event::from(create) };
+
+        let patch: UpdateEvent = serde_json::from_value(serde_json::json!({
+            "event_location": "Lyon",
+            "recurrence_rule": null,
+        })).unwrap();
+        patch.apply(&mut event);
+
+        assert_eq!(event.event_name, "anniv GDVCB");
+        assert_eq!(event.event_location, "Lyon");
+        assert_eq!(event.recurrence_rule, None);
+        assert_eq!(event.starts_at, Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap());
+        assert!(patch.changes_schedule());
+
+        let patch: UpdateEvent = serde_json::from_value(serde_json::json!({ "event_name": "Gala" })).unwrap();
+        assert!(!patch.changes_schedule());
+    }
+}
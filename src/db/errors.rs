@@ -1,43 +1,196 @@
 use std::fmt::Display;
 
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{http::{header, StatusCode}, HttpResponse, ResponseError};
 use deadpool_postgres::PoolError;
 use derive_more::From;
+use serde::Serialize;
 use tokio_pg_mapper::Error as PGMError;
-use tokio_postgres::error::Error as PGError;
+use tokio_postgres::error::{Error as PGError, SqlState};
+
+use crate::db::{filter::FilterError, validation::FieldErrors};
 
 #[derive(From, Debug)]
 pub enum MyError {
     NotFound,
+    Unauthorized,
+    Forbidden,
+    BadRequest(String),
+    Conflict(String),
+    PreconditionFailed,
+    InvalidFilter(FilterError),
+    Invalid(FieldErrors),
+    // A constraint broken in the in-memory repository, reported like the database error of that state.
+    Violation(SqlState),
     PGError(PGError),
     PGMError(PGMError),
     PoolError(PoolError),
+    // A state the handlers do not expect, such as a delete touching several rows; logged, never shown.
+    Internal(String),
 }
 
 impl Display for MyError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
-            MyError::NotFound => write!(f, "Not Found"),   
+            MyError::NotFound => write!(f, "Not Found"),
+            MyError::Unauthorized => write!(f, "Unauthorized"),
+            MyError::Forbidden => write!(f, "Forbidden"),
+            MyError::BadRequest(ref msg) => write!(f, "Bad Request: {}", msg),
+            MyError::Conflict(ref msg) => write!(f, "Conflict: {}", msg),
+            MyError::PreconditionFailed => write!(f, "Precondition Failed"),
+            MyError::InvalidFilter(ref err) => write!(f, "Invalid filter {}: {}", err.parameter, err.message),
+            MyError::Invalid(ref errors) => write!(f, "Invalid: {}", errors),
+            MyError::Violation(ref state) => write!(f, "Violation: {}", state.code()),
             MyError::PGError(_) => write!(f, "PGError"),
             MyError::PGMError(_) => write!(f, "PGMError"),
-            MyError::PoolError(_) => write!(f, "PoolError")
+            MyError::PoolError(_) => write!(f, "PoolError"),
+            MyError::Internal(ref msg) => write!(f, "Internal: {}", msg),
         }
     }
 }
 
-impl ResponseError for MyError {
-    fn error_response(&self) -> HttpResponse {
+/// An RFC 7807 `application/problem+json` body.
+///
+/// `code` is stable and meant for machines; `detail` is meant for people and may change. Problems
+/// use the `about:blank` type, so `title` is the reason phrase of `status`.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title: &'static str,
+    pub status: u16,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
+}
+
+impl Problem {
+    fn new(status: StatusCode, code: &'static str, detail: Option<String>) -> Self {
+        Problem {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            code,
+            detail,
+            parameter: None,
+            errors: None,
+        }
+    }
+}
+
+fn detail(detail: &str) -> Option<String> {
+    Some(detail.to_string())
+}
+
+/// Maps the SQLSTATE of a database error to a problem. Messages from Postgres name tables,
+/// constraints and values, so they are never passed on; unknown states become a bare 500.
+fn database_problem(state: Option<&SqlState>) -> Problem {
+    let state = match state {
+        Some(state) => state,
+        None => return Problem::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", None),
+    };
+
+    match *state {
+        SqlState::UNIQUE_VIOLATION => Problem::new(StatusCode::CONFLICT, "already_exists", detail("the resource already exists")),
+        SqlState::EXCLUSION_VIOLATION | SqlState::INTEGRITY_CONSTRAINT_VIOLATION => {
+            Problem::new(StatusCode::CONFLICT, "conflict", detail("the change conflicts with existing data"))
+        }
+        SqlState::T_R_SERIALIZATION_FAILURE | SqlState::T_R_DEADLOCK_DETECTED | SqlState::LOCK_NOT_AVAILABLE => {
+            Problem::new(StatusCode::CONFLICT, "concurrent_update", detail("the resource was changed concurrently, retry the request"))
+        }
+        SqlState::FOREIGN_KEY_VIOLATION => {
+            Problem::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_reference", detail("a referenced resource does not exist"))
+        }
+        SqlState::NOT_NULL_VIOLATION => Problem::new(StatusCode::UNPROCESSABLE_ENTITY, "missing_value", detail("a required value is missing")),
+        SqlState::CHECK_VIOLATION => Problem::new(StatusCode::UNPROCESSABLE_ENTITY, "constraint_violation", detail("a value is not allowed")),
+        SqlState::NO_DATA | SqlState::NO_DATA_FOUND => Problem::new(StatusCode::NOT_FOUND, "not_found", None),
+        // Class 22 holds the data exceptions: malformed, out of range or overlong values.
+        ref state if state.code().starts_with("22") => {
+            Problem::new(StatusCode::BAD_REQUEST, "invalid_value", detail("a value is malformed or out of range"))
+        }
+        _ => Problem::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", None),
+    }
+}
+
+impl MyError {
+    pub fn problem(&self) -> Problem {
         match *self {
-            MyError::NotFound => HttpResponse::NotFound().finish(),
-            MyError::PoolError(ref err) => {
-                HttpResponse::InternalServerError().body(err.to_string())
+            MyError::NotFound => Problem::new(StatusCode::NOT_FOUND, "not_found", None),
+            MyError::Unauthorized => Problem::new(StatusCode::UNAUTHORIZED, "unauthorized", detail("a valid bearer token is required")),
+            MyError::Forbidden => Problem::new(StatusCode::FORBIDDEN, "forbidden", None),
+            MyError::BadRequest(ref msg) => Problem::new(StatusCode::BAD_REQUEST, "bad_request", Some(msg.clone())),
+            MyError::Conflict(ref msg) => Problem::new(StatusCode::CONFLICT, "conflict", Some(msg.clone())),
+            MyError::PreconditionFailed => {
+                Problem::new(StatusCode::PRECONDITION_FAILED, "precondition_failed", detail("the resource has changed since it was read"))
             }
-            MyError::PGError(ref err) => {
-                HttpResponse::InternalServerError().body(err.to_string())
+            MyError::InvalidFilter(ref err) => Problem {
+                parameter: Some(err.parameter.clone()),
+                ..Problem::new(StatusCode::BAD_REQUEST, "invalid_filter", Some(err.message.clone()))
             },
-            _ => {
-                HttpResponse::InternalServerError().finish()
-            }
+            MyError::Invalid(ref errors) => Problem {
+                errors: Some(errors.clone()),
+                ..Problem::new(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed", detail("some fields are invalid"))
+            },
+            MyError::Violation(ref state) => database_problem(Some(state)),
+            MyError::PGError(ref err) => database_problem(err.code()),
+            MyError::PoolError(_) => Problem::new(StatusCode::SERVICE_UNAVAILABLE, "unavailable", detail("the database is unavailable")),
+            MyError::PGMError(_) | MyError::Internal(_) => Problem::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", None),
+        }
+    }
+}
+
+impl ResponseError for MyError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.problem().status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let problem = self.problem();
+        if problem.status >= 500 {
+            log::error!("{}: {:?}", self, self);
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let MyError::Unauthorized = *self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
         }
+        response
+            .content_type("application/problem+json")
+            .json(problem)
+    }
+}
+
+/// Turns the rejections of actix extractors (malformed JSON bodies, paths or query strings) into
+/// problems too; registered through `JsonConfig`, `PathConfig` and `QueryConfig`.
+pub fn extractor_error(err: impl Display, _: &actix_web::HttpRequest) -> actix_web::Error {
+    MyError::BadRequest(err.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MyError;
+    use crate::db::filter::FilterError;
+
+    #[test]
+    fn problems_have_stable_codes() {
+        let problem = MyError::Conflict("an organization must keep at least one owner".to_string()).problem();
+        assert_eq!((problem.status, problem.code, problem.title), (409, "conflict", "Conflict"));
+
+        let problem = MyError::InvalidFilter(FilterError { parameter: "planner_id".to_string(), message: "nope".to_string() }).problem();
+        let body = serde_json::to_value(&problem).unwrap();
+        assert_eq!(body["type"], "about:blank");
+        assert_eq!(body["code"], "invalid_filter");
+        assert_eq!(body["parameter"], "planner_id");
+
+        let body = serde_json::to_value(MyError::NotFound.problem()).unwrap();
+        assert!(body.get("detail").is_none());
+        assert!(body.get("parameter").is_none());
+
+        let body = serde_json::to_value(MyError::Internal("deleted 2 events".to_string()).problem()).unwrap();
+        assert_eq!((body["status"].as_u64(), body["code"].as_str()), (Some(500), Some("internal_error")));
+        assert!(body.get("detail").is_none());
     }
 }
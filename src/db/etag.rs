@@ -0,0 +1,130 @@
+//! Optimistic concurrency for versioned rows.
+//!
+//! The `version` column of a row is served as its strong `ETag`. Writes honour `If-Match`, so a
+//! client editing a stale copy gets a 412 instead of overwriting someone else's change, and reads
+//! honour `If-None-Match` with a 304.
+
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload,
+    http::header::{EntityTag, Header, IfMatch, IfNoneMatch, ETAG},
+    FromRequest,
+    HttpRequest,
+    HttpResponse
+};
+use serde::Serialize;
+
+use crate::db::errors::MyError;
+
+pub fn entity_tag(version: i32) -> EntityTag {
+    EntityTag::new_strong(version.to_string())
+}
+
+/// The conditional headers of a request. Both are optional: without them, requests are served
+/// unconditionally.
+#[derive(Debug, Default)]
+pub struct Preconditions {
+    if_match: Option<IfMatch>,
+    if_none_match: Option<IfNoneMatch>,
+}
+
+impl Preconditions {
+    /// Fails with 412 unless `If-Match` is absent, `*` or names `version`.
+    pub fn check(&self, version: i32) -> Result<(), MyError> {
+        match &self.if_match {
+            None | Some(IfMatch::Any) => Ok(()),
+            Some(IfMatch::Items(tags)) if tags.iter().any(|tag| tag.strong_eq(&entity_tag(version))) => Ok(()),
+            Some(IfMatch::Items(_)) => Err(MyError::PreconditionFailed),
+        }
+    }
+
+    /// Whether `If-None-Match` already names `version`, so that a GET can answer 304.
+    pub fn not_modified(&self, version: i32) -> bool {
+        match &self.if_none_match {
+            None => false,
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&entity_tag(version))),
+        }
+    }
+}
+
+impl FromRequest for Preconditions {
+    type Error = MyError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let parse = || -> Result<Self, MyError> {
+            let invalid = |_| MyError::BadRequest("If-Match and If-None-Match must list entity tags".to_string());
+            Ok(Preconditions {
+                if_match: req.headers().contains_key(IfMatch::name())
+                    .then(|| IfMatch::parse(req)).transpose().map_err(invalid)?,
+                if_none_match: req.headers().contains_key(IfNoneMatch::name())
+                    .then(|| IfNoneMatch::parse(req)).transpose().map_err(invalid)?,
+            })
+        };
+
+        ready(parse())
+    }
+}
+
+/// A 200 carrying `body` and the `ETag` of `version`.
+pub fn tagged(version: i32, body: impl Serialize) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((ETAG, entity_tag(version)))
+        .json(body)
+}
+
+/// Answers a GET whose `If-None-Match` matched `version`, or serves `body` otherwise.
+pub fn conditional(preconditions: &Preconditions, version: i32, body: impl Serialize) -> HttpResponse {
+    if preconditions.not_modified(version) {
+        HttpResponse::NotModified()
+            .insert_header((ETAG, entity_tag(version)))
+            .finish()
+    } else {
+        tagged(version, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header, test::TestRequest, FromRequest};
+
+    use super::Preconditions;
+    use crate::db::errors::MyError;
+
+    async fn preconditions(req: TestRequest) -> Result<Preconditions, MyError> {
+        let (req, mut payload) = req.to_http_parts();
+        Preconditions::from_request(&req, &mut payload).await
+    }
+
+    #[actix_web::test]
+    async fn if_match_needs_the_current_version() {
+        let unconditional = preconditions(TestRequest::default()).await.unwrap();
+        assert!(unconditional.check(3).is_ok());
+        assert!(!unconditional.not_modified(3));
+
+        let stale = preconditions(TestRequest::default().insert_header((header::IF_MATCH, "\"2\""))).await.unwrap();
+        assert!(matches!(stale.check(3), Err(MyError::PreconditionFailed)));
+        assert!(stale.check(2).is_ok());
+
+        let any = preconditions(TestRequest::default().insert_header((header::IF_MATCH, "*"))).await.unwrap();
+        assert!(any.check(3).is_ok());
+
+        // Weak tags never match If-Match, which compares strongly.
+        let weak = preconditions(TestRequest::default().insert_header((header::IF_MATCH, "W/\"3\""))).await.unwrap();
+        assert!(weak.check(3).is_err());
+    }
+
+    #[actix_web::test]
+    async fn if_none_match_compares_weakly() {
+        let cached = preconditions(TestRequest::default().insert_header((header::IF_NONE_MATCH, "W/\"3\", \"4\""))).await.unwrap();
+        assert!(cached.not_modified(3));
+        assert!(cached.not_modified(4));
+        assert!(!cached.not_modified(5));
+
+        // Unquoted tags are not entity tags and match nothing.
+        let unquoted = preconditions(TestRequest::default().insert_header((header::IF_MATCH, "3"))).await;
+        assert!(unquoted.map_or(true, |preconditions| preconditions.check(3).is_err()));
+    }
+}
@@ -0,0 +1,515 @@
+//! Whitelisted filters and sorts for list endpoints, e.g. `?name~=gala&location=Paris&sort=-starts_at`.
+//!
+//! Every query parameter other than `after`, `limit` and `sort` names a field, optionally followed
+//! by an operator character: `name=x` (equals), `name!=x` (differs), `name~=x` (contains, case
+//! insensitive), `starts_at>=x` and `starts_at<=x` (bounds, with `>` and `<` usually percent-encoded).
+//! `sort` lists fields separated by commas, a leading `-` sorting in descending order. Only the
+//! fields a model declares through `Filterable` are accepted, and values always reach the database
+//! as bind parameters.
+
+use std::{
+    cmp::Ordering,
+    future::{ready, Ready},
+    marker::PhantomData,
+};
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tokio_postgres::types::ToSql;
+
+use crate::db::{
+    errors::MyError,
+    models::{Event, Organization, Person},
+    pagination::Pagination,
+};
+
+/// Query parameters that belong to pagination and sorting rather than to filters.
+const RESERVED: &[&str] = &["after", "limit", "sort"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldKind {
+    Text,
+    Timestamp,
+    Boolean,
+}
+
+/// A field clients may filter and sort on, and the column it stands for.
+pub struct FilterField {
+    pub name: &'static str,
+    pub column: &'static str,
+    pub kind: FieldKind,
+}
+
+/// Models that list endpoints may filter; `KEY` is the column their pages are keyed on.
+pub trait Filterable {
+    const TABLE: &'static str;
+    const KEY: &'static str;
+    const FIELDS: &'static [FilterField];
+
+    /// The value of `column`, the key or one of `FIELDS`, for filtering rows outside the database.
+    fn value(&self, column: &str) -> FilterValue;
+}
+
+impl Filterable for Event {
+    const TABLE: &'static str = "event";
+    const KEY: &'static str = "event_id";
+    const FIELDS: &'static [FilterField] = &[
+        FilterField { name: "name", column: "event_name", kind: FieldKind::Text },
+        FilterField { name: "location", column: "event_location", kind: FieldKind::Text },
+        FilterField { name: "description", column: "event_description", kind: FieldKind::Text },
+        FilterField { name: "starts_at", column: "starts_at", kind: FieldKind::Timestamp },
+        FilterField { name: "ends_at", column: "ends_at", kind: FieldKind::Timestamp },
+        FilterField { name: "time_zone", column: "time_zone", kind: FieldKind::Text },
+        FilterField { name: "all_day", column: "all_day", kind: FieldKind::Boolean },
+    ];
+
+    fn value(&self, column: &str) -> FilterValue {
+        match column {
+            "event_name" => FilterValue::Text(self.event_name.clone()),
+            "event_location" => FilterValue::Text(self.event_location.clone()),
+            "event_description" => FilterValue::Text(self.event_description.clone()),
+            "starts_at" => FilterValue::Timestamp(self.starts_at),
+            "ends_at" => FilterValue::Timestamp(self.ends_at),
+            "time_zone" => FilterValue::Text(self.time_zone.clone()),
+            "all_day" => FilterValue::Boolean(self.all_day),
+            _ => FilterValue::Integer(self.event_id.unwrap_or_default()),
+        }
+    }
+}
+
+impl Filterable for Person {
+    const TABLE: &'static str = "person";
+    const KEY: &'static str = "person_id";
+    const FIELDS: &'static [FilterField] = &[
+        FilterField { name: "name", column: "person_name", kind: FieldKind::Text },
+    ];
+
+    fn value(&self, column: &str) -> FilterValue {
+        match column {
+            "person_name" => FilterValue::Text(self.person_name.clone()),
+            _ => FilterValue::Integer(self.person_id.unwrap_or_default()),
+        }
+    }
+}
+
+impl Filterable for Organization {
+    const TABLE: &'static str = "organization";
+    const KEY: &'static str = "organization_id";
+    const FIELDS: &'static [FilterField] = &[
+        FilterField { name: "name", column: "organization_name", kind: FieldKind::Text },
+    ];
+
+    fn value(&self, column: &str) -> FilterValue {
+        match column {
+            "organization_name" => FilterValue::Text(self.organization_name.clone()),
+            _ => FilterValue::Integer(self.organization_id.unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Equals,
+    Differs,
+    Contains,
+    AtLeast,
+    AtMost,
+}
+
+impl Operator {
+    fn from_suffix(suffix: Option<char>) -> Option<Self> {
+        match suffix {
+            None => Some(Operator::Equals),
+            Some('!') => Some(Operator::Differs),
+            Some('~') => Some(Operator::Contains),
+            Some('>') => Some(Operator::AtLeast),
+            Some('<') => Some(Operator::AtMost),
+            _ => None,
+        }
+    }
+
+    fn applies_to(self, kind: FieldKind) -> bool {
+        match self {
+            Operator::Equals | Operator::Differs => true,
+            Operator::Contains => kind == FieldKind::Text,
+            Operator::AtLeast | Operator::AtMost => kind == FieldKind::Timestamp,
+        }
+    }
+
+    /// Whether `value` passes the condition, as the SQL of `sql` would decide in the database.
+    fn holds(self, value: &FilterValue, operand: &FilterValue) -> bool {
+        match (self, value, operand) {
+            (Operator::Contains, FilterValue::Text(text), FilterValue::Text(pattern)) => {
+                text.to_lowercase().contains(&unescape_pattern(pattern).to_lowercase())
+            }
+            (Operator::Equals, value, operand) => value.compare(operand) == Some(Ordering::Equal),
+            (Operator::Differs, value, operand) => matches!(value.compare(operand), Some(Ordering::Less | Ordering::Greater)),
+            (Operator::AtLeast, value, operand) => matches!(value.compare(operand), Some(Ordering::Greater | Ordering::Equal)),
+            (Operator::AtMost, value, operand) => matches!(value.compare(operand), Some(Ordering::Less | Ordering::Equal)),
+            _ => false,
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            Operator::Equals => "=",
+            Operator::Differs => "<>",
+            Operator::Contains => "ILIKE",
+            Operator::AtLeast => ">=",
+            Operator::AtMost => "<=",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterValue {
+    Text(String),
+    Timestamp(DateTime<Utc>),
+    Boolean(bool),
+    Integer(i32),
+    BigInt(i64),
+}
+
+impl FilterValue {
+    fn as_sql(&self) -> &(dyn ToSql + Sync) {
+        match self {
+            FilterValue::Text(value) => value,
+            FilterValue::Timestamp(value) => value,
+            FilterValue::Boolean(value) => value,
+            FilterValue::Integer(value) => value,
+            FilterValue::BigInt(value) => value,
+        }
+    }
+
+    /// Orders values of the same kind; text compares byte-wise, like the `C` collation.
+    fn compare(&self, other: &FilterValue) -> Option<Ordering> {
+        match (self, other) {
+            (FilterValue::Text(a), FilterValue::Text(b)) => Some(a.cmp(b)),
+            (FilterValue::Timestamp(a), FilterValue::Timestamp(b)) => Some(a.cmp(b)),
+            (FilterValue::Boolean(a), FilterValue::Boolean(b)) => Some(a.cmp(b)),
+            (FilterValue::Integer(a), FilterValue::Integer(b)) => Some(a.cmp(b)),
+            (FilterValue::BigInt(a), FilterValue::BigInt(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+/// Returned as the body of a 400 when a filter or sort cannot be used.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FilterError {
+    pub parameter: String,
+    pub message: String,
+}
+
+fn invalid(parameter: &str, message: impl Into<String>) -> FilterError {
+    FilterError { parameter: parameter.to_string(), message: message.into() }
+}
+
+#[derive(Debug, PartialEq)]
+struct Condition {
+    column: &'static str,
+    operator: Operator,
+    value: FilterValue,
+}
+
+/// The filters and sort of a list request, checked against the fields of `E`.
+pub struct Filter<E> {
+    conditions: Vec<Condition>,
+    sort: Vec<(&'static str, bool)>,
+    model: PhantomData<E>,
+}
+
+impl<E> Default for Filter<E> {
+    fn default() -> Self {
+        Filter { conditions: Vec::new(), sort: Vec::new(), model: PhantomData }
+    }
+}
+
+fn field<E: Filterable>(name: &str) -> Option<&'static FilterField> {
+    E::FIELDS.iter().find(|field| field.name == name)
+}
+
+fn parse_value(field: &FilterField, value: &str) -> Option<FilterValue> {
+    match field.kind {
+        FieldKind::Text => Some(FilterValue::Text(value.to_string())),
+        FieldKind::Timestamp => DateTime::parse_from_rfc3339(value)
+            .map(|value| value.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+            })
+            .map(FilterValue::Timestamp),
+        FieldKind::Boolean => value.parse().ok().map(FilterValue::Boolean),
+    }
+}
+
+/// `%`, `_` and `\` are wildcards or the escape character of `ILIKE`, so they are matched literally.
+fn contains_pattern(value: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// The text a `contains_pattern` pattern looks for.
+fn unescape_pattern(pattern: &str) -> String {
+    let inner = pattern.strip_prefix('%').and_then(|inner| inner.strip_suffix('%')).unwrap_or(pattern);
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+impl<E: Filterable> Filter<E> {
+    pub fn parse(query_string: &str) -> Result<Self, FilterError> {
+        let pairs = web::Query::<Vec<(String, String)>>::from_query(query_string)
+            .map_err(|_| invalid("", "the query string is malformed"))?
+            .into_inner();
+
+        let mut filter = Filter::default();
+        for (key, value) in pairs {
+            if key == "sort" {
+                filter.parse_sort(&value)?;
+                continue;
+            }
+            if RESERVED.contains(&key.as_str()) {
+                continue;
+            }
+
+            let (name, suffix) = match key.char_indices().last() {
+                Some((index, c)) if !c.is_alphanumeric() && c != '_' => (&key[..index], Some(c)),
+                _ => (key.as_str(), None),
+            };
+            let field = field::<E>(name).ok_or_else(|| invalid(&key, format!("{} is not a filterable field", name)))?;
+            let operator = Operator::from_suffix(suffix)
+                .filter(|operator| operator.applies_to(field.kind))
+                .ok_or_else(|| invalid(&key, format!("this operator cannot be used on {}", name)))?;
+            let value = parse_value(field, &value)
+                .ok_or_else(|| invalid(&key, format!("{:?} is not a valid value for {}", value, name)))?;
+            let value = match (operator, value) {
+                (Operator::Contains, FilterValue::Text(text)) => FilterValue::Text(contains_pattern(&text)),
+                (_, value) => value,
+            };
+
+            filter.conditions.push(Condition { column: field.column, operator, value });
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_sort(&mut self, value: &str) -> Result<(), FilterError> {
+        for name in value.split(',') {
+            let (name, descending) = match name.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (name.strip_prefix('+').unwrap_or(name), false),
+            };
+            let field = field::<E>(name).ok_or_else(|| invalid("sort", format!("{} is not a sortable field", name)))?;
+            if self.sort.iter().any(|(column, _)| *column == field.column) {
+                return Err(invalid("sort", format!("{} is sorted on twice", name)));
+            }
+            self.sort.push((field.column, descending));
+        }
+        Ok(())
+    }
+
+    /// Turns the filter and the page into SQL whose bind parameters start at `$first_param`.
+    ///
+    /// With a sort, the page starts after the row of the cursor in that order: its sort values are
+    /// read back by subqueries so that cursors stay plain keys. The key breaks ties in either case.
+    pub fn compile(&self, pagination: &Pagination, first_param: usize) -> CompiledFilter {
+        let mut compiled = CompiledFilter { conditions: String::new(), order_by: String::new(), limit: String::new(), values: Vec::new() };
+        let column = |column: &str| format!("{}.{}", E::TABLE, column);
+
+        for condition in self.conditions.iter() {
+            let param = compiled.bind(first_param, condition.value.clone());
+            compiled.conditions.push_str(&format!(" AND {} {} {}", column(condition.column), condition.operator.sql(), param));
+        }
+
+        if self.sort.is_empty() {
+            let param = compiled.bind(first_param, FilterValue::Integer(pagination.after));
+            compiled.conditions.push_str(&format!(" AND {} > {}", column(E::KEY), param));
+        } else if pagination.after > 0 {
+            let param = compiled.bind(first_param, FilterValue::Integer(pagination.after));
+            let anchor = |name: &str| format!("(SELECT anchor.{} FROM {} anchor WHERE anchor.{} = {})", name, E::TABLE, E::KEY, param);
+
+            let mut keys = self.sort.clone();
+            keys.push((E::KEY, false));
+            let alternatives = (0..keys.len())
+                .map(|i| {
+                    let mut terms = keys[..i].iter()
+                        .map(|(name, _)| format!("{} = {}", column(name), anchor(name)))
+                        .collect::<Vec<String>>();
+                    let (name, descending) = keys[i];
+                    terms.push(format!("{} {} {}", column(name), if descending { "<" } else { ">" }, anchor(name)));
+                    format!("({})", terms.join(" AND "))
+                })
+                .collect::<Vec<String>>();
+            compiled.conditions.push_str(&format!(" AND ({})", alternatives.join(" OR ")));
+        }
+
+        let mut order_by = self.sort.iter()
+            .map(|(name, descending)| format!("{}{}", column(name), if *descending { " DESC" } else { "" }))
+            .collect::<Vec<String>>();
+        order_by.push(column(E::KEY));
+        compiled.order_by = order_by.join(", ");
+
+        compiled.limit = compiled.bind(first_param, FilterValue::BigInt(pagination.fetch_limit()));
+
+        compiled
+    }
+}
+
+impl<E: Filterable> Filter<E> {
+    /// Filters, sorts and pages `rows` in memory, the way the SQL of `compile` does in the database.
+    ///
+    /// `anchor` is the row the cursor of `pagination` points at, looked up in the whole table as
+    /// the subqueries of `compile` do; a sorted page whose anchor is gone is empty.
+    pub fn apply(&self, rows: Vec<E>, anchor: Option<&E>, pagination: &Pagination) -> Vec<E> {
+        let mut keys = self.sort.clone();
+        keys.push((E::KEY, false));
+        let order = |a: &E, b: &E| {
+            keys.iter()
+                .map(|(column, descending)| {
+                    let ordering = a.value(column).compare(&b.value(column)).unwrap_or(Ordering::Equal);
+                    if *descending { ordering.reverse() } else { ordering }
+                })
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+
+        let mut rows = rows.into_iter()
+            .filter(|row| self.conditions.iter().all(|condition| condition.operator.holds(&row.value(condition.column), &condition.value)))
+            .filter(|row| {
+                if self.sort.is_empty() {
+                    row.value(E::KEY).compare(&FilterValue::Integer(pagination.after)) == Some(Ordering::Greater)
+                } else if pagination.after > 0 {
+                    anchor.is_some_and(|anchor| order(row, anchor) == Ordering::Greater)
+                } else {
+                    true
+                }
+            })
+            .collect::<Vec<E>>();
+        rows.sort_by(order);
+        rows.truncate(pagination.fetch_limit() as usize);
+        rows
+    }
+}
+
+/// SQL fragments produced by `Filter::compile`: `conditions` is a run of `AND` terms to append to
+/// a `WHERE` clause, and `values` holds the bind parameters they reference, in order.
+pub struct CompiledFilter {
+    pub conditions: String,
+    pub order_by: String,
+    pub limit: String,
+    pub values: Vec<FilterValue>,
+}
+
+impl CompiledFilter {
+    fn bind(&mut self, first_param: usize, value: FilterValue) -> String {
+        self.values.push(value);
+        format!("${}", first_param + self.values.len() - 1)
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = &(dyn ToSql + Sync)> {
+        self.values.iter().map(FilterValue::as_sql)
+    }
+}
+
+impl<E: Filterable> FromRequest for Filter<E> {
+    type Error = MyError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Filter::parse(req.query_string()).map_err(MyError::InvalidFilter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{Filter, FilterValue};
+    use crate::db::{
+        models::{Event, Person},
+        pagination::Pagination,
+    };
+
+    const FIRST_PAGE: Pagination = Pagination { after: 0, limit: 10 };
+
+    #[test]
+    fn filters_become_bind_parameters() {
+        let filter = Filter::<Event>::parse("name~=50%25_off&location=Paris&starts_at>=2022-06-01").unwrap();
+        let compiled = filter.compile(&FIRST_PAGE, 2);
+
+        assert_eq!(
+            compiled.conditions,
+            " AND event.event_name ILIKE $2 AND event.event_location = $3 AND event.starts_at >= $4 AND event.event_id > $5"
+        );
+        assert_eq!(compiled.order_by, "event.event_id");
+        assert_eq!(compiled.limit, "$6");
+        assert_eq!(compiled.values, vec![
+            FilterValue::Text("%50\\%\\_off%".to_string()),
+            FilterValue::Text("Paris".to_string()),
+            FilterValue::Timestamp(Utc.with_ymd_and_hms(2022, 6, 1, 0, 0, 0).unwrap()),
+            FilterValue::Integer(0),
+            FilterValue::BigInt(11),
+        ]);
+    }
+
+    #[test]
+    fn sorted_pages_start_after_the_cursor_row() {
+        let filter = Filter::<Event>::parse("sort=-starts_at,name&limit=5").unwrap();
+
+        let compiled = filter.compile(&FIRST_PAGE, 1);
+        assert_eq!(compiled.conditions, "");
+        assert_eq!(compiled.order_by, "event.starts_at DESC, event.event_name, event.event_id");
+
+        let compiled = filter.compile(&Pagination { after: 7, limit: 10 }, 1);
+        assert!(compiled.conditions.starts_with(
+            " AND ((event.starts_at < (SELECT anchor.starts_at FROM event anchor WHERE anchor.event_id = $1))"
+        ));
+        assert!(compiled.conditions.ends_with("AND event.event_id > (SELECT anchor.event_id FROM event anchor WHERE anchor.event_id = $1)))"));
+        assert_eq!(compiled.values, vec![FilterValue::Integer(7), FilterValue::BigInt(11)]);
+    }
+
+    #[test]
+    fn rows_are_filtered_and_paged_in_memory() {
+        let persons = ["Zoé", "Ana", "anatole", "Bob"].iter().enumerate()
+            .map(|(index, name)| Person { person_id: Some(index as i32 + 1), person_name: name.to_string(), planner_id: None, version: 1 })
+            .collect::<Vec<Person>>();
+        let names = |persons: Vec<Person>| persons.into_iter().map(|person| person.person_name).collect::<Vec<String>>();
+
+        let filter = Filter::<Person>::parse("name~=ana").unwrap();
+        assert_eq!(names(filter.apply(persons.clone(), None, &FIRST_PAGE)), ["Ana", "anatole"]);
+        assert_eq!(names(filter.apply(persons.clone(), None, &Pagination { after: 2, limit: 10 })), ["anatole"]);
+
+        let filter = Filter::<Person>::parse("sort=-name").unwrap();
+        assert_eq!(names(filter.apply(persons.clone(), None, &Pagination { after: 0, limit: 1 })), ["anatole", "Zoé"]);
+        assert_eq!(names(filter.apply(persons.clone(), persons.get(2), &Pagination { after: 3, limit: 10 })), ["Zoé", "Bob", "Ana"]);
+        assert!(filter.apply(persons, None, &Pagination { after: 9, limit: 10 }).is_empty());
+    }
+
+    #[test]
+    fn unknown_fields_operators_and_values_are_rejected() {
+        assert_eq!(Filter::<Person>::parse("location=Paris").err().unwrap().parameter, "location");
+        assert_eq!(Filter::<Person>::parse("name>=a").err().unwrap().parameter, "name>");
+        assert_eq!(Filter::<Event>::parse("starts_at=yesterday").err().unwrap().parameter, "starts_at");
+        assert_eq!(Filter::<Event>::parse("all_day=maybe").err().unwrap().parameter, "all_day");
+        assert_eq!(Filter::<Event>::parse("name;drop=x").err().unwrap().parameter, "name;drop");
+        assert_eq!(Filter::<Event>::parse("sort=event_id").err().unwrap().parameter, "sort");
+        assert_eq!(Filter::<Event>::parse("sort=name,-name").err().unwrap().parameter, "sort");
+        assert!(Filter::<Event>::parse("after=Mg&limit=2&all_day!=true").is_ok());
+    }
+}
@@ -1,252 +1,1409 @@
+use std::sync::OnceLock;
+
 use actix_web::{web, Error, HttpResponse};
-use deadpool_postgres::{Client, Pool};
+use chrono::{DateTime, Utc};
+
+pub mod legacy;
 
 use crate::{
-    db::query, 
+    db::auth::{self, AuthenticatedPerson, TokenClaims, TokenSigner},
+    db::dto::{
+        CreateEvent,
+        CreateOrganization,
+        EventResponse,
+        GuestResponse,
+        GuestRsvp,
+        OccurrenceResponse,
+        OrganizationResponse,
+        PersonResponse,
+        RsvpPage,
+        UpdateAffiliation,
+        UpdateEvent,
+        UpdateOrganization,
+        UpdateParticipation,
+        UpdatePerson
+    },
+    db::ical::{self, ParsedEvent},
+    db::filter::Filter,
+    db::pagination::Pagination,
+    db::permissions,
+    db::recurrence,
+    db::repository::{Repository, Store, Versioned},
+    db::search,
+    db::validation::{FieldErrors, Validate},
     db::errors::MyError, 
+    db::etag::{self, Preconditions},
     db::models::{
         Event, 
+        EventOverride,
+        EventWindow,
+        ImportedEvent,
+        ImportIssue,
+        ImportReport,
+        Invitation,
+        InvitationRequest,
         Plan,
-        Planner,
         Organization,
         Affiliation,
-        Person
+        OrganizationRole,
+        Participation,
+        Person,
+        Registration,
+        Credentials,
+        SearchQuery,
+        SessionToken
     }
 };
 
 pub async fn create_event(
-    event: web::Json<Event>,
-    db_pool: web::Data<Pool>,
+    auth: AuthenticatedPerson,
+    event: web::Json<CreateEvent>,
+    repository: web::Data<dyn Repository>,
 ) -> Result<HttpResponse, Error> {
 
-    let event_info: Event = event.into_inner();
+    let event_info = event.into_inner().validated()?;
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let planner_id = transaction.get_person(auth.person_id).await?
+        .planner_id
+        .ok_or(MyError::NotFound)?;
 
-    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let new_event = transaction.create_event(event_info.into()).await?;
 
-    let new_event = query::create_event(&client, event_info).await?;
+    // The creator's planner holds the event, which makes its owner a member of it.
+    let event_id = new_event.event_id.ok_or(MyError::NotFound)?;
+    transaction.create_plan(Plan { plan_id: None, event_id, planner_id }).await?;
 
-    Ok(HttpResponse::Ok().json(new_event))
+    transaction.commit().await?;
+
+    Ok(etag::tagged(new_event.version, EventResponse::from(new_event)))
+}
+
+pub async fn get_event(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>,
+) -> Result<HttpResponse, MyError> {
+    let event_id = event_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let event = client.get_event(event_id).await?;
+
+    Ok(etag::conditional(&preconditions, event.version, EventResponse::from(event)))
 }
 
 pub async fn modify_event(
-    event: web::Json<Event>,
-    db_pool: web::Data<Pool>,
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    event: web::Json<UpdateEvent>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>,
 ) -> Result<HttpResponse, MyError> {
-    let event_info: Event = event.into_inner();
+    let event_id = event_id.into_inner();
+    let event_patch = event.into_inner().validated()?;
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
 
-    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+    permissions::ensure_event_member(&*transaction, &auth, event_id).await?;
+    preconditions.check(transaction.lock_version(Versioned::Event, event_id).await?)?;
 
-    let modified_event = query::modify_event(&client, event_info).await?;
+    let modified_event = transaction.modify_event(event_id, &event_patch).await?;
 
-    Ok(HttpResponse::Ok().json(modified_event))
+    transaction.commit().await?;
+
+    Ok(etag::tagged(modified_event.version, EventResponse::from(modified_event)))
 
 }
 
 pub async fn delete_event(
-    event: web::Json<Event>,
-    db_pool: web::Data<Pool>,
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>,
 ) -> Result<HttpResponse, MyError> {
-    let event_info = event.into_inner();
+    let event_id = event_id.into_inner();
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    permissions::ensure_event_member(&*transaction, &auth, event_id).await?;
+    preconditions.check(transaction.lock_version(Versioned::Event, event_id).await?)?;
 
-    let nb_deleted_event = query::delete_event(&client, event_info).await?;
+    let nb_deleted_event = transaction.delete_event(event_id).await?;
 
     match nb_deleted_event {
-        0 => Ok(HttpResponse::NotFound().finish()),
-        1 => Ok(HttpResponse::Ok().finish()),
-        _ => Ok(HttpResponse::InternalServerError().finish()),
+        0 => Err(MyError::NotFound),
+        1 => {
+            transaction.commit().await?;
+            Ok(HttpResponse::Ok().finish())
+        }
+        nb => Err(MyError::Internal(format!("deleted {} events", nb))),
     }
 }
 
 pub async fn get_events(
-    person: web::Json<Person>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    filter: Filter<Event>,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+
+    let client = repository.connect().await?;
+
+    let events = client.list_events(auth.person_id, &filter, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page(events, |event| event.event_id).map(EventResponse::from)))
+}
+
+/// A person's schedule is theirs alone, like their calendar.
+pub async fn get_person_events(
+    auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    window: web::Query<EventWindow>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let person_id = person_id.into_inner();
+    let window = window.into_inner();
+    permissions::ensure_self(&auth, person_id)?;
+
+    if let (Some(from), Some(to)) = (window.from, window.to) {
+        if to < from {
+            return Err(MyError::BadRequest("to must not be before from".to_string()));
+        }
+    }
+
+    let client = repository.connect().await?;
+
+    let occurrences = client.get_occurrences_between(Some(person_id), window.from, window.to).await?;
+
+    let occurrences = occurrences.into_iter().map(OccurrenceResponse::from).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(occurrences))
+}
+
+async fn planner_calendar(store: &dyn Store, name: &str, planner_id: Option<i32>) -> Result<HttpResponse, MyError> {
+    let planner_id = planner_id.ok_or(MyError::NotFound)?;
+
+    let events = store.get_planner_events(planner_id).await?;
+
+    let event_ids = events.iter().filter_map(|event| event.event_id).collect::<Vec<i32>>();
+    let overrides = store.get_event_overrides(&event_ids).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(ical::render_calendar(name, &events, &overrides, Utc::now())))
+}
+
+/// A person's calendar is theirs alone.
+pub async fn get_person_calendar(
+    auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let person_id = person_id.into_inner();
+    permissions::ensure_self(&auth, person_id)?;
+
+    let client = repository.connect().await?;
+
+    let person = client.get_person(person_id).await?;
+
+    planner_calendar(&*client, &person.person_name, person.planner_id).await
+}
+
+pub async fn get_organization_calendar(
+    auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let organization_id = organization_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_organization_member(&*client, &auth, organization_id).await?;
+
+    let organization = client.get_organization(organization_id).await?;
+
+    planner_calendar(&*client, &organization.organization_name, organization.planner_id).await
+}
+
+/// The event an imported UID stands for: one of ours that was exported, or one imported into the
+/// planner before. Exported UIDs of events the caller is no member of are taken as foreign ones.
+async fn find_imported_event(store: &dyn Store, auth: &AuthenticatedPerson, planner_id: i32, uid: &str) -> Result<Option<Event>, MyError> {
+    if let Some(event_id) = ical::exported_event_id(uid) {
+        if store.is_event_member(auth.person_id, event_id).await? {
+            return store.get_event(event_id).await.map(Some);
+        }
+    }
+    store.get_event_by_ical_uid(planner_id, uid).await
+}
+
+async fn import_event(store: &dyn Store, auth: &AuthenticatedPerson, planner_id: i32, parsed: ParsedEvent, report: &mut ImportReport) -> Result<(), MyError> {
+    let uid = parsed.uid;
+
+    let event_info = match parsed.event.validated() {
+        Ok(event_info) => event_info,
+        Err(err) => {
+            report.failed.push(ImportIssue { uid: Some(uid), reason: err.to_string() });
+            return Ok(());
+        }
+    };
+
+    let event_id = match find_imported_event(store, auth, planner_id, &uid).await? {
+        Some(event) => event.event_id.unwrap_or_default(),
+        None if parsed.cancelled => {
+            report.skipped.push(ImportIssue { uid: Some(uid), reason: "cancelled".to_string() });
+            return Ok(());
+        }
+        None => match store.create_imported_event(event_info.into(), planner_id, &uid).await {
+            Ok(event) => event.event_id.unwrap_or_default(),
+            Err(err) => {
+                report.failed.push(ImportIssue { uid: Some(uid), reason: err.to_string() });
+                return Ok(());
+            }
+        },
+    };
+
+    match store.ensure_plan(Plan { plan_id: None, event_id, planner_id }).await? {
+        Some(_) => report.created.push(ImportedEvent { uid, event_id, recurrence_id: None }),
+        None => report.skipped.push(ImportIssue { uid: Some(uid), reason: "already imported".to_string() }),
+    }
+
+    Ok(())
+}
+
+async fn import_override(store: &dyn Store, auth: &AuthenticatedPerson, planner_id: i32, parsed: ParsedEvent, report: &mut ImportReport) -> Result<(), MyError> {
+    let uid = parsed.uid;
+    let recurrence_id = parsed.recurrence_id.unwrap_or(parsed.event.starts_at);
+
+    let event = match find_imported_event(store, auth, planner_id, &uid).await? {
+        Some(event) => event,
+        None => {
+            report.failed.push(ImportIssue { uid: Some(uid), reason: "no recurring event for RECURRENCE-ID".to_string() });
+            return Ok(());
+        }
+    };
+    let event_id = event.event_id.unwrap_or_default();
+
+    if !store.is_event_member(auth.person_id, event_id).await? {
+        report.failed.push(ImportIssue { uid: Some(uid), reason: "uid belongs to another planner".to_string() });
+        return Ok(());
+    }
+
+    if !recurrence::is_occurrence(&event, recurrence_id)? {
+        report.failed.push(ImportIssue { uid: Some(uid), reason: "RECURRENCE-ID is not an occurrence".to_string() });
+        return Ok(());
+    }
+
+    let existing = store.get_event_overrides(&[event_id]).await?;
+    if existing.iter().any(|o| o.occurrence_starts_at == recurrence_id) {
+        report.skipped.push(ImportIssue { uid: Some(uid), reason: "already imported".to_string() });
+        return Ok(());
+    }
+
+    let override_info = EventOverride {
+        override_id: None,
+        event_id,
+        occurrence_starts_at: recurrence_id,
+        cancelled: parsed.cancelled,
+        event_name: Some(parsed.event.event_name),
+        event_location: Some(parsed.event.event_location),
+        event_description: Some(parsed.event.event_description),
+        starts_at: Some(parsed.event.starts_at),
+        ends_at: Some(parsed.event.ends_at),
+    };
+    let override_info = match override_info.validated() {
+        Ok(override_info) => override_info,
+        Err(err) => {
+            report.failed.push(ImportIssue { uid: Some(uid), reason: err.to_string() });
+            return Ok(());
+        }
+    };
+    match store.create_event_override(override_info).await {
+        Ok(_) => report.created.push(ImportedEvent { uid, event_id, recurrence_id: Some(recurrence_id) }),
+        Err(err) => report.failed.push(ImportIssue { uid: Some(uid), reason: err.to_string() }),
+    }
+
+    Ok(())
+}
+
+pub async fn import_calendar(
+    auth: AuthenticatedPerson,
+    planner_id: web::Path<i32>,
+    body: String,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let planner_id = planner_id.into_inner();
+
+    let mut client = repository.connect().await?;
+
+    // Unknown planners are forbidden too, so that ids cannot be probed.
+    permissions::ensure_planner_member(&*client, &auth, planner_id).await?;
+
+    let (parsed, failed) = ical::parse_calendar(&body);
+    let mut report = ImportReport { failed, ..Default::default() };
+
+    // Recurring events have to exist before the occurrences that override them.
+    let (overrides, events): (Vec<ParsedEvent>, Vec<ParsedEvent>) = parsed
+        .into_iter()
+        .partition(|parsed| parsed.recurrence_id.is_some());
+
+    // Every entry is imported in a transaction of its own, so that an event never lands without its
+    // plan. An entry that fails is reported and leaves nothing behind: committing the aborted
+    // transaction rolls it back.
+    for parsed in events {
+        let transaction = client.transaction().await?;
+        import_event(&*transaction, &auth, planner_id, parsed, &mut report).await?;
+        transaction.commit().await?;
+    }
+    for parsed in overrides {
+        let transaction = client.transaction().await?;
+        import_override(&*transaction, &auth, planner_id, parsed, &mut report).await?;
+        transaction.commit().await?;
+    }
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub async fn get_event_overrides(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
+    let event_id = event_id.into_inner();
 
-    let person_info = person.into_inner();
+    let client = repository.connect().await?;
 
-    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
 
-    let events = query::get_events(&client, person_info).await?;
+    let overrides = client.list_event_overrides(event_id, pagination.after, pagination.fetch_limit()).await?;
 
-    Ok(HttpResponse::Ok().json(events))
+    Ok(HttpResponse::Ok().json(pagination.page(overrides, |event_override| event_override.override_id)))
+}
+
+pub async fn get_event_override(
+    auth: AuthenticatedPerson,
+    path: web::Path<(i32, DateTime<Utc>)>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let (event_id, occurrence_starts_at) = path.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let event_override = client.get_event_override(event_id, occurrence_starts_at).await?;
+
+    Ok(HttpResponse::Ok().json(event_override))
+}
+
+pub async fn create_event_override(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    event_override: web::Json<EventOverride>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let override_info = EventOverride { event_id: event_id.into_inner(), ..event_override.into_inner() }.validated()?;
+
+    if let (Some(starts_at), Some(ends_at)) = (override_info.starts_at, override_info.ends_at) {
+        if ends_at < starts_at {
+            return Err(MyError::BadRequest("ends_at must not be before starts_at".to_string()));
+        }
+    }
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, override_info.event_id).await?;
+
+    let event = client.get_event(override_info.event_id).await?;
+    if !recurrence::is_occurrence(&event, override_info.occurrence_starts_at)? {
+        return Err(MyError::NotFound);
+    }
+
+    let new_override = client.create_event_override(override_info).await?;
+
+    Ok(HttpResponse::Ok().json(new_override))
+}
+
+pub async fn delete_event_override(
+    auth: AuthenticatedPerson,
+    path: web::Path<(i32, DateTime<Utc>)>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let (event_id, occurrence_starts_at) = path.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let nb_deleted_override = client.delete_event_override(event_id, occurrence_starts_at).await?;
+
+    match nb_deleted_override {
+        0 => Err(MyError::NotFound),
+        1 => Ok(HttpResponse::Ok().finish()),
+        nb => Err(MyError::Internal(format!("deleted {} overrides", nb))),
+    }
 }
 
 pub async fn create_plan(
+    auth: AuthenticatedPerson,
     plan: web::Json<Plan>,
-    db_pool: web::Data<Pool>
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let plan_info = plan.into_inner();
+    let plan_info = plan.into_inner().validated()?;
+
+    let client = repository.connect().await?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    // Planning an event makes the planner's members its members, so both sides must be ours.
+    permissions::ensure_event_member(&*client, &auth, plan_info.event_id).await?;
+    permissions::ensure_planner_member(&*client, &auth, plan_info.planner_id).await?;
 
-    let new_plan = query::create_plan(&client, plan_info).await?;
+    let new_plan = client.create_plan(plan_info).await?;
 
     Ok(HttpResponse::Ok().json(new_plan))
 }
 
+pub async fn get_plans(
+    auth: AuthenticatedPerson,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let plans = client.list_plans(auth.person_id, pagination.after, pagination.fetch_limit()).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page(plans, |plan| plan.plan_id)))
+}
+
+pub async fn get_plan(
+    auth: AuthenticatedPerson,
+    plan_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let plan = permissions::hide_missing(client.get_plan(plan_id.into_inner()).await)?;
+    permissions::ensure_planner_member(&*client, &auth, plan.planner_id).await?;
+
+    Ok(HttpResponse::Ok().json(plan))
+}
+
 pub async fn delete_plan(
-    plan: web::Json<Plan>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    plan_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let plan_info = plan.into_inner();
+    let plan_id = plan_id.into_inner();
+
+    let client = repository.connect().await?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let plan = client.get_plan(plan_id).await?;
+    permissions::ensure_planner_member(&*client, &auth, plan.planner_id).await?;
 
-    let nb_delete_plan = query::delete_plan(&client, plan_info).await?;
+    let nb_delete_plan = client.delete_plan(plan_id).await?;
 
     match nb_delete_plan {
-        0 => Ok(HttpResponse::NotFound().finish()),
+        0 => Err(MyError::NotFound),
         1 => Ok(HttpResponse::Ok().finish()),
-        _ => Ok(HttpResponse::InternalServerError().finish())
+        nb => Err(MyError::Internal(format!("deleted {} plans", nb))),
     }
 }
 
-pub async fn create_planner(
-    db_pool: web::Data<Pool>
+pub async fn get_planners(
+    auth: AuthenticatedPerson,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let planners = client.list_planners(auth.person_id, pagination.after, pagination.fetch_limit()).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page(planners, |planner| Some(planner.planner_id))))
+}
+
+pub async fn get_planner(
+    auth: AuthenticatedPerson,
+    planner_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
+    let planner_id = planner_id.into_inner();
+
+    let client = repository.connect().await?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    permissions::ensure_planner_member(&*client, &auth, planner_id).await?;
 
-    let new_planner = query::create_planner(&client).await?;
+    let planner = client.get_planner(planner_id).await?;
 
-    Ok(HttpResponse::Ok().json(new_planner))
+    Ok(HttpResponse::Ok().json(planner))
 }
 
 pub async fn delete_planner(
-    planner: web::Json<Planner>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    planner_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let planner_info = planner.into_inner();
+    let planner_id = planner_id.into_inner();
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let client = repository.connect().await?;
 
-    let nb_delete_planner = query::delete_planner(&client, planner_info).await?;
+    permissions::ensure_planner_member(&*client, &auth, planner_id).await?;
+
+    let nb_delete_planner = client.delete_planner(planner_id).await?;
 
     match nb_delete_planner {
-        0 => Ok(HttpResponse::NotFound().finish()),
+        0 => Err(MyError::NotFound),
         1 => Ok(HttpResponse::Ok().finish()),
-        _ => Ok(HttpResponse::InternalServerError().finish())
+        nb => Err(MyError::Internal(format!("deleted {} planners", nb))),
     }
 }
 
-pub async fn create_person(
-    person: web::Json<Person>,
-    db_pool: web::Data<Pool>
+pub async fn get_persons(
+    _auth: AuthenticatedPerson,
+    filter: Filter<Person>,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
 
-    let person_inf0 = person.into_inner();
-
-    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let persons = client.list_persons(&filter, &pagination).await?;
 
-    let planner = query::create_planner(&client).await?;
+    Ok(HttpResponse::Ok().json(pagination.page(persons, |person| person.person_id).map(PersonResponse::from)))
+}
 
-    let person_info = Person {
-        person_id: None, 
-        person_name: person_inf0.person_name, 
-        planner_id: Some(planner.planner_id)
-    };
+pub async fn get_person(
+    _auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
 
-    let person = query::create_person(&client, person_info).await?;
+    let person = client.get_person(person_id.into_inner()).await?;
 
-    Ok(HttpResponse::Ok().json(person))
+    Ok(etag::conditional(&preconditions, person.version, PersonResponse::from(person)))
 }
 
 pub async fn modify_person(
-    person: web::Json<Person>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    person: web::Json<UpdatePerson>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let person_info = person.into_inner();
+    let person_id = person_id.into_inner();
+    permissions::ensure_self(&auth, person_id)?;
+
+    let person_patch = person.into_inner().validated()?;
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    preconditions.check(transaction.lock_version(Versioned::Person, person_id).await?)?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let person = transaction.modify_person(person_id, &person_patch).await?;
 
-    let person = query::modify_person(&client, person_info).await?;
+    transaction.commit().await?;
 
-    Ok(HttpResponse::Ok().json(person))
+    Ok(etag::tagged(person.version, PersonResponse::from(person)))
 }
 
 pub async fn delete_person(
-    person: web::Json<Person>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let person_info = person.into_inner();
+    let person_id = person_id.into_inner();
+    permissions::ensure_self(&auth, person_id)?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
 
-    let nb_deleted_person = query::delete_person(&client, person_info).await?;
+    preconditions.check(transaction.lock_version(Versioned::Person, person_id).await?)?;
 
-    match nb_deleted_person {
-        0 => Ok(HttpResponse::NotFound().finish()),
-        1 => Ok(HttpResponse::Ok().finish()),
-        _ => Ok(HttpResponse::InternalServerError().finish())
+    let person = transaction.get_person(person_id).await?;
+
+    let nb_deleted_person = transaction.delete_person(person_id).await?;
+    if nb_deleted_person != 1 {
+        return Err(MyError::Internal(format!("deleted {} persons", nb_deleted_person)));
+    }
+
+    // The person's planner goes with them, and its plans with it.
+    if let Some(planner_id) = person.planner_id {
+        transaction.delete_unused_planner(planner_id).await?;
     }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
 pub async fn create_affiliation(
+    auth: AuthenticatedPerson,
     affiliation: web::Json<Affiliation>,
-    db_pool: web::Data<Pool>
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
+    let affiliation_info = affiliation.into_inner().validated()?;
+
+    let client = repository.connect().await?;
+
+    let caller_role = permissions::ensure_organization_manager(&*client, &auth, affiliation_info.organization_id).await?;
+    permissions::ensure_may_assign(caller_role, affiliation_info.role)?;
+
+    let new_affiliation = client.create_affiliation(affiliation_info).await?;
+
+    Ok(etag::tagged(new_affiliation.version, new_affiliation))
+}
+
+pub async fn get_affiliations(
+    auth: AuthenticatedPerson,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let affiliations = client.list_affiliations(auth.person_id, pagination.after, pagination.fetch_limit()).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page(affiliations, |affiliation| affiliation.affiliation_id)))
+}
+
+/// Visible to the affiliated person and to the members of the organization.
+pub async fn get_affiliation(
+    auth: AuthenticatedPerson,
+    affiliation_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let affiliation = permissions::hide_missing(client.get_affiliation(affiliation_id.into_inner()).await)?;
+    if affiliation.person_id != auth.person_id {
+        permissions::ensure_organization_member(&*client, &auth, affiliation.organization_id).await?;
+    }
+
+    Ok(etag::conditional(&preconditions, affiliation.version, affiliation))
+}
+
+pub async fn modify_affiliation(
+    auth: AuthenticatedPerson,
+    affiliation_id: web::Path<i32>,
+    affiliation: web::Json<UpdateAffiliation>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let affiliation_id = affiliation_id.into_inner();
     let affiliation_info = affiliation.into_inner();
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let version = transaction.lock_version(Versioned::Affiliation, affiliation_id).await?;
+    let affiliation = transaction.get_affiliation(affiliation_id).await?;
+    let caller_role = permissions::ensure_organization_manager(&*transaction, &auth, affiliation.organization_id).await?;
+    permissions::ensure_may_assign(caller_role, affiliation.role)?;
+    permissions::ensure_may_assign(caller_role, affiliation_info.role)?;
+    preconditions.check(version)?;
+
+    let affiliation = transaction.modify_affiliation(affiliation_id, affiliation_info.role).await?;
+
+    transaction.commit().await?;
+
+    Ok(etag::tagged(affiliation.version, affiliation))
+}
+
+pub async fn get_organization_members(
+    auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let organization_id = organization_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_organization_member(&*client, &auth, organization_id).await?;
+
+    let members = client.get_organization_members(organization_id).await?;
+
+    Ok(HttpResponse::Ok().json(members))
+}
 
-    let new_affiliation = query::create_affiliation(&client, affiliation_info).await?;
+pub async fn get_person_organizations(
+    auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let person_id = person_id.into_inner();
+    permissions::ensure_self(&auth, person_id)?;
+
+    let client = repository.connect().await?;
+
+    let organizations = client.get_person_organizations(person_id).await?;
 
-    Ok(HttpResponse::Ok().json(new_affiliation))
+    Ok(HttpResponse::Ok().json(organizations))
 }
 
 pub async fn delete_affiliation(
-    affiliation: web::Json<Affiliation>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    affiliation_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let affiliation_info = affiliation.into_inner();
+    let affiliation_id = affiliation_id.into_inner();
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    // Members may leave on their own; anyone else has to be removed by an owner or admin.
+    let version = transaction.lock_version(Versioned::Affiliation, affiliation_id).await?;
+    let affiliation = transaction.get_affiliation(affiliation_id).await?;
+    if affiliation.person_id != auth.person_id {
+        let caller_role = permissions::ensure_organization_manager(&*transaction, &auth, affiliation.organization_id).await?;
+        permissions::ensure_may_assign(caller_role, affiliation.role)?;
+    }
+    preconditions.check(version)?;
 
-    let nb_delete_affiliation = query::delete_affiliation(&client, affiliation_info).await?;
+    let nb_delete_affiliation = transaction.delete_affiliation(affiliation_id).await?;
 
     match nb_delete_affiliation {
-        0 => Ok(HttpResponse::NotFound().finish()),
-        1 => Ok(HttpResponse::Ok().finish()),
-        _ => Ok(HttpResponse::InternalServerError().finish())
+        0 => Err(MyError::NotFound),
+        1 => {
+            transaction.commit().await?;
+            Ok(HttpResponse::Ok().finish())
+        }
+        nb => Err(MyError::Internal(format!("deleted {} affiliations", nb))),
+    }
+}
+
+pub async fn create_participation(
+    auth: AuthenticatedPerson,
+    participation: web::Json<Participation>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let participation_info = participation.into_inner().validated()?;
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, participation_info.event_id).await?;
+
+    let new_participation = client.create_participation(participation_info).await?;
+
+    Ok(etag::tagged(new_participation.version, new_participation))
+}
+
+pub async fn get_participations(
+    auth: AuthenticatedPerson,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let participations = client.list_participations(auth.person_id, pagination.after, pagination.fetch_limit()).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page(participations, |participation| participation.participation_id)))
+}
+
+/// Visible to the participant and to the members of the event.
+pub async fn get_participation(
+    auth: AuthenticatedPerson,
+    participation_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let participation = permissions::hide_missing(client.get_participation(participation_id.into_inner()).await)?;
+    if participation.person_id != auth.person_id {
+        permissions::ensure_event_member(&*client, &auth, participation.event_id).await?;
+    }
+
+    Ok(etag::conditional(&preconditions, participation.version, participation))
+}
+
+pub async fn modify_participation(
+    auth: AuthenticatedPerson,
+    participation_id: web::Path<i32>,
+    participation: web::Json<UpdateParticipation>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let participation_id = participation_id.into_inner();
+    let participation_info = participation.into_inner();
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let version = transaction.lock_version(Versioned::Participation, participation_id).await?;
+
+    // Only the participant answers; anyone else gets the same 404 as for a missing participation.
+    let participation = transaction.get_participation(participation_id).await?;
+    if participation.person_id != auth.person_id {
+        return Err(MyError::NotFound);
+    }
+    preconditions.check(version)?;
+
+    let participation = transaction.modify_participation(participation_id, auth.person_id, participation_info.rsvp_status).await?;
+
+    transaction.commit().await?;
+
+    Ok(etag::tagged(participation.version, participation))
+}
+
+pub async fn get_event_participants(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event_id = event_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let participants = client.get_event_participants(event_id).await?;
+
+    Ok(HttpResponse::Ok().json(participants))
+}
+
+pub async fn get_person_participations(
+    auth: AuthenticatedPerson,
+    person_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let person_id = person_id.into_inner();
+    permissions::ensure_self(&auth, person_id)?;
+
+    let client = repository.connect().await?;
+
+    let participations = client.get_person_participations(person_id).await?;
+
+    Ok(HttpResponse::Ok().json(participations))
+}
+
+pub async fn delete_participation(
+    auth: AuthenticatedPerson,
+    participation_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let participation_id = participation_id.into_inner();
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let version = transaction.lock_version(Versioned::Participation, participation_id).await?;
+    let participation = transaction.get_participation(participation_id).await?;
+    if participation.person_id != auth.person_id {
+        permissions::ensure_event_member(&*transaction, &auth, participation.event_id).await?;
+    }
+    preconditions.check(version)?;
+
+    let nb_deleted_participation = transaction.delete_participation(participation_id).await?;
+
+    match nb_deleted_participation {
+        0 => Err(MyError::NotFound),
+        1 => {
+            transaction.commit().await?;
+            Ok(HttpResponse::Ok().finish())
+        }
+        nb => Err(MyError::Internal(format!("deleted {} participations", nb))),
     }
 }
 
 pub async fn create_organization(
-    organization: web::Json<Organization>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    organization: web::Json<CreateOrganization>,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let organization_info = organization.into_inner();
+    let organization_info = organization.into_inner().validated()?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
 
-    let planner = query::create_planner(&client).await?;
+    let planner = transaction.create_planner().await?;
 
-    let organization_info = Organization { 
-        organization_id: None, 
-        organization_name: organization_info.organization_name, 
-        planner_id: Some(planner.planner_id)
+    let new_organization = transaction.create_organization(organization_info.into_organization(planner.planner_id)).await?;
+
+    let affiliation_info = Affiliation {
+        affiliation_id: None,
+        person_id: auth.person_id,
+        organization_id: new_organization.organization_id.ok_or(MyError::NotFound)?,
+        role: OrganizationRole::Owner,
+        version: 1,
     };
+    transaction.create_affiliation(affiliation_info).await?;
+
+    transaction.commit().await?;
+
+    Ok(etag::tagged(new_organization.version, OrganizationResponse::from(new_organization)))
+}
+
+pub async fn get_organizations(
+    _auth: AuthenticatedPerson,
+    filter: Filter<Organization>,
+    pagination: Pagination,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
 
-    let new_organization = query::create_organization(&client, organization_info).await?;
+    let organizations = client.list_organizations(&filter, &pagination).await?;
 
-    Ok(HttpResponse::Ok().json(new_organization))
+    Ok(HttpResponse::Ok().json(pagination.page(organizations, |organization| organization.organization_id).map(OrganizationResponse::from)))
+}
+
+pub async fn get_organization(
+    _auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let organization = client.get_organization(organization_id.into_inner()).await?;
+
+    Ok(etag::conditional(&preconditions, organization.version, OrganizationResponse::from(organization)))
+}
+
+pub async fn modify_organization(
+    auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    organization: web::Json<UpdateOrganization>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let organization_id = organization_id.into_inner();
+    let organization_patch = organization.into_inner().validated()?;
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    permissions::ensure_organization_manager(&*transaction, &auth, organization_id).await?;
+    preconditions.check(transaction.lock_version(Versioned::Organization, organization_id).await?)?;
+
+    let organization = transaction.modify_organization(organization_id, &organization_patch).await?;
+
+    transaction.commit().await?;
+
+    Ok(etag::tagged(organization.version, OrganizationResponse::from(organization)))
 }
 
 pub async fn delete_organization(
-    organization: web::Json<Organization>,
-    db_pool: web::Data<Pool>
+    auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let organization_id = organization_id.into_inner();
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    permissions::ensure_organization_owner(&*transaction, &auth, organization_id).await?;
+    preconditions.check(transaction.lock_version(Versioned::Organization, organization_id).await?)?;
+
+    let organization = transaction.get_organization(organization_id).await?;
+
+    let nb_delete_organization = transaction.delete_organization(organization_id).await?;
+    if nb_delete_organization != 1 {
+        return Err(MyError::Internal(format!("deleted {} organizations", nb_delete_organization)));
+    }
+
+    // Like a person's, the organization's planner and plans go with it.
+    if let Some(planner_id) = organization.planner_id {
+        transaction.delete_unused_planner(planner_id).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// An invitation from the caller as requested, to be pointed at its event or organization.
+fn new_invitation(request: &InvitationRequest, auth: &AuthenticatedPerson) -> Invitation {
+    Invitation {
+        invitation_id: None,
+        token: auth::random_token(),
+        event_id: None,
+        organization_id: None,
+        rsvp_status: None,
+        role: None,
+        expires_at: request.expires_at.unwrap_or_else(|| auth::invitation_expiry(Utc::now())),
+        max_uses: request.max_uses,
+        uses: 0,
+        created_by: auth.person_id,
+        revoked_at: None,
+    }
+}
+
+pub async fn create_event_invitation(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    invitation: web::Json<InvitationRequest>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event_id = event_id.into_inner();
+    let request = invitation.into_inner().validated()?;
+    if request.role.is_some() {
+        return Err(MyError::Invalid(FieldErrors::single("role", "only applies to organization invitations")));
+    }
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let invitation = Invitation {
+        event_id: Some(event_id),
+        rsvp_status: Some(request.rsvp_status.unwrap_or_default()),
+        ..new_invitation(&request, &auth)
+    };
+    let new_invitation = client.create_invitation(invitation).await?;
+
+    Ok(HttpResponse::Ok().json(new_invitation))
+}
+
+pub async fn get_event_invitations(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event_id = event_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let invitations = client.get_event_invitations(event_id).await?;
+
+    Ok(HttpResponse::Ok().json(invitations))
+}
+
+pub async fn create_organization_invitation(
+    auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    invitation: web::Json<InvitationRequest>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let organization_id = organization_id.into_inner();
+    let request = invitation.into_inner().validated()?;
+    if request.rsvp_status.is_some() {
+        return Err(MyError::Invalid(FieldErrors::single("rsvp_status", "only applies to event invitations")));
+    }
+    let role = request.role.unwrap_or_default();
+
+    let client = repository.connect().await?;
+
+    let caller_role = permissions::ensure_organization_manager(&*client, &auth, organization_id).await?;
+    permissions::ensure_may_assign(caller_role, role)?;
+
+    let invitation = Invitation {
+        organization_id: Some(organization_id),
+        role: Some(role),
+        ..new_invitation(&request, &auth)
+    };
+    let new_invitation = client.create_invitation(invitation).await?;
+
+    Ok(HttpResponse::Ok().json(new_invitation))
+}
+
+pub async fn get_organization_invitations(
+    auth: AuthenticatedPerson,
+    organization_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
 ) -> Result<HttpResponse, MyError> {
-    let organization_info = organization.into_inner();
+    let organization_id = organization_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_organization_manager(&*client, &auth, organization_id).await?;
 
-    let client = db_pool.get().await.map_err(MyError::PoolError)?;
+    let invitations = client.get_organization_invitations(organization_id).await?;
 
-    let nb_delete_organization = query::delete_organization(&client, organization_info).await?;
+    Ok(HttpResponse::Ok().json(invitations))
+}
+
+pub async fn revoke_invitation(
+    auth: AuthenticatedPerson,
+    invitation_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let invitation_id = invitation_id.into_inner();
 
-    match nb_delete_organization {
-        0 => Ok(HttpResponse::NotFound().finish()),
+    let client = repository.connect().await?;
+
+    let invitation = client.get_invitation(invitation_id).await?;
+    permissions::ensure_invitation_host(&*client, &auth, &invitation).await?;
+
+    let nb_revoked_invitation = client.revoke_invitation(invitation_id).await?;
+
+    match nb_revoked_invitation {
+        0 => Err(MyError::NotFound),
         1 => Ok(HttpResponse::Ok().finish()),
-        _ => Ok(HttpResponse::InternalServerError().finish())
+        nb => Err(MyError::Internal(format!("revoked {} invitations", nb))),
     }
-}
\ No newline at end of file
+}
+
+/// Joins the caller to the event or organization of the invitation. The use only counts if the
+/// participation or affiliation is created, which fails if the caller already has one.
+pub async fn accept_invitation(
+    auth: AuthenticatedPerson,
+    token: web::Path<String>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let token = token.into_inner();
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let invitation = transaction.use_invitation(&token).await?;
+
+    let response = match (invitation.event_id, invitation.organization_id) {
+        (Some(event_id), _) => {
+            let participation_info = Participation {
+                participation_id: None,
+                event_id,
+                person_id: auth.person_id,
+                rsvp_status: invitation.rsvp_status.unwrap_or_default(),
+                responded_at: None,
+                version: 1,
+            };
+            let participation = transaction.create_participation(participation_info).await?;
+            etag::tagged(participation.version, participation)
+        }
+        (None, Some(organization_id)) => {
+            let affiliation_info = Affiliation {
+                affiliation_id: None,
+                person_id: auth.person_id,
+                organization_id,
+                role: invitation.role.unwrap_or_default(),
+                version: 1,
+            };
+            let affiliation = transaction.create_affiliation(affiliation_info).await?;
+            etag::tagged(affiliation.version, affiliation)
+        }
+        (None, None) => return Err(MyError::NotFound),
+    };
+
+    transaction.commit().await?;
+
+    Ok(response)
+}
+
+/// The public page behind an event invitation; anyone holding its token may read it.
+pub async fn get_rsvp_page(
+    token: web::Path<String>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let invitation = client.get_invitation_by_token(&token.into_inner()).await?;
+    let event_id = invitation.event_id.ok_or(MyError::NotFound)?;
+    let event = client.get_event(event_id).await?;
+
+    Ok(HttpResponse::Ok().json(RsvpPage::new(event, &invitation)))
+}
+
+/// Answers an event invitation without an account. The guest gets a token of their own to change
+/// their answer with; a second answer with the same email is a conflict and uses nothing.
+pub async fn create_guest_rsvp(
+    token: web::Path<String>,
+    rsvp: web::Json<GuestRsvp>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let token = token.into_inner();
+    let rsvp = rsvp.into_inner().validated()?;
+
+    let mut client = repository.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let invitation = transaction.use_invitation(&token).await?;
+    let event_id = invitation.event_id.ok_or(MyError::NotFound)?;
+
+    let guest_info = rsvp.into_guest(event_id, invitation.invitation_id, auth::random_token());
+    let guest = transaction.create_guest(guest_info).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(GuestResponse::with_token(guest)))
+}
+
+pub async fn get_guest_rsvp(
+    guest_token: web::Path<String>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let guest = client.get_guest(&guest_token.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(GuestResponse::with_token(guest)))
+}
+
+/// Replaces the answer of a guest. Once merged into a person, they answer through their
+/// participation instead and the guest token no longer works.
+pub async fn modify_guest_rsvp(
+    guest_token: web::Path<String>,
+    rsvp: web::Json<GuestRsvp>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let rsvp = rsvp.into_inner().validated()?;
+
+    let client = repository.connect().await?;
+
+    let guest = client.get_guest(&guest_token.into_inner()).await?;
+    let guest = client.modify_guest(rsvp.into_guest(guest.event_id, guest.invitation_id, guest.token)).await?;
+
+    Ok(HttpResponse::Ok().json(GuestResponse::with_token(guest)))
+}
+
+/// Turns the answer of a guest into a participation of the caller. Holding the guest token is the
+/// proof of being that guest: the email they gave was never verified.
+pub async fn merge_guest_rsvp(
+    auth: AuthenticatedPerson,
+    guest_token: web::Path<String>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    let participation = client.merge_guest(&guest_token.into_inner(), auth.person_id).await?;
+
+    Ok(etag::tagged(participation.version, participation))
+}
+
+pub async fn get_event_guests(
+    auth: AuthenticatedPerson,
+    event_id: web::Path<i32>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event_id = event_id.into_inner();
+
+    let client = repository.connect().await?;
+
+    permissions::ensure_event_member(&*client, &auth, event_id).await?;
+
+    let guests = client.get_event_guests(event_id).await?
+        .into_iter()
+        .map(GuestResponse::from)
+        .collect::<Vec<GuestResponse>>();
+
+    Ok(HttpResponse::Ok().json(guests))
+}
+
+// Verified against when the email is unknown, so that a failed login takes the same time either way.
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| auth::hash_password("praecipio").unwrap())
+}
+
+pub async fn register(
+    registration: web::Json<Registration>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, Error> {
+    let registration = registration.into_inner().validated()?;
+
+    let mut client = repository.connect().await?;
+
+    let password = registration.password;
+    let password_hash = web::block(move || auth::hash_password(&password)).await??;
+
+    let transaction = client.transaction().await?;
+
+    let planner = transaction.create_planner().await?;
+
+    let person_info = Person {
+        person_id: None,
+        person_name: registration.person_name,
+        planner_id: Some(planner.planner_id),
+        version: 1,
+    };
+
+    let person = transaction.create_person(person_info).await?;
+    let person_id = person.person_id.ok_or(MyError::NotFound)?;
+
+    // A taken email is left to the unique index, which answers 409 even to concurrent sign-ups.
+    transaction.create_account(person_id, &registration.email, &password_hash).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(PersonResponse::from(person)))
+}
+
+pub async fn login(
+    credentials: web::Json<Credentials>,
+    repository: web::Data<dyn Repository>,
+    signer: web::Data<TokenSigner>
+) -> Result<HttpResponse, Error> {
+    let credentials = credentials.into_inner().validated()?;
+
+    let client = repository.connect().await?;
+
+    let account = client.get_account_by_email(&credentials.email).await?;
+
+    let password_hash = account
+        .as_ref()
+        .map_or_else(|| dummy_password_hash().to_string(), |account| account.password_hash.clone());
+    let password = credentials.password;
+    let verified = web::block(move || auth::verify_password(&password, &password_hash)).await?;
+
+    let account = match account {
+        Some(account) if verified => account,
+        _ => return Err(MyError::Unauthorized.into()),
+    };
+
+    let now = Utc::now();
+    let claims = TokenClaims {
+        session_id: auth::new_session_id(),
+        person_id: account.person_id,
+        expires_at: auth::session_expiry(now),
+    };
+
+    client.create_session(&claims.session_id, claims.person_id, claims.expires_at).await?;
+
+    Ok(HttpResponse::Ok().json(SessionToken {
+        token: signer.sign(&claims),
+        person_id: claims.person_id,
+        expires_at: claims.expires_at,
+    }))
+}
+
+pub async fn logout(
+    auth: AuthenticatedPerson,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let client = repository.connect().await?;
+
+    client.revoke_session(&auth.session_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn search(
+    auth: AuthenticatedPerson,
+    search_query: web::Query<SearchQuery>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let search_query = search_query.into_inner();
+
+    let text = search_query.q.trim();
+    if text.is_empty() {
+        return Err(MyError::BadRequest("q must not be empty".to_string()));
+    }
+    if text.chars().count() > search::MAX_SEARCH_LENGTH {
+        return Err(MyError::BadRequest(format!("q must be at most {} characters long", search::MAX_SEARCH_LENGTH)));
+    }
+
+    let limit = search_query.limit.unwrap_or(search::DEFAULT_SEARCH_LIMIT);
+    if !(1..=search::MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(MyError::BadRequest(format!("limit must be between 1 and {}", search::MAX_SEARCH_LIMIT)));
+    }
+
+    let client = repository.connect().await?;
+
+    let results = client.search(auth.person_id, text, limit).await?;
+
+    Ok(HttpResponse::Ok().json(results))
+}
@@ -0,0 +1,220 @@
+//! Deprecated routes that address their resource by an id in the JSON body.
+//!
+//! They only remain for clients written before the `/{id}` routes existed and forward to the
+//! handlers of those routes; `main` marks their responses with a `Deprecation` header.
+
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::db::{
+    auth::AuthenticatedPerson,
+    dto::{UpdateAffiliation, UpdateEvent, UpdateParticipation, UpdatePerson},
+    errors::MyError,
+    etag::Preconditions,
+    handlers,
+    repository::Repository,
+    models::{
+        Affiliation,
+        EventOverride,
+        Participation,
+        Plan,
+        Planner
+    },
+    validation::FieldErrors
+};
+
+/// The old routes take the id alongside the fields of the change.
+#[derive(Deserialize)]
+pub struct EventBody {
+    pub event_id: Option<i32>,
+    #[serde(flatten)]
+    pub event: UpdateEvent,
+}
+
+#[derive(Deserialize)]
+pub struct EventId {
+    pub event_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct PersonBody {
+    pub person_id: Option<i32>,
+    #[serde(flatten)]
+    pub person: UpdatePerson,
+}
+
+#[derive(Deserialize)]
+pub struct PersonId {
+    pub person_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct AffiliationBody {
+    pub affiliation_id: Option<i32>,
+    #[serde(flatten)]
+    pub affiliation: UpdateAffiliation,
+}
+
+#[derive(Deserialize)]
+pub struct ParticipationBody {
+    pub participation_id: Option<i32>,
+    #[serde(flatten)]
+    pub participation: UpdateParticipation,
+}
+
+#[derive(Deserialize)]
+pub struct OrganizationId {
+    pub organization_id: Option<i32>,
+}
+
+fn required(id: Option<i32>, name: &str) -> Result<web::Path<i32>, MyError> {
+    match id {
+        Some(id) if id > 0 => Ok(web::Path::from(id)),
+        Some(_) => Err(FieldErrors::single(name, "must be a positive id").into()),
+        None => Err(FieldErrors::single(name, "is required").into()),
+    }
+}
+
+pub async fn modify_event(
+    auth: AuthenticatedPerson,
+    event: web::Json<EventBody>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event = event.into_inner();
+    let event_id = required(event.event_id, "event_id")?;
+
+    handlers::modify_event(auth, event_id, web::Json(event.event), preconditions, repository).await
+}
+
+pub async fn delete_event(
+    auth: AuthenticatedPerson,
+    event: web::Json<EventId>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event_id = required(event.event_id, "event_id")?;
+
+    handlers::delete_event(auth, event_id, preconditions, repository).await
+}
+
+pub async fn create_event_override(
+    auth: AuthenticatedPerson,
+    event_override: web::Json<EventOverride>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let event_id = web::Path::from(event_override.event_id);
+
+    handlers::create_event_override(auth, event_id, event_override, repository).await
+}
+
+pub async fn delete_event_override(
+    auth: AuthenticatedPerson,
+    event_override: web::Json<EventOverride>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let path = web::Path::from((event_override.event_id, event_override.occurrence_starts_at));
+
+    handlers::delete_event_override(auth, path, repository).await
+}
+
+pub async fn delete_plan(
+    auth: AuthenticatedPerson,
+    plan: web::Json<Plan>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let plan_id = required(plan.plan_id, "plan_id")?;
+
+    handlers::delete_plan(auth, plan_id, repository).await
+}
+
+pub async fn delete_planner(
+    auth: AuthenticatedPerson,
+    planner: web::Json<Planner>,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let planner_id = web::Path::from(planner.planner_id);
+
+    handlers::delete_planner(auth, planner_id, repository).await
+}
+
+/// Without an id in the body, the authenticated person is the one being changed.
+pub async fn modify_person(
+    auth: AuthenticatedPerson,
+    person: web::Json<PersonBody>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let person = person.into_inner();
+    let person_id = web::Path::from(person.person_id.unwrap_or(auth.person_id));
+
+    handlers::modify_person(auth, person_id, web::Json(person.person), preconditions, repository).await
+}
+
+pub async fn delete_person(
+    auth: AuthenticatedPerson,
+    person: web::Json<PersonId>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let person_id = required(person.person_id, "person_id")?;
+
+    handlers::delete_person(auth, person_id, preconditions, repository).await
+}
+
+pub async fn modify_affiliation(
+    auth: AuthenticatedPerson,
+    affiliation: web::Json<AffiliationBody>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let affiliation = affiliation.into_inner();
+    let affiliation_id = required(affiliation.affiliation_id, "affiliation_id")?;
+
+    handlers::modify_affiliation(auth, affiliation_id, web::Json(affiliation.affiliation), preconditions, repository).await
+}
+
+pub async fn delete_affiliation(
+    auth: AuthenticatedPerson,
+    affiliation: web::Json<Affiliation>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let affiliation_id = required(affiliation.affiliation_id, "affiliation_id")?;
+
+    handlers::delete_affiliation(auth, affiliation_id, preconditions, repository).await
+}
+
+pub async fn modify_participation(
+    auth: AuthenticatedPerson,
+    participation: web::Json<ParticipationBody>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let participation = participation.into_inner();
+    let participation_id = required(participation.participation_id, "participation_id")?;
+
+    handlers::modify_participation(auth, participation_id, web::Json(participation.participation), preconditions, repository).await
+}
+
+pub async fn delete_participation(
+    auth: AuthenticatedPerson,
+    participation: web::Json<Participation>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let participation_id = required(participation.participation_id, "participation_id")?;
+
+    handlers::delete_participation(auth, participation_id, preconditions, repository).await
+}
+
+pub async fn delete_organization(
+    auth: AuthenticatedPerson,
+    organization: web::Json<OrganizationId>,
+    preconditions: Preconditions,
+    repository: web::Data<dyn Repository>
+) -> Result<HttpResponse, MyError> {
+    let organization_id = required(organization.organization_id, "organization_id")?;
+
+    handlers::delete_organization(auth, organization_id, preconditions, repository).await
+}
@@ -0,0 +1,732 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetName, Tz};
+
+use crate::db::{
+    dto::CreateEvent,
+    models::{
+        Event,
+        EventOverride,
+        ImportIssue
+    },
+    recurrence
+};
+
+const PRODID: &str = "-//Praecipio//Praecipio Server//EN";
+
+/// Stable identifier of an event across exports, used as the VEVENT UID.
+pub fn event_uid(event_id: i32) -> String {
+    format!("event-{}@praecipio", event_id)
+}
+
+/// The event behind a UID written by `event_uid`, so that re-importing an export finds it again.
+pub fn exported_event_id(uid: &str) -> Option<i32> {
+    uid.strip_prefix("event-")?.strip_suffix("@praecipio")?.parse().ok()
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Content lines are folded at 75 octets without splitting a UTF-8 sequence.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn format_utc(datetime: &DateTime<Utc>) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_local(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn format_date(date: &NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    format!("{}{:02}{:02}", sign, seconds / 3600, (seconds % 3600) / 60)
+}
+
+fn event_tz(event_info: &Event) -> Tz {
+    event_info.time_zone.parse::<Tz>().unwrap_or(Tz::UTC)
+}
+
+// Timed occurrences of a series are written in local time so that clients keep the wall-clock
+// time across DST changes; everything else is written in UTC.
+fn date_time_property(name: &str, event_info: &Event, datetime: &DateTime<Utc>) -> String {
+    let tz = event_tz(event_info);
+    if event_info.all_day {
+        format!("{};VALUE=DATE:{}", name, format_date(&datetime.with_timezone(&tz).date_naive()))
+    } else if recurrence::is_recurring(event_info) && tz != Tz::UTC {
+        format!("{};TZID={}:{}", name, tz.name(), format_local(&datetime.with_timezone(&tz).naive_local()))
+    } else {
+        format!("{}:{}", name, format_utc(datetime))
+    }
+}
+
+fn date_time_list_property(name: &str, event_info: &Event, datetimes: &[DateTime<Utc>]) -> Option<String> {
+    let (first, rest) = datetimes.split_first()?;
+    let mut property = date_time_property(name, event_info, first);
+    for datetime in rest {
+        let value = date_time_property(name, event_info, datetime);
+        let (_, value) = value.rsplit_once(':').unwrap_or_default();
+        property.push(',');
+        property.push_str(value);
+    }
+    Some(property)
+}
+
+fn all_day_end(event_info: &Event, starts_at: &DateTime<Utc>, ends_at: &DateTime<Utc>) -> NaiveDate {
+    let tz = event_tz(event_info);
+    let start = starts_at.with_timezone(&tz).date_naive();
+    let end = ends_at.with_timezone(&tz);
+    // DTEND is exclusive for all-day events: an event ending at midnight ends on that day.
+    let end_date = if end.naive_local().time() == chrono::NaiveTime::MIN {
+        end.date_naive()
+    } else {
+        end.date_naive() + Duration::days(1)
+    };
+    end_date.max(start + Duration::days(1))
+}
+
+fn push_schedule(out: &mut String, event_info: &Event, starts_at: &DateTime<Utc>, ends_at: &DateTime<Utc>) {
+    push_line(out, &date_time_property("DTSTART", event_info, starts_at));
+    if event_info.all_day {
+        let end = all_day_end(event_info, starts_at, ends_at);
+        push_line(out, &format!("DTEND;VALUE=DATE:{}", format_date(&end)));
+    } else {
+        push_line(out, &date_time_property("DTEND", event_info, ends_at));
+    }
+}
+
+fn push_event(out: &mut String, event_info: &Event, overrides: &[&EventOverride], stamp: &DateTime<Utc>) {
+    let event_id = event_info.event_id.unwrap_or_default();
+
+    push_line(out, "BEGIN:VEVENT");
+    push_line(out, &format!("UID:{}", event_uid(event_id)));
+    push_line(out, &format!("DTSTAMP:{}", format_utc(stamp)));
+    push_schedule(out, event_info, &event_info.starts_at, &event_info.ends_at);
+    push_line(out, &format!("SUMMARY:{}", escape_text(&event_info.event_name)));
+    push_line(out, &format!("LOCATION:{}", escape_text(&event_info.event_location)));
+    push_line(out, &format!("DESCRIPTION:{}", escape_text(&event_info.event_description)));
+
+    if let Some(ref rule) = event_info.recurrence_rule {
+        push_line(out, &format!("RRULE:{}", rule.trim_start_matches("RRULE:")));
+    }
+    if let Some(rdate) = date_time_list_property("RDATE", event_info, &event_info.recurrence_dates) {
+        push_line(out, &rdate);
+    }
+
+    let mut exdates = event_info.exception_dates.clone();
+    exdates.extend(overrides.iter().filter(|o| o.cancelled).map(|o| o.occurrence_starts_at));
+    if let Some(exdate) = date_time_list_property("EXDATE", event_info, &exdates) {
+        push_line(out, &exdate);
+    }
+    push_line(out, "END:VEVENT");
+
+    let duration = event_info.ends_at - event_info.starts_at;
+    for event_override in overrides.iter().filter(|o| !o.cancelled) {
+        let starts_at = event_override.starts_at.unwrap_or(event_override.occurrence_starts_at);
+        let ends_at = event_override.ends_at.unwrap_or(starts_at + duration);
+
+        push_line(out, "BEGIN:VEVENT");
+        push_line(out, &format!("UID:{}", event_uid(event_id)));
+        push_line(out, &format!("DTSTAMP:{}", format_utc(stamp)));
+        push_line(out, &date_time_property("RECURRENCE-ID", event_info, &event_override.occurrence_starts_at));
+        push_schedule(out, event_info, &starts_at, &ends_at);
+        push_line(out, &format!(
+            "SUMMARY:{}",
+            escape_text(event_override.event_name.as_ref().unwrap_or(&event_info.event_name))
+        ));
+        push_line(out, &format!(
+            "LOCATION:{}",
+            escape_text(event_override.event_location.as_ref().unwrap_or(&event_info.event_location))
+        ));
+        push_line(out, &format!(
+            "DESCRIPTION:{}",
+            escape_text(event_override.event_description.as_ref().unwrap_or(&event_info.event_description))
+        ));
+        push_line(out, "END:VEVENT");
+    }
+}
+
+// nth weekday of the month in BYDAY form, counting from the end for the last week of the month.
+fn by_day(date: &NaiveDate) -> String {
+    let weekday = match date.weekday() {
+        chrono::Weekday::Mon => "MO",
+        chrono::Weekday::Tue => "TU",
+        chrono::Weekday::Wed => "WE",
+        chrono::Weekday::Thu => "TH",
+        chrono::Weekday::Fri => "FR",
+        chrono::Weekday::Sat => "SA",
+        chrono::Weekday::Sun => "SU",
+    };
+    if (*date + Duration::days(7)).month() != date.month() {
+        format!("-1{}", weekday)
+    } else {
+        format!("{}{}", (date.day() - 1) / 7 + 1, weekday)
+    }
+}
+
+/// Describes the offsets of `tz` as observed during `year`, with yearly rules for DST changes.
+fn push_timezone(out: &mut String, tz: Tz, year: i32) {
+    push_line(out, "BEGIN:VTIMEZONE");
+    push_line(out, &format!("TZID:{}", tz.name()));
+
+    let start = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+    let mut previous = tz.offset_from_utc_datetime(&start.naive_utc());
+    let mut transitions = Vec::new();
+    for hour in 1..(366 * 24) {
+        let instant = start + Duration::hours(hour);
+        let offset = tz.offset_from_utc_datetime(&instant.naive_utc());
+        if offset.fix() != previous.fix() {
+            transitions.push((instant, previous, offset));
+        }
+        previous = offset;
+    }
+
+    if transitions.is_empty() {
+        let offset = format_offset(previous.fix().local_minus_utc());
+        push_line(out, "BEGIN:STANDARD");
+        push_line(out, "DTSTART:19700101T000000");
+        push_line(out, &format!("TZOFFSETFROM:{}", offset));
+        push_line(out, &format!("TZOFFSETTO:{}", offset));
+        push_line(out, &format!("TZNAME:{}", escape_text(previous.abbreviation())));
+        push_line(out, "END:STANDARD");
+    }
+
+    for (instant, from, to) in transitions {
+        let from_seconds = from.fix().local_minus_utc();
+        let to_seconds = to.fix().local_minus_utc();
+        let kind = if to_seconds > from_seconds { "DAYLIGHT" } else { "STANDARD" };
+        let local = instant.naive_utc() + Duration::seconds(from_seconds as i64);
+
+        push_line(out, &format!("BEGIN:{}", kind));
+        push_line(out, &format!("DTSTART:{}", format_local(&local)));
+        push_line(out, &format!("TZOFFSETFROM:{}", format_offset(from_seconds)));
+        push_line(out, &format!("TZOFFSETTO:{}", format_offset(to_seconds)));
+        push_line(out, &format!("RRULE:FREQ=YEARLY;BYMONTH={};BYDAY={}", local.month(), by_day(&local.date())));
+        push_line(out, &format!("TZNAME:{}", escape_text(to.abbreviation())));
+        push_line(out, &format!("END:{}", kind));
+    }
+
+    push_line(out, "END:VTIMEZONE");
+}
+
+/// Renders `events` and their occurrence overrides as an RFC 5545 VCALENDAR.
+pub fn render_calendar(name: &str, events: &[Event], overrides: &[EventOverride], stamp: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, &format!("PRODID:{}", PRODID));
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "METHOD:PUBLISH");
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape_text(name)));
+
+    let mut timezones: Vec<(Tz, i32)> = Vec::new();
+    for event in events.iter().filter(|e| recurrence::is_recurring(e) && !e.all_day) {
+        let tz = event_tz(event);
+        if tz != Tz::UTC && !timezones.iter().any(|(known, _)| *known == tz) {
+            timezones.push((tz, event.starts_at.with_timezone(&tz).year()));
+        }
+    }
+    for (tz, year) in timezones {
+        push_timezone(&mut out, tz, year);
+    }
+
+    for event in events {
+        let event_overrides = overrides
+            .iter()
+            .filter(|o| Some(o.event_id) == event.event_id)
+            .collect::<Vec<&EventOverride>>();
+        push_event(&mut out, event, &event_overrides, &stamp);
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// A VEVENT read from an iCalendar file.
+pub struct ParsedEvent {
+    pub uid: String,
+    pub event: CreateEvent,
+    pub recurrence_id: Option<DateTime<Utc>>,
+    pub cancelled: bool,
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in input.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// Splits on `separator` outside of double-quoted parameter values.
+fn split_unquoted(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&input[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let colon = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?.0;
+
+    let mut head = split_unquoted(&line[..colon], ';').into_iter();
+    let name = head.next()?.trim().to_ascii_uppercase();
+    let params = head
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+
+    Some(Property { name, params, value: line[colon + 1..].to_string() })
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(other) => unescaped.push(other),
+                None => {}
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+fn to_utc(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        // Local times skipped by a DST change are moved past the gap.
+        .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
+fn parse_tz(property: &Property, default_tz: Tz) -> Result<Tz, String> {
+    match property.param("TZID") {
+        Some(tzid) => tzid.parse::<Tz>().map_err(|_| format!("unknown time zone {}", tzid)),
+        None => Ok(default_tz),
+    }
+}
+
+fn parse_value(value: &str, is_date: bool, tz: Tz) -> Result<DateTime<Utc>, String> {
+    let invalid = || format!("invalid date {}", value);
+
+    if is_date || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        return to_utc(tz, date.and_time(chrono::NaiveTime::MIN)).ok_or_else(invalid);
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let datetime = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(Utc.from_utc_datetime(&datetime));
+    }
+
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    to_utc(tz, datetime).ok_or_else(invalid)
+}
+
+fn is_date(property: &Property) -> bool {
+    property.param("VALUE").is_some_and(|value| value.eq_ignore_ascii_case("DATE"))
+}
+
+fn parse_date_time(property: &Property, default_tz: Tz) -> Result<DateTime<Utc>, String> {
+    parse_value(&property.value, is_date(property), parse_tz(property, default_tz)?)
+}
+
+fn parse_date_time_list(property: &Property, default_tz: Tz) -> Result<Vec<DateTime<Utc>>, String> {
+    let tz = parse_tz(property, default_tz)?;
+    property.value
+        .split(',')
+        .map(|value| parse_value(value.trim(), is_date(property), tz))
+        .collect()
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {}", value);
+
+    let (negative, rest) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            _ => {
+                let amount = number.parse::<i64>().map_err(|_| invalid())?;
+                number.clear();
+                duration += match (c, in_time) {
+                    ('W', false) => Duration::weeks(amount),
+                    ('D', false) => Duration::days(amount),
+                    ('H', true) => Duration::hours(amount),
+                    ('M', true) => Duration::minutes(amount),
+                    ('S', true) => Duration::seconds(amount),
+                    _ => return Err(invalid()),
+                };
+            }
+        }
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(if negative { -duration } else { duration })
+}
+
+fn parse_vevent(properties: &[Property], default_tz: Tz) -> Result<ParsedEvent, String> {
+    let find = |name: &str| properties.iter().find(|p| p.name == name);
+    let text = |name: &str| find(name).map(|p| unescape_text(&p.value)).unwrap_or_default();
+
+    let uid = find("UID").map(|p| p.value.trim().to_string()).ok_or("missing UID")?;
+    let dtstart = find("DTSTART").ok_or("missing DTSTART")?;
+
+    let tz = parse_tz(dtstart, default_tz)?;
+    let all_day = is_date(dtstart) || dtstart.value.len() == 8;
+    let starts_at = parse_date_time(dtstart, default_tz)?;
+    let ends_at = match (find("DTEND"), find("DURATION")) {
+        (Some(dtend), _) => parse_date_time(dtend, default_tz)?,
+        (None, Some(duration)) => starts_at + parse_duration(duration.value.trim())?,
+        (None, None) if all_day => starts_at + Duration::days(1),
+        (None, None) => starts_at,
+    };
+
+    let mut recurrence_dates = Vec::new();
+    for rdate in properties.iter().filter(|p| p.name == "RDATE") {
+        recurrence_dates.extend(parse_date_time_list(rdate, default_tz)?);
+    }
+    let mut exception_dates = Vec::new();
+    for exdate in properties.iter().filter(|p| p.name == "EXDATE") {
+        exception_dates.extend(parse_date_time_list(exdate, default_tz)?);
+    }
+
+    let recurrence_id = match find("RECURRENCE-ID") {
+        Some(recurrence_id) => Some(parse_date_time(recurrence_id, default_tz)?),
+        None => None,
+    };
+
+    let event = CreateEvent {
+        event_name: text("SUMMARY"),
+        event_location: text("LOCATION"),
+        event_description: text("DESCRIPTION"),
+        starts_at,
+        ends_at,
+        time_zone: tz.name().to_string(),
+        all_day,
+        recurrence_rule: find("RRULE").map(|p| p.value.trim().to_string()),
+        recurrence_dates,
+        exception_dates,
+    };
+
+    Ok(ParsedEvent {
+        uid,
+        event,
+        recurrence_id,
+        cancelled: find("STATUS").is_some_and(|p| p.value.trim().eq_ignore_ascii_case("CANCELLED")),
+    })
+}
+
+/// Reads every VEVENT of an iCalendar file, along with the ones that could not be parsed.
+pub fn parse_calendar(input: &str) -> (Vec<ParsedEvent>, Vec<ImportIssue>) {
+    let mut events = Vec::new();
+    let mut issues = Vec::new();
+
+    let mut default_tz = Tz::UTC;
+    let mut components: Vec<String> = Vec::new();
+    let mut properties: Vec<Property> = Vec::new();
+    let mut seen_calendar = false;
+
+    for line in unfold(input) {
+        let property = match parse_property(&line) {
+            Some(property) => property,
+            None => continue,
+        };
+
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.trim().to_ascii_uppercase();
+                seen_calendar |= component == "VCALENDAR";
+                if component == "VEVENT" {
+                    properties.clear();
+                }
+                components.push(component);
+            }
+            "END" => {
+                let ended = components.pop();
+                if ended.as_deref() == Some("VEVENT") {
+                    match parse_vevent(&properties, default_tz) {
+                        Ok(event) => events.push(event),
+                        Err(reason) => issues.push(ImportIssue {
+                            uid: properties.iter().find(|p| p.name == "UID").map(|p| p.value.trim().to_string()),
+                            reason,
+                        }),
+                    }
+                }
+            }
+            "X-WR-TIMEZONE" if components.last().map(String::as_str) == Some("VCALENDAR") => {
+                if let Ok(tz) = property.value.trim().parse::<Tz>() {
+                    default_tz = tz;
+                }
+            }
+            _ if components.last().map(String::as_str) == Some("VEVENT") => properties.push(property),
+            _ => {}
+        }
+    }
+
+    if !seen_calendar {
+        issues.push(ImportIssue { uid: None, reason: "not an iCalendar file".to_string() });
+    }
+
+    (events, issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{escape_text, parse_calendar, push_line, render_calendar};
+    use crate::db::models::{Event, EventOverride};
+
+    fn gala() -> Event {
+        Event {
+            event_id: Some(42),
+            event_name: "Gala, edition 2022".to_string(),
+            event_location: "Paris".to_string(),
+            event_description: "Petit anniv;\nvenez nombreux".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 17, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 18, 22, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape_text("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut out = String::new();
+        push_line(&mut out, &format!("DESCRIPTION:{}", "é".repeat(60)));
+        for line in out.split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(out.replace("\r\n ", ""), format!("DESCRIPTION:{}\r\n", "é".repeat(60)));
+    }
+
+    #[test]
+    fn single_event_is_rendered_in_utc() {
+        let stamp = Utc.with_ymd_and_hms(2022, 6, 1, 0, 0, 0).unwrap();
+        let ics = render_calendar("GDVCB", &[gala()], &[], stamp);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:event-42@praecipio\r\n"));
+        assert!(ics.contains("DTSTART:20220618T170000Z\r\n"));
+        assert!(ics.contains("DTEND:20220618T220000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Gala\\, edition 2022\r\n"));
+        assert!(!ics.contains("VTIMEZONE"));
+    }
+
+    #[test]
+    fn all_day_event_uses_dates() {
+        let mut event = gala();
+        event.all_day = true;
+        event.starts_at = Utc.with_ymd_and_hms(2022, 6, 17, 22, 0, 0).unwrap();
+        event.ends_at = Utc.with_ymd_and_hms(2022, 6, 18, 22, 0, 0).unwrap();
+
+        let ics = render_calendar("GDVCB", &[event], &[], Utc::now());
+        assert!(ics.contains("DTSTART;VALUE=DATE:20220618\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20220619\r\n"));
+    }
+
+    #[test]
+    fn series_is_rendered_with_timezone_and_overrides() {
+        let mut event = gala();
+        event.recurrence_rule = Some("FREQ=WEEKLY;COUNT=4".to_string());
+        let overrides = vec![
+            EventOverride {
+                override_id: Some(1),
+                event_id: 42,
+                occurrence_starts_at: Utc.with_ymd_and_hms(2022, 6, 25, 17, 0, 0).unwrap(),
+                cancelled: true,
+                event_name: None,
+                event_location: None,
+                event_description: None,
+                starts_at: None,
+                ends_at: None,
+            },
+            EventOverride {
+                override_id: Some(2),
+                event_id: 42,
+                occurrence_starts_at: Utc.with_ymd_and_hms(2022, 7, 2, 17, 0, 0).unwrap(),
+                cancelled: false,
+                event_name: None,
+                event_location: Some("Lyon".to_string()),
+                event_description: None,
+                starts_at: None,
+                ends_at: None,
+            },
+        ];
+
+        let ics = render_calendar("GDVCB", &[event], &overrides, Utc::now());
+        assert!(ics.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/Paris\r\n"));
+        assert!(ics.contains("RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n"));
+        assert!(ics.contains("RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\n"));
+        assert!(ics.contains("DTSTART;TZID=Europe/Paris:20220618T190000\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;COUNT=4\r\n"));
+        assert!(ics.contains("EXDATE;TZID=Europe/Paris:20220625T190000\r\n"));
+        assert!(ics.contains("RECURRENCE-ID;TZID=Europe/Paris:20220702T190000\r\n"));
+        assert!(ics.contains("LOCATION:Lyon\r\n"));
+        assert_eq!(ics.matches("UID:event-42@praecipio").count(), 2);
+    }
+
+    #[test]
+    fn rendered_calendar_is_parsed_back() {
+        let mut series = gala();
+        series.event_id = Some(43);
+        series.recurrence_rule = Some("FREQ=WEEKLY;COUNT=4".to_string());
+        series.exception_dates = vec![Utc.with_ymd_and_hms(2022, 6, 25, 17, 0, 0).unwrap()];
+
+        let ics = render_calendar("GDVCB", &[gala(), series], &[], Utc::now());
+        let (events, issues) = parse_calendar(&ics);
+
+        assert!(issues.is_empty());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].uid, "event-42@praecipio");
+        assert_eq!(events[0].event.event_name, "Gala, edition 2022");
+        assert_eq!(events[0].event.event_description, "Petit anniv;\nvenez nombreux");
+        assert_eq!(events[0].event.starts_at, gala().starts_at);
+        assert_eq!(events[1].event.time_zone, "Europe/Paris");
+        assert_eq!(events[1].event.starts_at, gala().starts_at);
+        assert_eq!(events[1].event.recurrence_rule.as_deref(), Some("FREQ=WEEKLY;COUNT=4"));
+        assert_eq!(events[1].event.exception_dates, vec![Utc.with_ymd_and_hms(2022, 6, 25, 17, 0, 0).unwrap()]);
+    }
+
+    #[test]
+    fn foreign_calendar_is_parsed() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+X-WR-TIMEZONE:Europe/Paris\r\n\
+BEGIN:VEVENT\r\n\
+UID:abc@example.com\r\n\
+DTSTART;VALUE=DATE:20220714\r\n\
+SUMMARY:F\u{ea}te nationale\r\n\
+BEGIN:VALARM\r\n\
+DESCRIPTION:rappel\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:def@example.com\r\n\
+DTSTART:20220715T200000\r\n\
+DURATION:PT1H30M\r\n\
+DESCRIPTION:une description qui est suffisamment longue pour devoir etre rep\r\n \
+liee sur deux lignes\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:sans uid\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:ghi@example.com\r\n\
+DTSTART;TZID=Mars/Olympus_Mons:20220715T200000\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+        let (events, issues) = parse_calendar(ics);
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].event.all_day);
+        assert_eq!(events[0].event.event_name, "F\u{ea}te nationale");
+        assert_eq!(events[0].event.event_description, "");
+        assert_eq!(events[0].event.starts_at, Utc.with_ymd_and_hms(2022, 7, 13, 22, 0, 0).unwrap());
+        assert_eq!(events[0].event.ends_at, Utc.with_ymd_and_hms(2022, 7, 14, 22, 0, 0).unwrap());
+        assert_eq!(events[1].event.starts_at, Utc.with_ymd_and_hms(2022, 7, 15, 18, 0, 0).unwrap());
+        assert_eq!(events[1].event.ends_at, Utc.with_ymd_and_hms(2022, 7, 15, 19, 30, 0).unwrap());
+        assert!(events[1].event.event_description.ends_with("repliee sur deux lignes"));
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].uid, None);
+        assert_eq!(issues[1].uid.as_deref(), Some("ghi@example.com"));
+    }
+
+    #[test]
+    fn garbage_is_reported() {
+        let (events, issues) = parse_calendar("hello world");
+        assert!(events.is_empty());
+        assert_eq!(issues.len(), 1);
+    }
+}
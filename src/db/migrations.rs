@@ -0,0 +1,198 @@
+use deadpool_postgres::{Client, GenericClient, Transaction};
+
+use crate::db::errors::MyError;
+
+/// A schema change embedded in the binary, identified by its version number.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../../migrations/", $name, "/up.sql")),
+            down: include_str!(concat!("../../migrations/", $name, "/down.sql")),
+        }
+    };
+}
+
+/// Every known migration, in the order they must be applied.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_event_schedule"),
+    migration!(3, "0003_event_period_index"),
+    migration!(4, "0004_event_recurrence"),
+    migration!(5, "0005_event_ical_uid"),
+    migration!(6, "0006_participation_rsvp"),
+    migration!(7, "0007_accounts"),
+    migration!(8, "0008_affiliation_admin"),
+    migration!(9, "0009_affiliation_role"),
+    migration!(10, "0010_search"),
+    migration!(11, "0011_row_version"),
+    migration!(12, "0012_invitations"),
+    migration!(13, "0013_guests"),
+    migration!(14, "0014_event_ical_uid_per_planner"),
+];
+
+// Arbitrary key so that concurrent server instances do not migrate at the same time.
+const MIGRATION_LOCK_KEY: i64 = 0x7072_6165_6369_7069;
+
+/// Takes the migration lock for the rest of the transaction, then creates the bookkeeping table if
+/// this is the first run; creating it before holding the lock would race with other instances.
+async fn lock_migrations(transaction: &Transaction<'_>) -> Result<(), MyError> {
+    transaction
+        .execute("SELECT pg_advisory_xact_lock($1);", &[&MIGRATION_LOCK_KEY])
+        .await
+        .map_err(MyError::PGError)?;
+
+    let _stmt = "CREATE TABLE IF NOT EXISTS schema_migrations (
+        version INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    );";
+
+    transaction.batch_execute(_stmt).await.map_err(MyError::PGError)
+}
+
+/// The applied versions, none if migrations never ran; reading them creates nothing.
+pub async fn applied_versions(client: &Client) -> Result<Vec<i32>, MyError> {
+    let exists = client
+        .query_one("SELECT to_regclass('schema_migrations') IS NOT NULL;", &[])
+        .await
+        .map_err(MyError::PGError)?
+        .get::<_, bool>(0);
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    let _stmt = "SELECT version FROM schema_migrations ORDER BY version;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(&statement, &[])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| row.get::<_, i32>(0))
+        .collect::<Vec<i32>>())
+}
+
+/// Applies every pending migration in a single transaction and returns the ones that ran.
+pub async fn migrate_up(client: &mut Client) -> Result<Vec<&'static Migration>, MyError> {
+    let transaction = client.transaction().await.map_err(MyError::PGError)?;
+    lock_migrations(&transaction).await?;
+
+    let applied = transaction
+        .query("SELECT version FROM schema_migrations;", &[])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| row.get::<_, i32>(0))
+        .collect::<Vec<i32>>();
+
+    let mut ran = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        transaction.batch_execute(migration.up).await.map_err(MyError::PGError)?;
+        transaction
+            .execute(
+                "INSERT INTO schema_migrations(version, name) VALUES($1, $2);",
+                &[&migration.version, &migration.name],
+            )
+            .await
+            .map_err(MyError::PGError)?;
+        ran.push(migration);
+    }
+
+    transaction.commit().await.map_err(MyError::PGError)?;
+
+    Ok(ran)
+}
+
+/// Reverts the `steps` most recently applied migrations and returns the ones that were reverted.
+pub async fn migrate_down(client: &mut Client, steps: usize) -> Result<Vec<&'static Migration>, MyError> {
+    let transaction = client.transaction().await.map_err(MyError::PGError)?;
+    lock_migrations(&transaction).await?;
+
+    let applied = transaction
+        .query("SELECT version FROM schema_migrations ORDER BY version DESC LIMIT $1;", &[&(steps as i64)])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| row.get::<_, i32>(0))
+        .collect::<Vec<i32>>();
+
+    let mut reverted = Vec::new();
+    for version in applied {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == version)
+            .ok_or(MyError::NotFound)?;
+
+        transaction.batch_execute(migration.down).await.map_err(MyError::PGError)?;
+        transaction
+            .execute("DELETE FROM schema_migrations WHERE version = $1;", &[&migration.version])
+            .await
+            .map_err(MyError::PGError)?;
+        reverted.push(migration);
+    }
+
+    transaction.commit().await.map_err(MyError::PGError)?;
+
+    Ok(reverted)
+}
+
+/// Lists every known migration along with whether it has been applied.
+pub async fn status(client: &Client) -> Result<Vec<(&'static Migration, bool)>, MyError> {
+    let applied = applied_versions(client).await?;
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|m| (m, applied.contains(&m.version)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{applied_versions, migrate_down, migrate_up, MIGRATIONS};
+    use crate::db::testing::TestDatabase;
+
+    #[test]
+    fn migrations_are_strictly_ordered() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+    }
+
+    #[test]
+    fn migration_names_start_with_their_version() {
+        for migration in MIGRATIONS {
+            assert!(migration.name.starts_with(&format!("{:04}_", migration.version)));
+        }
+    }
+
+    #[actix_web::test]
+    async fn concurrent_first_runs_migrate_once() {
+        let database = TestDatabase::create().await;
+        let mut first = database.pool().get().await.unwrap();
+        let second = database.pool().get().await.unwrap();
+
+        migrate_down(&mut first, MIGRATIONS.len()).await.unwrap();
+        first.batch_execute("DROP TABLE schema_migrations;").await.unwrap();
+        assert!(applied_versions(&first).await.unwrap().is_empty());
+
+        let runs = [first, second].map(|mut client| actix_web::rt::spawn(async move { migrate_up(&mut client).await.map(|ran| ran.len()) }));
+        let mut ran = Vec::new();
+        for run in runs {
+            ran.push(run.await.unwrap().unwrap());
+        }
+        ran.sort();
+        assert_eq!(ran, [0, MIGRATIONS.len()]);
+
+        let client = database.pool().get().await.unwrap();
+        assert_eq!(applied_versions(&client).await.unwrap().len(), MIGRATIONS.len());
+    }
+}
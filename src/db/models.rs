@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+use postgres_types::{FromSql, ToSql};
 use serde::{
     Deserialize,
     Serialize
@@ -5,30 +7,118 @@ use serde::{
 
 use tokio_pg_mapper_derive::PostgresMapper;
 
-#[derive(Deserialize, PostgresMapper, Serialize)]
+/// A row of `event`. Clients see events through `db::dto` only.
+#[derive(Clone, PostgresMapper)]
 #[pg_mapper(table = "event")]
 pub struct Event {
     pub event_id: Option<i32>,
     pub event_name: String,
     pub event_location: String,
     pub event_description: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub time_zone: String,
+    pub all_day: bool,
+    pub recurrence_rule: Option<String>,
+    pub recurrence_dates: Vec<DateTime<Utc>>,
+    pub exception_dates: Vec<DateTime<Utc>>,
+    pub version: i32,
 }
 
-#[derive(Deserialize, PostgresMapper, Serialize)]
+#[derive(Clone, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "event_override")]
+pub struct EventOverride {
+    pub override_id: Option<i32>,
+    // Taken from the path on `/events/{event_id}/occurrences`.
+    #[serde(default)]
+    pub event_id: i32,
+    pub occurrence_starts_at: DateTime<Utc>,
+    #[serde(default)]
+    pub cancelled: bool,
+    pub event_name: Option<String>,
+    pub event_location: Option<String>,
+    pub event_description: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+pub struct EventOccurrence {
+    pub event: Event,
+    pub occurrence_starts_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ImportedEvent {
+    pub uid: String,
+    pub event_id: i32,
+    pub recurrence_id: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ImportIssue {
+    pub uid: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Default, Serialize)]
+pub struct ImportReport {
+    pub created: Vec<ImportedEvent>,
+    pub skipped: Vec<ImportIssue>,
+    pub failed: Vec<ImportIssue>,
+}
+
+#[derive(Deserialize)]
+pub struct EventWindow {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// A row of `person`. Clients see persons through `db::dto` only.
+#[derive(Clone, PostgresMapper)]
 #[pg_mapper(table = "person")]
 pub struct Person {
     pub person_id: Option<i32>,
     pub person_name: String,
     pub planner_id : Option<i32>,
+    pub version: i32,
 }
 
-#[derive(Deserialize, PostgresMapper, Serialize)]
+#[derive(Clone, PostgresMapper)]
+#[pg_mapper(table = "account")]
+pub struct Account {
+    pub account_id: i32,
+    pub person_id: i32,
+    pub email: String,
+    pub password_hash: String,
+}
+
+#[derive(Deserialize)]
+pub struct Registration {
+    pub person_name: String,
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SessionToken {
+    pub token: String,
+    pub person_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Deserialize, PostgresMapper, Serialize)]
 #[pg_mapper(table = "planner")]
 pub struct Planner {
     pub planner_id: i32,
 }
 
-#[derive(Deserialize, PostgresMapper, Serialize)]
+#[derive(Clone, Deserialize, PostgresMapper, Serialize)]
 #[pg_mapper(table = "plan")]
 pub struct Plan {
     pub plan_id: Option<i32>,
@@ -36,26 +126,203 @@ pub struct Plan {
     pub planner_id: i32,
 }
 
-#[derive(Deserialize, PostgresMapper, Serialize)]
+#[derive(Clone, Deserialize, PostgresMapper, Serialize)]
 #[pg_mapper(table = "affiliation")]
 pub struct Affiliation {
     pub affiliation_id: Option<i32>,
     pub person_id: i32,
     pub organization_id: i32,
+    #[serde(default)]
+    pub role: OrganizationRole,
+    // Set by the database; ignored in request bodies.
+    #[serde(default)]
+    pub version: i32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, FromSql, PartialEq, Serialize, ToSql)]
+#[serde(rename_all = "lowercase")]
+#[postgres(name = "organization_role", rename_all = "lowercase")]
+pub enum OrganizationRole {
+    Owner,
+    Admin,
+    #[default]
+    Member,
+    Viewer,
+}
+
+impl OrganizationRole {
+    /// Owners and admins manage the organization's affiliations and planner.
+    pub fn can_manage(self) -> bool {
+        matches!(self, OrganizationRole::Owner | OrganizationRole::Admin)
+    }
 }
 
 #[derive(Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "affiliation")]
+pub struct Member {
+    pub affiliation_id: i32,
+    pub organization_id: i32,
+    pub person_id: i32,
+    pub person_name: String,
+    pub role: OrganizationRole,
+}
+
+#[derive(Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "affiliation")]
+pub struct Membership {
+    pub affiliation_id: i32,
+    pub person_id: i32,
+    pub organization_id: i32,
+    pub organization_name: String,
+    pub role: OrganizationRole,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, FromSql, PartialEq, Serialize, ToSql)]
+#[serde(rename_all = "lowercase")]
+#[postgres(name = "rsvp_status", rename_all = "lowercase")]
+pub enum RsvpStatus {
+    #[default]
+    Invited,
+    Accepted,
+    Declined,
+    Tentative,
+}
+
+#[derive(Clone, Deserialize, PostgresMapper, Serialize)]
 #[pg_mapper(table = "participation")]
 pub struct Participation {
     pub participation_id: Option<i32>,
     pub event_id: i32,
     pub person_id: i32,
+    #[serde(default)]
+    pub rsvp_status: RsvpStatus,
+    pub responded_at: Option<DateTime<Utc>>,
+    // Set by the database; ignored in request bodies.
+    #[serde(default)]
+    pub version: i32,
 }
 
 #[derive(Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "participation")]
+pub struct Participant {
+    pub participation_id: i32,
+    pub event_id: i32,
+    pub person_id: i32,
+    pub person_name: String,
+    pub rsvp_status: RsvpStatus,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+/// A shareable link into an event, as an RSVP, or into an organization, as a role.
+///
+/// Exactly one of `event_id` and `organization_id` is set, along with `rsvp_status` or `role`
+/// respectively. Each acceptance uses the invitation once, until `max_uses` is reached.
+#[derive(Clone, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "invitation")]
+pub struct Invitation {
+    pub invitation_id: Option<i32>,
+    pub token: String,
+    pub event_id: Option<i32>,
+    pub organization_id: Option<i32>,
+    pub rsvp_status: Option<RsvpStatus>,
+    pub role: Option<OrganizationRole>,
+    pub expires_at: DateTime<Utc>,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub created_by: i32,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Invitation {
+    /// Whether the invitation can still be accepted at `now`.
+    pub fn is_outstanding(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now && self.uses < self.max_uses
+    }
+}
+
+/// Body of `POST /events/{event_id}/invitations` and `POST /organizations/{organization_id}/invitations`.
+///
+/// `rsvp_status` only applies to events and `role` to organizations.
+#[derive(Deserialize)]
+pub struct InvitationRequest {
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default = "single_use")]
+    pub max_uses: i32,
+    pub rsvp_status: Option<RsvpStatus>,
+    pub role: Option<OrganizationRole>,
+}
+
+fn single_use() -> i32 {
+    1
+}
+
+/// An answer to an event from someone without an account, given through one of its invitations.
+///
+/// `token` lets the guest change their answer. Once a signed-in person presents it, `person_id`
+/// points at their `Person` and the answer lives on as a participation.
+#[derive(Clone, PostgresMapper)]
+#[pg_mapper(table = "guest")]
+pub struct Guest {
+    pub guest_id: Option<i32>,
+    pub token: String,
+    pub event_id: i32,
+    pub invitation_id: Option<i32>,
+    pub guest_name: String,
+    pub email: String,
+    pub rsvp_status: RsvpStatus,
+    pub plus_ones: i32,
+    pub responded_at: DateTime<Utc>,
+    pub person_id: Option<i32>,
+}
+
+/// A row of `organization`. Clients see organizations through `db::dto` only.
+#[derive(Clone, PostgresMapper)]
 #[pg_mapper(table = "organization")]
 pub struct Organization {
     pub organization_id: Option<i32>,
     pub organization_name: String,
     pub planner_id: Option<i32>,
+    pub version: i32,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "event")]
+pub struct EventHit {
+    pub event_id: i32,
+    pub event_name: String,
+    pub starts_at: DateTime<Utc>,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+#[derive(Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "person")]
+pub struct PersonHit {
+    pub person_id: i32,
+    pub person_name: String,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+#[derive(Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "organization")]
+pub struct OrganizationHit {
+    pub organization_id: i32,
+    pub organization_name: String,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+/// Search hits grouped by entity type, each group ranked from the best match down.
+#[derive(Deserialize, Serialize)]
+pub struct SearchResults {
+    pub events: Vec<EventHit>,
+    pub persons: Vec<PersonHit>,
+    pub organizations: Vec<OrganizationHit>,
 }
@@ -0,0 +1,150 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::db::errors::MyError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Page sizes used by list endpoints; falls back to the defaults when not registered as app data.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PageSettings {
+    pub page_size: i64,
+    pub max_page_size: i64,
+}
+
+impl Default for PageSettings {
+    fn default() -> Self {
+        PageSettings { page_size: DEFAULT_PAGE_SIZE, max_page_size: MAX_PAGE_SIZE }
+    }
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    after: Option<String>,
+    limit: Option<i64>,
+}
+
+/// A list response: at most one page of items and the cursor of the next page, if any.
+#[derive(Deserialize, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page { items: self.items.into_iter().map(f).collect(), next_cursor: self.next_cursor }
+    }
+}
+
+/// Cursors are opaque to clients; they carry the key of the last item of the previous page.
+pub fn encode_cursor(key: i32) -> String {
+    URL_SAFE_NO_PAD.encode(key.to_string())
+}
+
+pub fn decode_cursor(cursor: &str) -> Option<i32> {
+    let key = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    String::from_utf8(key).ok()?.parse().ok()
+}
+
+/// Keyset pagination read from `?after=<cursor>&limit=<n>`.
+///
+/// Rows are listed by increasing key, starting right after `after`. Queries fetch one row more
+/// than `limit` so that `page` can tell whether another page follows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pagination {
+    pub after: i32,
+    pub limit: i64,
+}
+
+impl Pagination {
+    fn parse(query_string: &str, settings: PageSettings) -> Result<Self, MyError> {
+        let query = web::Query::<PageQuery>::from_query(query_string)
+            .map_err(|_| MyError::BadRequest("after must be a cursor and limit a number".to_string()))?
+            .into_inner();
+
+        let after = match query.after {
+            Some(cursor) => decode_cursor(&cursor)
+                .ok_or_else(|| MyError::BadRequest("after is not a valid cursor".to_string()))?,
+            None => 0,
+        };
+
+        let limit = query.limit.unwrap_or(settings.page_size);
+        if limit < 1 {
+            return Err(MyError::BadRequest("limit must be at least 1".to_string()));
+        }
+
+        Ok(Pagination { after, limit: limit.min(settings.max_page_size) })
+    }
+
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    pub fn page<T>(&self, mut items: Vec<T>, key: impl Fn(&T) -> Option<i32>) -> Page<T> {
+        let next_cursor = if items.len() as i64 > self.limit {
+            items.truncate(self.limit as usize);
+            items.last().and_then(key).map(encode_cursor)
+        } else {
+            None
+        };
+
+        Page { items, next_cursor }
+    }
+}
+
+impl FromRequest for Pagination {
+    type Error = MyError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let settings = req.app_data::<web::Data<PageSettings>>()
+            .map(|settings| *settings.get_ref())
+            .unwrap_or_default();
+
+        ready(Pagination::parse(req.query_string(), settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_cursor, encode_cursor, PageSettings, Pagination};
+
+    #[test]
+    fn cursor_roundtrip() {
+        assert_eq!(decode_cursor(&encode_cursor(42)), Some(42));
+        assert_eq!(decode_cursor("not a cursor"), None);
+    }
+
+    #[test]
+    fn limits_are_defaulted_and_capped() {
+        let settings = PageSettings { page_size: 10, max_page_size: 20 };
+
+        assert_eq!(Pagination::parse("", settings).unwrap(), Pagination { after: 0, limit: 10 });
+        assert_eq!(Pagination::parse("limit=500", settings).unwrap().limit, 20);
+        assert_eq!(
+            Pagination::parse(&format!("after={}&limit=5", encode_cursor(3)), settings).unwrap(),
+            Pagination { after: 3, limit: 5 }
+        );
+        assert!(Pagination::parse("limit=0", settings).is_err());
+        assert!(Pagination::parse("limit=many", settings).is_err());
+        assert!(Pagination::parse("after=%%%", settings).is_err());
+    }
+
+    #[test]
+    fn page_sets_next_cursor_only_when_more_rows_follow() {
+        let pagination = Pagination { after: 0, limit: 2 };
+
+        let page = pagination.page(vec![1, 2, 3], |id| Some(*id));
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, Some(encode_cursor(2)));
+
+        let page = pagination.page(vec![1, 2], |id| Some(*id));
+        assert_eq!(page.next_cursor, None);
+    }
+}
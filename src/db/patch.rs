@@ -0,0 +1,138 @@
+//! JSON merge patches (RFC 7396) for the `PATCH` routes.
+//!
+//! A field left out of the patch is left untouched, `null` clears it and any other value replaces
+//! it. Updates only assign the columns a patch supplies.
+
+use serde::{Deserialize, Deserializer};
+use tokio_postgres::types::ToSql;
+
+/// One field of a merge patch.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Patch<T> {
+    #[default]
+    Absent,
+    Null,
+    Value(T),
+}
+
+// Absent fields never reach this: patch structs default them with `#[serde(default)]`.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::<T>::deserialize(deserializer).map(|value| value.map_or(Patch::Null, Patch::Value))
+    }
+}
+
+impl<T: Clone> Patch<T> {
+    pub fn is_supplied(&self) -> bool {
+        !matches!(self, Patch::Absent)
+    }
+
+    /// Merges into a field that cannot be null. Validation refuses `null` for such fields, so it
+    /// leaves the field as is here.
+    pub fn merge(&self, target: &mut T) {
+        if let Patch::Value(value) = self {
+            *target = value.clone();
+        }
+    }
+
+    pub fn merge_nullable(&self, target: &mut Option<T>) {
+        match self {
+            Patch::Absent => {}
+            Patch::Null => *target = None,
+            Patch::Value(value) => *target = Some(value.clone()),
+        }
+    }
+}
+
+impl<T: Clone + Default> Patch<T> {
+    /// Merges into a field stored as an empty value rather than `NULL`, such as a list.
+    pub fn merge_or_default(&self, target: &mut T) {
+        match self {
+            Patch::Absent => {}
+            Patch::Null => *target = T::default(),
+            Patch::Value(value) => *target = value.clone(),
+        }
+    }
+}
+
+/// The assignments of an `UPDATE`, limited to the columns being changed.
+#[derive(Default)]
+pub struct Changes<'a> {
+    assignments: Vec<String>,
+    values: Vec<&'a (dyn ToSql + Sync)>,
+}
+
+impl<'a> Changes<'a> {
+    pub fn set(&mut self, column: &str, value: &'a (dyn ToSql + Sync)) {
+        self.values.push(value);
+        self.assignments.push(format!("{} = ${}", column, self.values.len()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assignments.is_empty()
+    }
+
+    /// `UPDATE table SET ... WHERE key = $n RETURNING returning;`, where `$n` follows the values.
+    pub fn statement(&self, table: &str, key: &str, returning: &str) -> String {
+        format!(
+            "update {} set {} where {} = ${} returning {};",
+            table,
+            self.assignments.join(", "),
+            key,
+            self.values.len() + 1,
+            returning
+        )
+    }
+
+    pub fn params(&self, key: &'a (dyn ToSql + Sync)) -> Vec<&'a (dyn ToSql + Sync)> {
+        let mut params = self.values.clone();
+        params.push(key);
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{Changes, Patch};
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    struct Body {
+        name: Patch<String>,
+        rule: Patch<String>,
+        dates: Patch<Vec<i32>>,
+    }
+
+    #[test]
+    fn absent_null_and_values_differ() {
+        let body: Body = serde_json::from_str(r#"{ "name": "Gala", "rule": null }"#).unwrap();
+        assert_eq!(body.name, Patch::Value("Gala".to_string()));
+        assert_eq!(body.rule, Patch::Null);
+        assert_eq!(body.dates, Patch::Absent);
+
+        let (mut name, mut rule, mut dates) = ("Bal".to_string(), Some("FREQ=DAILY".to_string()), vec![1]);
+        body.name.merge(&mut name);
+        body.rule.merge_nullable(&mut rule);
+        body.dates.merge_or_default(&mut dates);
+        assert_eq!((name.as_str(), rule, &dates), ("Gala", None, &vec![1]));
+
+        Patch::<Vec<i32>>::Null.merge_or_default(&mut dates);
+        assert!(dates.is_empty());
+    }
+
+    #[test]
+    fn only_supplied_columns_are_assigned() {
+        let (name, location, id) = ("Gala".to_string(), "Lyon".to_string(), 4);
+        let mut changes = Changes::default();
+        changes.set("event_name", &name);
+        changes.set("event_location", &location);
+
+        assert_eq!(
+            changes.statement("event", "event_id", "event_id"),
+            "update event set event_name = $1, event_location = $2 where event_id = $3 returning event_id;"
+        );
+        assert_eq!(changes.params(&id).len(), 3);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::db::{
+    auth::AuthenticatedPerson,
+    errors::MyError,
+    models::{Invitation, OrganizationRole},
+    repository::Store
+};
+
+fn allow(allowed: bool) -> Result<(), MyError> {
+    if allowed {
+        Ok(())
+    } else {
+        Err(MyError::Forbidden)
+    }
+}
+
+/// A row the caller may not see is forbidden whether it exists or not, so that ids cannot be probed.
+pub fn hide_missing<T>(row: Result<T, MyError>) -> Result<T, MyError> {
+    match row {
+        Err(MyError::NotFound) => Err(MyError::Forbidden),
+        row => row,
+    }
+}
+
+/// Only the person themselves may act on their `Person`.
+pub fn ensure_self(auth: &AuthenticatedPerson, person_id: i32) -> Result<(), MyError> {
+    allow(auth.person_id == person_id)
+}
+
+pub async fn ensure_planner_member(store: &dyn Store, auth: &AuthenticatedPerson, planner_id: i32) -> Result<(), MyError> {
+    allow(store.is_planner_member(auth.person_id, planner_id).await?)
+}
+
+/// Unknown events are reported as forbidden too, so that ids cannot be probed.
+pub async fn ensure_event_member(store: &dyn Store, auth: &AuthenticatedPerson, event_id: i32) -> Result<(), MyError> {
+    allow(store.is_event_member(auth.person_id, event_id).await?)
+}
+
+/// Owners and admins manage an organization's affiliations and roles; returns the caller's role.
+pub async fn ensure_organization_manager(store: &dyn Store, auth: &AuthenticatedPerson, organization_id: i32) -> Result<OrganizationRole, MyError> {
+    match store.get_organization_role(auth.person_id, organization_id).await? {
+        Some(role) if role.can_manage() => Ok(role),
+        _ => Err(MyError::Forbidden),
+    }
+}
+
+/// Any role in the organization lets a person see what it holds, such as its calendar.
+pub async fn ensure_organization_member(store: &dyn Store, auth: &AuthenticatedPerson, organization_id: i32) -> Result<(), MyError> {
+    allow(store.get_organization_role(auth.person_id, organization_id).await?.is_some())
+}
+
+pub async fn ensure_organization_owner(store: &dyn Store, auth: &AuthenticatedPerson, organization_id: i32) -> Result<(), MyError> {
+    allow(store.get_organization_role(auth.person_id, organization_id).await? == Some(OrganizationRole::Owner))
+}
+
+/// Only owners may hand out the owner role or take it away.
+pub fn ensure_may_assign(caller: OrganizationRole, role: OrganizationRole) -> Result<(), MyError> {
+    allow(role != OrganizationRole::Owner || caller == OrganizationRole::Owner)
+}
+
+/// Invitations are managed by those who could have made them: members of the event, or owners
+/// and admins allowed to hand out the role.
+pub async fn ensure_invitation_host(store: &dyn Store, auth: &AuthenticatedPerson, invitation: &Invitation) -> Result<(), MyError> {
+    match (invitation.event_id, invitation.organization_id) {
+        (Some(event_id), _) => ensure_event_member(store, auth, event_id).await,
+        (None, Some(organization_id)) => {
+            let caller_role = ensure_organization_manager(store, auth, organization_id).await?;
+            ensure_may_assign(caller_role, invitation.role.unwrap_or_default())
+        }
+        (None, None) => Err(MyError::Forbidden),
+    }
+}
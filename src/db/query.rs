@@ -1,19 +1,106 @@
-use deadpool_postgres::Client;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
 use tokio_pg_mapper::FromTokioPostgresRow;
+use tokio_postgres::error::{Error as PGError, SqlState};
+use tokio_postgres::types::ToSql;
 
 use crate::{
+    db::dto::{UpdateEvent, UpdateOrganization, UpdatePerson},
     db::errors::MyError, 
+    db::filter::Filter,
+    db::patch::Changes,
+    db::pagination::Pagination,
+    db::recurrence,
+    db::search,
     db::models::{
+        Account,
         Event,
+        EventOccurrence,
+        EventOverride,
+        Guest,
+        Invitation,
         Person,
         Plan, Planner,
         Affiliation,
-        Organization
+        Organization,
+        Member,
+        Membership,
+        Participant,
+        Participation,
+        OrganizationRole,
+        RsvpStatus,
+        EventHit,
+        PersonHit,
+        OrganizationHit,
+        SearchResults
     }
 };
 
-pub async fn create_event(client: &Client, event_info: Event) -> Result<Event, MyError> {
-    let _stmt = "INSERT INTO event(event_name, event_location, event_description) VALUES($1, $2, $3) RETURNING $table_fields;";
+/// Detail of the conflict raised when the last owner of an organization would go.
+pub const OWNER_REQUIRED: &str = "an organization must keep at least one owner";
+
+/// Detail of the conflict raised when a planner still in use would be deleted.
+pub const PLANNER_IN_USE: &str = "the planner belongs to a person or an organization";
+
+// Raised by the trigger that keeps at least one owner in every organization.
+fn owner_error(err: PGError) -> MyError {
+    if err.code() == Some(&SqlState::INTEGRITY_CONSTRAINT_VIOLATION) {
+        MyError::Conflict(OWNER_REQUIRED.to_string())
+    } else {
+        MyError::PGError(err)
+    }
+}
+
+/// Runs the `UPDATE` of `changes` on the row of `T` whose `key` is `id` and returns the updated row.
+async fn update_row<T: FromTokioPostgresRow>(client: &impl GenericClient, key: &str, id: &i32, changes: &Changes<'_>) -> Result<T, MyError> {
+    let _stmt = changes.statement(T::sql_table().as_str(), key, &T::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(&statement, &changes.params(id))
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(T::from_row_ref)
+    .collect::<Result<Vec<T>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// Locks the row of `T` whose `key` is `id` until the end of the transaction and returns its
+/// version, so that an `If-Match` checked against it still holds when the row is written.
+pub async fn lock_version<T: FromTokioPostgresRow>(client: &impl GenericClient, key: &str, id: i32) -> Result<i32, MyError> {
+    let _stmt = format!("select version from {} where {} = $1 for update;", T::sql_table(), key);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query_opt(&statement, &[&id])
+    .await
+    .map_err(MyError::PGError)?
+    .map(|row| row.get("version"))
+    .ok_or(MyError::NotFound)
+}
+
+/// Rejects events that end before they start and returns the end of their series, if it ends.
+pub fn check_event_schedule(event_info: &Event) -> Result<Option<DateTime<Utc>>, MyError> {
+    if event_info.ends_at < event_info.starts_at {
+        return Err(MyError::BadRequest("ends_at must not be before starts_at".to_string()));
+    }
+
+    recurrence::series_end(event_info)
+}
+
+pub async fn create_event(client: &impl GenericClient, event_info: Event) -> Result<Event, MyError> {
+    insert_event(client, event_info, None, None).await
+}
+
+/// Creates an event imported from an iCalendar file into the planner, remembering its VEVENT UID.
+pub async fn create_imported_event(client: &impl GenericClient, event_info: Event, planner_id: i32, ical_uid: &str) -> Result<Event, MyError> {
+    insert_event(client, event_info, Some(planner_id), Some(ical_uid)).await
+}
+
+async fn insert_event(client: &impl GenericClient, event_info: Event, ical_planner_id: Option<i32>, ical_uid: Option<&str>) -> Result<Event, MyError> {
+    let series_ends_at = check_event_schedule(&event_info)?;
+
+    let _stmt = "INSERT INTO event(event_name, event_location, event_description, starts_at, ends_at, time_zone, all_day, recurrence_rule, recurrence_dates, exception_dates, series_ends_at, ical_planner_id, ical_uid) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING $table_fields;";
     let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
@@ -23,65 +110,235 @@ pub async fn create_event(client: &Client, event_info: Event) -> Result<Event, M
             &event_info.event_name, 
             &event_info.event_location,
             &event_info.event_description,
+            &event_info.starts_at,
+            &event_info.ends_at,
+            &event_info.time_zone,
+            &event_info.all_day,
+            &event_info.recurrence_rule,
+            &event_info.recurrence_dates,
+            &event_info.exception_dates,
+            &series_ends_at,
+            &ical_planner_id,
+            &ical_uid,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Event::from_row_ref(row).unwrap())
-    .collect::<Vec<Event>>()
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
 }
 
-pub async fn modify_event(client: &Client, event_info: Event) -> Result<Event, MyError> {
-    let _stmt = "UPDATE event SET event_name = $1, event_description = $2 where event_id=$3 RETURNING $table_fields;";
+pub async fn modify_event(client: &impl GenericClient, event_id: i32, event_patch: &UpdateEvent) -> Result<Event, MyError> {
+    // The patch is checked against the row it applies to, which must not change in between.
+    let _stmt = "select $table_fields from event where event_id = $1 for update;";
+    let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    let mut event_info = client.query(&statement, &[&event_id])
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)?;
+
+    event_patch.apply(&mut event_info);
+    let series_ends_at = check_event_schedule(&event_info)?;
+
+    let mut changes = Changes::default();
+    if event_patch.event_name.is_supplied() {
+        changes.set("event_name", &event_info.event_name);
+    }
+    if event_patch.event_location.is_supplied() {
+        changes.set("event_location", &event_info.event_location);
+    }
+    if event_patch.event_description.is_supplied() {
+        changes.set("event_description", &event_info.event_description);
+    }
+    if event_patch.starts_at.is_supplied() {
+        changes.set("starts_at", &event_info.starts_at);
+    }
+    if event_patch.ends_at.is_supplied() {
+        changes.set("ends_at", &event_info.ends_at);
+    }
+    if event_patch.time_zone.is_supplied() {
+        changes.set("time_zone", &event_info.time_zone);
+    }
+    if event_patch.all_day.is_supplied() {
+        changes.set("all_day", &event_info.all_day);
+    }
+    if event_patch.recurrence_rule.is_supplied() {
+        changes.set("recurrence_rule", &event_info.recurrence_rule);
+    }
+    if event_patch.recurrence_dates.is_supplied() {
+        changes.set("recurrence_dates", &event_info.recurrence_dates);
+    }
+    if event_patch.exception_dates.is_supplied() {
+        changes.set("exception_dates", &event_info.exception_dates);
+    }
+    if event_patch.changes_schedule() {
+        changes.set("series_ends_at", &series_ends_at);
+    }
+
+    if changes.is_empty() {
+        return Ok(event_info);
+    }
+    update_row(client, "event_id", &event_id, &changes).await
+}
+
+pub async fn get_events(client: &impl GenericClient, person_id: i32) -> Result<Vec<Event>, MyError> {
+    get_events_between(client, Some(person_id), None, None).await
+}
+
+/// The events in the person's own planner, by id, after the key `after`.
+pub async fn list_events(client: &impl GenericClient, person_id: i32, filter: &Filter<Event>, pagination: &Pagination) -> Result<Vec<Event>, MyError> {
+    let filter = filter.compile(pagination, 2);
+    let _stmt = "SELECT $table_fields FROM event
+    JOIN plan ON event.event_id = plan.event_id
+    WHERE plan.planner_id = (SELECT planner_id FROM person WHERE person_id = $1)$conditions
+    ORDER BY $order_by
+    LIMIT $limit;";
+    let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields())
+        .replace("$conditions", &filter.conditions)
+        .replace("$order_by", &filter.order_by)
+        .replace("$limit", &filter.limit);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![&person_id];
+    params.extend(filter.params());
+
+    Ok(client.query(&statement, &params)
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?)
+}
+
+pub async fn get_events_between(
+    client: &impl GenericClient,
+    person_id: Option<i32>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<Event>, MyError> {
+    let _stmt = "SELECT $table_fields FROM event
+    JOIN plan ON event.event_id = plan.event_id
+    WHERE plan.planner_id = (SELECT planner_id FROM person WHERE person_id = $1)
+    AND (
+        tstzrange(event.starts_at, event.series_ends_at, '[]') && tstzrange($2, $3, '[)')
+        OR EXISTS (
+            SELECT 1 FROM event_override
+            WHERE event_override.event_id = event.event_id
+            AND tstzrange(event_override.starts_at, event_override.ends_at, '[]') && tstzrange($2, $3, '[)')
+        )
+    )
+    ORDER BY event.starts_at, event.event_id;";
+    let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement, 
+        &[
+            &person_id, 
+            &from,
+            &to,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?)
+
+}
+
+pub async fn get_occurrences_between(
+    client: &impl GenericClient,
+    person_id: Option<i32>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<EventOccurrence>, MyError> {
+    let events = get_events_between(client, person_id, from, to).await?;
+
+    let event_ids = events.iter().filter_map(|event| event.event_id).collect::<Vec<i32>>();
+    let overrides = get_event_overrides(client, &event_ids).await?;
+
+    let mut occurrences = Vec::new();
+    for event in events.iter() {
+        occurrences.extend(recurrence::expand(event, &overrides, from, to)?);
+    }
+    occurrences.sort_by_key(|occurrence| occurrence.event.starts_at);
+
+    Ok(occurrences)
+}
+
+pub async fn get_planner_events(client: &impl GenericClient, planner_id: i32) -> Result<Vec<Event>, MyError> {
+    let _stmt = "SELECT $table_fields FROM event
+    JOIN plan ON event.event_id = plan.event_id
+    WHERE plan.planner_id = $1
+    ORDER BY event.starts_at, event.event_id;";
     let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &planner_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?)
+}
 
+pub async fn get_event(client: &impl GenericClient, event_id: i32) -> Result<Event, MyError> {
+    let _stmt = "SELECT $table_fields FROM event WHERE event_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.query(
         &statement,
         &[
-            &event_info.event_name,
-            &event_info.event_description,
-            &event_info.event_id.unwrap().to_string(),
+            &event_id,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Event::from_row_ref(row).unwrap())
-    .collect::<Vec<Event>>()
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
-    
 }
 
-pub async fn get_events(client: &Client, person_info: Person) -> Result<Vec<Event>, MyError> {
-    let _stmt = "SELECT event.event_id, event.event_name, event.event_description, event.event_location FROM event
-    JOIN plan ON event.event_id = plan.event_id 
-    JOIN planner ON plan.planner_id = planner.planner_id
-    JOIN person ON planner.planner_id = person.planner_id
-    WHERE person.person_id = $1;";
-    let _stmt = _stmt.to_string();
+/// The event imported into the planner under this VEVENT UID, if any.
+pub async fn get_event_by_ical_uid(client: &impl GenericClient, planner_id: i32, ical_uid: &str) -> Result<Option<Event>, MyError> {
+    let _stmt = "SELECT $table_fields FROM event WHERE ical_planner_id = $1 AND ical_uid = $2;";
+    let _stmt = _stmt.replace("$table_fields", &Event::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     Ok(client.query(
-        &statement, 
+        &statement,
         &[
-            &person_info.person_id, 
+            &planner_id,
+            &ical_uid,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Event::from_row_ref(row).unwrap())
-    .collect::<Vec<Event>>())
-
+    .map(Event::from_row_ref)
+    .collect::<Result<Vec<Event>, _>>()?
+    .pop())
 }
 
-pub async fn delete_event(client: &Client, event_info: Event) -> Result<u64, MyError> {
+pub async fn delete_event(client: &impl GenericClient, event_id: i32) -> Result<u64, MyError> {
     let _stmt = "DELETE FROM event WHERE event_id = $1;";
     let _stmt = _stmt.to_string();
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
@@ -89,7 +346,7 @@ pub async fn delete_event(client: &Client, event_info: Event) -> Result<u64, MyE
     client.execute(
         &statement,
         &[
-            &event_info.event_id.unwrap(),
+            &event_id,
         ]
     )
     .await
@@ -97,203 +354,1308 @@ pub async fn delete_event(client: &Client, event_info: Event) -> Result<u64, MyE
 
 }
 
-pub async fn create_plan(client: &Client, plan_info: Plan) -> Result<Plan, MyError> {
-    let _stmt = "INSERT INTO plan(planner_id, event_id) VALUES($1,$2) RETURNING $table_fields;";
-    let _stmt = _stmt.replace("$table_fields", &Plan::sql_table_fields());
+pub async fn get_event_overrides(client: &impl GenericClient, event_ids: &[i32]) -> Result<Vec<EventOverride>, MyError> {
+    let _stmt = "SELECT $table_fields FROM event_override WHERE event_id = ANY($1);";
+    let _stmt = _stmt.replace("$table_fields", &EventOverride::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
-    
+
+    Ok(client.query(
+        &statement,
+        &[
+            &event_ids,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(EventOverride::from_row_ref)
+    .collect::<Result<Vec<EventOverride>, _>>()?)
+}
+
+pub async fn list_event_overrides(client: &impl GenericClient, event_id: i32, after: i32, limit: i64) -> Result<Vec<EventOverride>, MyError> {
+    let _stmt = "SELECT $table_fields FROM event_override WHERE event_id = $1 AND override_id > $2 ORDER BY override_id LIMIT $3;";
+    let _stmt = _stmt.replace("$table_fields", &EventOverride::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &event_id,
+            &after,
+            &limit,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(EventOverride::from_row_ref)
+    .collect::<Result<Vec<EventOverride>, _>>()?)
+}
+
+pub async fn get_event_override(client: &impl GenericClient, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<EventOverride, MyError> {
+    let _stmt = "SELECT $table_fields FROM event_override WHERE event_id = $1 AND occurrence_starts_at = $2;";
+    let _stmt = _stmt.replace("$table_fields", &EventOverride::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
     client.query(
-        &statement, 
+        &statement,
         &[
-            &plan_info.planner_id, 
-            &plan_info.event_id,
+            &event_id,
+            &occurrence_starts_at,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Plan::from_row_ref(row).unwrap())
-    .collect::<Vec<Plan>>()
+    .map(EventOverride::from_row_ref)
+    .collect::<Result<Vec<EventOverride>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
 }
 
-pub async fn delete_plan(client: &Client, plan_info: Plan) -> Result<u64, MyError> {
-    let _stmt = "DELETE FROM plan WHERE plan_id = $1;";
+pub async fn create_event_override(client: &impl GenericClient, override_info: EventOverride) -> Result<EventOverride, MyError> {
+    let _stmt = "INSERT INTO event_override(event_id, occurrence_starts_at, cancelled, event_name, event_location, event_description, starts_at, ends_at)
+    VALUES($1, $2, $3, $4, $5, $6, $7, $8)
+    ON CONFLICT (event_id, occurrence_starts_at) DO UPDATE SET
+        cancelled = EXCLUDED.cancelled,
+        event_name = EXCLUDED.event_name,
+        event_location = EXCLUDED.event_location,
+        event_description = EXCLUDED.event_description,
+        starts_at = EXCLUDED.starts_at,
+        ends_at = EXCLUDED.ends_at
+    RETURNING $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &EventOverride::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &override_info.event_id,
+            &override_info.occurrence_starts_at,
+            &override_info.cancelled,
+            &override_info.event_name,
+            &override_info.event_location,
+            &override_info.event_description,
+            &override_info.starts_at,
+            &override_info.ends_at,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(EventOverride::from_row_ref)
+    .collect::<Result<Vec<EventOverride>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn delete_event_override(client: &impl GenericClient, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<u64, MyError> {
+    let _stmt = "DELETE FROM event_override WHERE event_id = $1 AND occurrence_starts_at = $2;";
     let _stmt = _stmt.to_string();
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.execute(
         &statement,
         &[
-            &plan_info.plan_id.unwrap(),
+            &event_id,
+            &occurrence_starts_at,
         ]
     )
     .await
     .map_err(MyError::PGError)
 }
 
-pub async fn create_planner(client: &Client) -> Result<Planner, MyError> {
-    let _stmt = "insert into planner default values returning $table_fields;";
-    let _stmt = _stmt.replace("$table_fields", &Planner::sql_table_fields());
+pub async fn create_plan(client: &impl GenericClient, plan_info: Plan) -> Result<Plan, MyError> {
+    let _stmt = "INSERT INTO plan(planner_id, event_id) VALUES($1,$2) RETURNING $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Plan::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
-
+    
     client.query(
         &statement, 
         &[
-
+            &plan_info.planner_id, 
+            &plan_info.event_id,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Planner::from_row_ref(row).unwrap())
-    .collect::<Vec<Planner>>()
+    .map(Plan::from_row_ref)
+    .collect::<Result<Vec<Plan>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
 }
 
-pub async fn delete_planner(client: &Client, planner_info: Planner) -> Result<u64, MyError> {
-    let _stmt = "delete from planner where planner_id = $1;";
-    let _stmt = _stmt.to_string();
+/// Links an event to a planner unless it already is, returning `None` in that case.
+pub async fn ensure_plan(client: &impl GenericClient, plan_info: Plan) -> Result<Option<Plan>, MyError> {
+    let _stmt = "INSERT INTO plan(planner_id, event_id) VALUES($1,$2) ON CONFLICT (event_id, planner_id) DO NOTHING RETURNING $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Plan::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
-    client.execute(
+    Ok(client.query(
         &statement,
         &[
-            &planner_info.planner_id,
+            &plan_info.planner_id,
+            &plan_info.event_id,
         ]
     )
     .await
-    .map_err(MyError::PGError)
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Plan::from_row_ref)
+    .collect::<Result<Vec<Plan>, _>>()?
+    .pop())
 }
 
-pub async fn create_person(client: &Client, person_info: Person) -> Result<Person, MyError> {
-    let _stmt = "insert into person(person_name, planner_id) values($1, $2) returning $table_fields;";
-    let _stmt = _stmt.replace("$table_fields", &Person::sql_fields());
+pub async fn get_plan(client: &impl GenericClient, plan_id: i32) -> Result<Plan, MyError> {
+    let _stmt = "select $table_fields from plan where plan_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Plan::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.query(
         &statement,
         &[
-            &person_info.person_name,
-            &person_info.planner_id,
+            &plan_id,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Person::from_row_ref(row).unwrap())
-    .collect::<Vec<Person>>()
+    .map(Plan::from_row_ref)
+    .collect::<Result<Vec<Plan>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
 }
 
-pub async fn modify_person(client: &Client, person_info: Person) -> Result<Person, MyError> {
-    let _stmt = "update person set person_name = $1 where person_id = $2 returning $table_fields;";
-    let _stmt = _stmt.replace("$table_fields", &Person::sql_table_fields());
+/// The plans of every planner the person belongs to, whatever their role.
+pub async fn list_plans(client: &impl GenericClient, person_id: i32, after: i32, limit: i64) -> Result<Vec<Plan>, MyError> {
+    let _stmt = "select $table_fields from plan where plan.planner_id in ($member_planners) and plan_id > $2 order by plan_id limit $3;";
+    let _stmt = _stmt.replace("$table_fields", &Plan::sql_table_fields());
+    let _stmt = _stmt.replace("$member_planners", MEMBER_PLANNERS).replace("$roles", "'owner', 'admin', 'member', 'viewer'");
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
-    client.query(
+    Ok(client.query(
         &statement,
         &[
-            &person_info.person_name,
-            &person_info.person_id,
+            &person_id,
+            &after,
+            &limit,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Person::from_row_ref(row).unwrap())
-    .collect::<Vec<Person>>()
-    .pop()
-    .ok_or(MyError::NotFound)
+    .map(Plan::from_row_ref)
+    .collect::<Result<Vec<Plan>, _>>()?)
 }
 
-pub async fn delete_person(client: &Client, person_info: Person) -> Result<u64, MyError> {
-    let _stmt = "DELETE FROM person WHERE person_id = $1;";
+pub async fn delete_plan(client: &impl GenericClient, plan_id: i32) -> Result<u64, MyError> {
+    let _stmt = "DELETE FROM plan WHERE plan_id = $1;";
     let _stmt = _stmt.to_string();
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.execute(
         &statement,
         &[
-            &person_info.person_id.unwrap(),
+            &plan_id,
         ]
     )
     .await
     .map_err(MyError::PGError)
 }
 
-pub async fn create_affiliation(client: &Client, affiliation_info: Affiliation) -> Result<Affiliation, MyError> {
-    let _stmt = "insert into affiliation(person_id, organization_id) values ($1, $2) returning $table_fields;";
-    let _stmt = _stmt.replace("$tale_fields", &Affiliation::sql_table_fields());
+pub async fn create_planner(client: &impl GenericClient) -> Result<Planner, MyError> {
+    let _stmt = "insert into planner default values returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Planner::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement, 
+        &[
+
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Planner::from_row_ref)
+    .collect::<Result<Vec<Planner>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn get_planner(client: &impl GenericClient, planner_id: i32) -> Result<Planner, MyError> {
+    let _stmt = "select $table_fields from planner where planner_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Planner::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.query(
         &statement,
         &[
-            &affiliation_info.person_id,
-            &affiliation_info.organization_id,
+            &planner_id,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Affiliation::from_row_ref(row).unwrap())
-    .collect::<Vec<Affiliation>>()
+    .map(Planner::from_row_ref)
+    .collect::<Result<Vec<Planner>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
 }
 
-pub async fn delete_affiliation(client: &Client, affiliation_info: Affiliation) -> Result<u64, MyError> {
-    let _stmt = "delete from affiliation where affiliation_id = $1;";
+/// The person's own planner and those of their organizations, whatever their role.
+pub async fn list_planners(client: &impl GenericClient, person_id: i32, after: i32, limit: i64) -> Result<Vec<Planner>, MyError> {
+    let _stmt = "select $table_fields from planner where planner.planner_id in ($member_planners) and planner_id > $2 order by planner_id limit $3;";
+    let _stmt = _stmt.replace("$table_fields", &Planner::sql_table_fields());
+    let _stmt = _stmt.replace("$member_planners", MEMBER_PLANNERS).replace("$roles", "'owner', 'admin', 'member', 'viewer'");
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &person_id,
+            &after,
+            &limit,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Planner::from_row_ref)
+    .collect::<Result<Vec<Planner>, _>>()?)
+}
+
+pub async fn delete_planner(client: &impl GenericClient, planner_id: i32) -> Result<u64, MyError> {
+    let _stmt = "delete from planner where planner_id = $1;";
+    let _stmt = _stmt.to_string();
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &planner_id,
+        ]
+    )
+    .await
+    .map_err(|err| {
+        // Persons and organizations keep their planner until they are deleted themselves.
+        if err.code() == Some(&SqlState::FOREIGN_KEY_VIOLATION) {
+            MyError::Conflict(PLANNER_IN_USE.to_string())
+        } else {
+            MyError::PGError(err)
+        }
+    })
+}
+
+/// Removes a planner once its person or organization is gone, unless something else still uses it.
+pub async fn delete_unused_planner(client: &impl GenericClient, planner_id: i32) -> Result<u64, MyError> {
+    let _stmt = "delete from planner where planner_id = $1
+    and not exists (select 1 from person where planner_id = $1)
+    and not exists (select 1 from organization where planner_id = $1);";
     let _stmt = _stmt.to_string();
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.execute(
         &statement,
         &[
-            &affiliation_info.affiliation_id.unwrap(),
+            &planner_id,
         ]
     )
     .await
     .map_err(MyError::PGError)
 }
 
-pub async fn create_organization(client: &Client, organization_info: Organization) -> Result<Organization, MyError> {
-    let _stmt = "insert into organization(organization_name, planner_id) values ($1, $2) returning $table_fields;";
-    let _stmt = _stmt.replace("$table_fields", &Organization::sql_table_fields());
+pub async fn create_person(client: &impl GenericClient, person_info: Person) -> Result<Person, MyError> {
+    let _stmt = "insert into person(person_name, planner_id) values($1, $2) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Person::sql_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
     client.query(
         &statement,
         &[
-            &organization_info.organization_name,
-            &organization_info.planner_id,
+            &person_info.person_name,
+            &person_info.planner_id,
         ]
     )
     .await
     .map_err(MyError::PGError)?
     .iter()
-    .map(|row| Organization::from_row_ref(row).unwrap())
-    .collect::<Vec<Organization>>()
+    .map(Person::from_row_ref)
+    .collect::<Result<Vec<Person>, _>>()?
     .pop()
     .ok_or(MyError::NotFound)
 }
 
-pub async fn delete_organization(client: &Client, organization_info: Organization) ->Result<u64, MyError> {
-    let _stmt = "delete from organization where organization_id = $1;";
-    let _stmt = _stmt.to_string();
+pub async fn get_person(client: &impl GenericClient, person_id: i32) -> Result<Person, MyError> {
+    let _stmt = "select $table_fields from person where person_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Person::sql_table_fields());
     let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
 
-    client.execute(
+    client.query(
         &statement,
         &[
-            &organization_info.organization_id.unwrap(),
+            &person_id,
         ]
     )
     .await
-    .map_err(MyError::PGError)
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Person::from_row_ref)
+    .collect::<Result<Vec<Person>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn list_persons(client: &impl GenericClient, filter: &Filter<Person>, pagination: &Pagination) -> Result<Vec<Person>, MyError> {
+    let filter = filter.compile(pagination, 1);
+    let _stmt = "select $table_fields from person where true$conditions order by $order_by limit $limit;";
+    let _stmt = _stmt.replace("$table_fields", &Person::sql_table_fields())
+        .replace("$conditions", &filter.conditions)
+        .replace("$order_by", &filter.order_by)
+        .replace("$limit", &filter.limit);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(&statement, &filter.params().collect::<Vec<_>>())
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Person::from_row_ref)
+    .collect::<Result<Vec<Person>, _>>()?)
 }
 
+pub async fn modify_person(client: &impl GenericClient, person_id: i32, person_patch: &UpdatePerson) -> Result<Person, MyError> {
+    let mut person_info = get_person(client, person_id).await?;
+    person_patch.apply(&mut person_info);
+
+    let mut changes = Changes::default();
+    if person_patch.person_name.is_supplied() {
+        changes.set("person_name", &person_info.person_name);
+    }
+
+    if changes.is_empty() {
+        return Ok(person_info);
+    }
+    update_row(client, "person_id", &person_id, &changes).await
+}
+
+pub async fn delete_person(client: &impl GenericClient, person_id: i32) -> Result<u64, MyError> {
+    let _stmt = "DELETE FROM person WHERE person_id = $1;";
+    let _stmt = _stmt.to_string();
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &person_id,
+        ]
+    )
+    .await
+    .map_err(owner_error)
+}
+
+pub async fn create_account(client: &impl GenericClient, person_id: i32, email: &str, password_hash: &str) -> Result<Account, MyError> {
+    let _stmt = "insert into account(person_id, email, password_hash) values ($1, $2, $3) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Account::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &person_id,
+            &email,
+            &password_hash,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Account::from_row_ref)
+    .collect::<Result<Vec<Account>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// Looks an account up by email, ignoring case.
+pub async fn get_account_by_email(client: &impl GenericClient, email: &str) -> Result<Option<Account>, MyError> {
+    let _stmt = "select $table_fields from account where lower(email) = lower($1);";
+    let _stmt = _stmt.replace("$table_fields", &Account::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &email,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Account::from_row_ref)
+    .collect::<Result<Vec<Account>, _>>()?
+    .pop())
+}
+
+pub async fn create_session(client: &impl GenericClient, session_id: &str, person_id: i32, expires_at: DateTime<Utc>) -> Result<u64, MyError> {
+    let _stmt = "insert into session(session_id, person_id, expires_at) values ($1, $2, $3);";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &session_id,
+            &person_id,
+            &expires_at,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)
+}
+
+/// A session is active until it expires or is revoked by a logout.
+pub async fn is_session_active(client: &impl GenericClient, session_id: &str, person_id: i32) -> Result<bool, MyError> {
+    let _stmt = "select 1 from session where session_id = $1 and person_id = $2 and revoked_at is null and expires_at > now();";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(!client.query(
+        &statement,
+        &[
+            &session_id,
+            &person_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .is_empty())
+}
+
+pub async fn revoke_session(client: &impl GenericClient, session_id: &str) -> Result<u64, MyError> {
+    let _stmt = "update session set revoked_at = now() where session_id = $1 and revoked_at is null;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &session_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)
+}
+
+pub async fn create_affiliation(client: &impl GenericClient, affiliation_info: Affiliation) -> Result<Affiliation, MyError> {
+    let _stmt = "insert into affiliation(person_id, organization_id, role) values ($1, $2, $3) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Affiliation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &affiliation_info.person_id,
+            &affiliation_info.organization_id,
+            &affiliation_info.role,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Affiliation::from_row_ref)
+    .collect::<Result<Vec<Affiliation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn get_affiliation(client: &impl GenericClient, affiliation_id: i32) -> Result<Affiliation, MyError> {
+    let _stmt = "select $table_fields from affiliation where affiliation_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Affiliation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &affiliation_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Affiliation::from_row_ref)
+    .collect::<Result<Vec<Affiliation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// The affiliations of the organizations the person belongs to, whatever their role.
+pub async fn list_affiliations(client: &impl GenericClient, person_id: i32, after: i32, limit: i64) -> Result<Vec<Affiliation>, MyError> {
+    let _stmt = "select $table_fields from affiliation
+        where affiliation.organization_id in (select organization_id from affiliation where person_id = $1)
+        and affiliation_id > $2 order by affiliation_id limit $3;";
+    let _stmt = _stmt.replace("$table_fields", &Affiliation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &person_id,
+            &after,
+            &limit,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Affiliation::from_row_ref)
+    .collect::<Result<Vec<Affiliation>, _>>()?)
+}
+
+pub async fn modify_affiliation(client: &impl GenericClient, affiliation_id: i32, role: OrganizationRole) -> Result<Affiliation, MyError> {
+    let _stmt = "update affiliation set role = $1 where affiliation_id = $2 returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Affiliation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &role,
+            &affiliation_id,
+        ]
+    )
+    .await
+    .map_err(owner_error)?
+    .iter()
+    .map(Affiliation::from_row_ref)
+    .collect::<Result<Vec<Affiliation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn delete_affiliation(client: &impl GenericClient, affiliation_id: i32) -> Result<u64, MyError> {
+    let _stmt = "delete from affiliation where affiliation_id = $1;";
+    let _stmt = _stmt.to_string();
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &affiliation_id,
+        ]
+    )
+    .await
+    .map_err(owner_error)
+}
+
+pub async fn get_organization_members(client: &impl GenericClient, organization_id: i32) -> Result<Vec<Member>, MyError> {
+    let _stmt = "select affiliation.affiliation_id, affiliation.organization_id, affiliation.person_id, person.person_name, affiliation.role
+    from affiliation
+    join person on person.person_id = affiliation.person_id
+    where affiliation.organization_id = $1
+    order by affiliation.role, person.person_name, affiliation.affiliation_id;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &organization_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Member::from_row_ref)
+    .collect::<Result<Vec<Member>, _>>()?)
+}
+
+pub async fn get_person_organizations(client: &impl GenericClient, person_id: i32) -> Result<Vec<Membership>, MyError> {
+    let _stmt = "select affiliation.affiliation_id, affiliation.person_id, affiliation.organization_id, organization.organization_name, affiliation.role
+    from affiliation
+    join organization on organization.organization_id = affiliation.organization_id
+    where affiliation.person_id = $1
+    order by organization.organization_name, affiliation.affiliation_id;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &person_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Membership::from_row_ref)
+    .collect::<Result<Vec<Membership>, _>>()?)
+}
+
+pub async fn create_participation(client: &impl GenericClient, participation_info: Participation) -> Result<Participation, MyError> {
+    let _stmt = "insert into participation(event_id, person_id, rsvp_status, responded_at) values ($1, $2, $3, case when $3 = 'invited'::rsvp_status then null else now() end) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Participation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &participation_info.event_id,
+            &participation_info.person_id,
+            &participation_info.rsvp_status,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participation::from_row_ref)
+    .collect::<Result<Vec<Participation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// Records the RSVP answer of the participating person; the response timestamp only moves when the status actually changes.
+pub async fn modify_participation(client: &impl GenericClient, participation_id: i32, person_id: i32, rsvp_status: RsvpStatus) -> Result<Participation, MyError> {
+    let _stmt = "update participation set
+        responded_at = case
+            when $1 = 'invited'::rsvp_status then null
+            when rsvp_status = $1 then responded_at
+            else now()
+        end,
+        rsvp_status = $1
+    where participation_id = $2 and person_id = $3 returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Participation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &rsvp_status,
+            &participation_id,
+            &person_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participation::from_row_ref)
+    .collect::<Result<Vec<Participation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn get_event_participants(client: &impl GenericClient, event_id: i32) -> Result<Vec<Participant>, MyError> {
+    let _stmt = "select participation.participation_id, participation.event_id, participation.person_id, person.person_name, participation.rsvp_status, participation.responded_at
+    from participation
+    join person on person.person_id = participation.person_id
+    where participation.event_id = $1
+    order by participation.participation_id;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &event_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participant::from_row_ref)
+    .collect::<Result<Vec<Participant>, _>>()?)
+}
+
+pub async fn get_person_participations(client: &impl GenericClient, person_id: i32) -> Result<Vec<Participation>, MyError> {
+    let _stmt = "select $table_fields from participation where person_id = $1 order by participation_id;";
+    let _stmt = _stmt.replace("$table_fields", &Participation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &person_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participation::from_row_ref)
+    .collect::<Result<Vec<Participation>, _>>()?)
+}
+
+pub async fn get_participation(client: &impl GenericClient, participation_id: i32) -> Result<Participation, MyError> {
+    let _stmt = "select $table_fields from participation where participation_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Participation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &participation_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participation::from_row_ref)
+    .collect::<Result<Vec<Participation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// The person's own participations and those in events planned in their planners, whatever their role.
+pub async fn list_participations(client: &impl GenericClient, person_id: i32, after: i32, limit: i64) -> Result<Vec<Participation>, MyError> {
+    let _stmt = "select $table_fields from participation
+        where (participation.person_id = $1 or participation.event_id in (select event_id from plan where plan.planner_id in ($member_planners)))
+        and participation_id > $2 order by participation_id limit $3;";
+    let _stmt = _stmt.replace("$table_fields", &Participation::sql_table_fields());
+    let _stmt = _stmt.replace("$member_planners", MEMBER_PLANNERS).replace("$roles", "'owner', 'admin', 'member', 'viewer'");
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &person_id,
+            &after,
+            &limit,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participation::from_row_ref)
+    .collect::<Result<Vec<Participation>, _>>()?)
+}
+
+pub async fn delete_participation(client: &impl GenericClient, participation_id: i32) -> Result<u64, MyError> {
+    let _stmt = "delete from participation where participation_id = $1;";
+    let _stmt = _stmt.to_string();
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &participation_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)
+}
+
+pub async fn create_organization(client: &impl GenericClient, organization_info: Organization) -> Result<Organization, MyError> {
+    let _stmt = "insert into organization(organization_name, planner_id) values ($1, $2) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Organization::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &organization_info.organization_name,
+            &organization_info.planner_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Organization::from_row_ref)
+    .collect::<Result<Vec<Organization>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn get_organization(client: &impl GenericClient, organization_id: i32) -> Result<Organization, MyError> {
+    let _stmt = "select $table_fields from organization where organization_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Organization::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &organization_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Organization::from_row_ref)
+    .collect::<Result<Vec<Organization>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn list_organizations(client: &impl GenericClient, filter: &Filter<Organization>, pagination: &Pagination) -> Result<Vec<Organization>, MyError> {
+    let filter = filter.compile(pagination, 1);
+    let _stmt = "select $table_fields from organization where true$conditions order by $order_by limit $limit;";
+    let _stmt = _stmt.replace("$table_fields", &Organization::sql_table_fields())
+        .replace("$conditions", &filter.conditions)
+        .replace("$order_by", &filter.order_by)
+        .replace("$limit", &filter.limit);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(&statement, &filter.params().collect::<Vec<_>>())
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Organization::from_row_ref)
+    .collect::<Result<Vec<Organization>, _>>()?)
+}
+
+pub async fn modify_organization(client: &impl GenericClient, organization_id: i32, organization_patch: &UpdateOrganization) -> Result<Organization, MyError> {
+    let mut organization_info = get_organization(client, organization_id).await?;
+    organization_patch.apply(&mut organization_info);
+
+    let mut changes = Changes::default();
+    if organization_patch.organization_name.is_supplied() {
+        changes.set("organization_name", &organization_info.organization_name);
+    }
+
+    if changes.is_empty() {
+        return Ok(organization_info);
+    }
+    update_row(client, "organization_id", &organization_id, &changes).await
+}
+
+pub async fn delete_organization(client: &impl GenericClient, organization_id: i32) -> Result<u64, MyError> {
+    let _stmt = "delete from organization where organization_id = $1;";
+    let _stmt = _stmt.to_string();
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &organization_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)
+}
+
+
+pub async fn create_invitation(client: &impl GenericClient, invitation_info: Invitation) -> Result<Invitation, MyError> {
+    let _stmt = "insert into invitation(token, event_id, organization_id, rsvp_status, role, expires_at, max_uses, created_by) values ($1, $2, $3, $4, $5, $6, $7, $8) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Invitation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &invitation_info.token,
+            &invitation_info.event_id,
+            &invitation_info.organization_id,
+            &invitation_info.rsvp_status,
+            &invitation_info.role,
+            &invitation_info.expires_at,
+            &invitation_info.max_uses,
+            &invitation_info.created_by,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Invitation::from_row_ref)
+    .collect::<Result<Vec<Invitation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn get_invitation(client: &impl GenericClient, invitation_id: i32) -> Result<Invitation, MyError> {
+    let _stmt = "select $table_fields from invitation where invitation_id = $1;";
+    let _stmt = _stmt.replace("$table_fields", &Invitation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &invitation_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Invitation::from_row_ref)
+    .collect::<Result<Vec<Invitation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+// Invitations that can still be accepted: neither revoked, expired nor used up.
+const OUTSTANDING: &str = "revoked_at is null and expires_at > now() and uses < max_uses";
+
+/// The outstanding invitations to the event, oldest first.
+pub async fn get_event_invitations(client: &impl GenericClient, event_id: i32) -> Result<Vec<Invitation>, MyError> {
+    let _stmt = "select $table_fields from invitation where event_id = $1 and $outstanding order by invitation_id;";
+    let _stmt = _stmt.replace("$table_fields", &Invitation::sql_table_fields()).replace("$outstanding", OUTSTANDING);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &event_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Invitation::from_row_ref)
+    .collect::<Result<Vec<Invitation>, _>>()?)
+}
+
+/// The outstanding invitations to the organization, oldest first.
+pub async fn get_organization_invitations(client: &impl GenericClient, organization_id: i32) -> Result<Vec<Invitation>, MyError> {
+    let _stmt = "select $table_fields from invitation where organization_id = $1 and $outstanding order by invitation_id;";
+    let _stmt = _stmt.replace("$table_fields", &Invitation::sql_table_fields()).replace("$outstanding", OUTSTANDING);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &organization_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Invitation::from_row_ref)
+    .collect::<Result<Vec<Invitation>, _>>()?)
+}
+
+/// Revokes the invitation unless it already was; returns the number of invitations revoked.
+pub async fn revoke_invitation(client: &impl GenericClient, invitation_id: i32) -> Result<u64, MyError> {
+    let _stmt = "update invitation set revoked_at = now() where invitation_id = $1 and revoked_at is null;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    client.execute(
+        &statement,
+        &[
+            &invitation_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)
+}
+
+/// Counts one use of the outstanding invitation with this token and returns it; unknown, revoked,
+/// expired and used up invitations are all not found alike.
+pub async fn use_invitation(client: &impl GenericClient, token: &str) -> Result<Invitation, MyError> {
+    let _stmt = "update invitation set uses = uses + 1 where token = $1 and $outstanding returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Invitation::sql_table_fields()).replace("$outstanding", OUTSTANDING);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &token,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Invitation::from_row_ref)
+    .collect::<Result<Vec<Invitation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// The outstanding invitation with this token, without using it.
+pub async fn get_invitation_by_token(client: &impl GenericClient, token: &str) -> Result<Invitation, MyError> {
+    let _stmt = "select $table_fields from invitation where token = $1 and $outstanding;";
+    let _stmt = _stmt.replace("$table_fields", &Invitation::sql_table_fields()).replace("$outstanding", OUTSTANDING);
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &token,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Invitation::from_row_ref)
+    .collect::<Result<Vec<Invitation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+pub async fn create_guest(client: &impl GenericClient, guest_info: Guest) -> Result<Guest, MyError> {
+    let _stmt = "insert into guest(token, event_id, invitation_id, guest_name, email, rsvp_status, plus_ones) values ($1, $2, $3, $4, $5, $6, $7) returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Guest::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &guest_info.token,
+            &guest_info.event_id,
+            &guest_info.invitation_id,
+            &guest_info.guest_name,
+            &guest_info.email,
+            &guest_info.rsvp_status,
+            &guest_info.plus_ones,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Guest::from_row_ref)
+    .collect::<Result<Vec<Guest>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// The guest with this token, as long as they have not been merged into a person.
+pub async fn get_guest(client: &impl GenericClient, token: &str) -> Result<Guest, MyError> {
+    let _stmt = "select $table_fields from guest where token = $1 and person_id is null;";
+    let _stmt = _stmt.replace("$table_fields", &Guest::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &token,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Guest::from_row_ref)
+    .collect::<Result<Vec<Guest>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// Replaces the answer of the unmerged guest with `guest_info.token`; like participations, the
+/// response timestamp only moves when the status changes.
+pub async fn modify_guest(client: &impl GenericClient, guest_info: Guest) -> Result<Guest, MyError> {
+    let _stmt = "update guest set
+        guest_name = $2,
+        email = $3,
+        responded_at = case when rsvp_status = $4 then responded_at else now() end,
+        rsvp_status = $4,
+        plus_ones = $5
+        where token = $1 and person_id is null
+        returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Guest::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &guest_info.token,
+            &guest_info.guest_name,
+            &guest_info.email,
+            &guest_info.rsvp_status,
+            &guest_info.plus_ones,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Guest::from_row_ref)
+    .collect::<Result<Vec<Guest>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+/// The guests of the event, merged or not, in the order they answered first.
+pub async fn get_event_guests(client: &impl GenericClient, event_id: i32) -> Result<Vec<Guest>, MyError> {
+    let _stmt = "select $table_fields from guest where event_id = $1 order by guest_id;";
+    let _stmt = _stmt.replace("$table_fields", &Guest::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &event_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Guest::from_row_ref)
+    .collect::<Result<Vec<Guest>, _>>()?)
+}
+
+/// Hands the unmerged guest holding this token over to the person: their answer becomes a
+/// participation, which is a conflict if the person already takes part in the event.
+pub async fn merge_guest(client: &impl GenericClient, token: &str, person_id: i32) -> Result<Participation, MyError> {
+    let _stmt = "with merged as (
+            update guest set person_id = $2
+            where token = $1 and person_id is null
+            returning event_id, rsvp_status, responded_at
+        )
+        insert into participation(event_id, person_id, rsvp_status, responded_at)
+        select event_id, $2, rsvp_status, responded_at from merged
+        returning $table_fields;";
+    let _stmt = _stmt.replace("$table_fields", &Participation::sql_table_fields());
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    client.query(
+        &statement,
+        &[
+            &token,
+            &person_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(Participation::from_row_ref)
+    .collect::<Result<Vec<Participation>, _>>()?
+    .pop()
+    .ok_or(MyError::NotFound)
+}
+
+// Planners whose events a person may edit: their own and those of the organizations
+// they belong to with a role in `$roles`.
+const MEMBER_PLANNERS: &str = "select planner_id from person where person_id = $1
+    union
+    select organization.planner_id from affiliation
+    join organization on organization.organization_id = affiliation.organization_id
+    where affiliation.person_id = $1 and affiliation.role in ($roles)";
+
+/// Managing a planner, i.e. adding or removing its plans, takes an owner or admin role in organizations.
+pub async fn is_planner_member(client: &impl GenericClient, person_id: i32, planner_id: i32) -> Result<bool, MyError> {
+    let _stmt = "select exists(select 1 from ($member_planners) member_planner where member_planner.planner_id = $2);";
+    let _stmt = _stmt.replace("$member_planners", MEMBER_PLANNERS).replace("$roles", "'owner', 'admin'");
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query_one(
+        &statement,
+        &[
+            &person_id,
+            &planner_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .get(0))
+}
+
+/// An event belongs to the members of every planner it is planned in; viewers only get to read it.
+pub async fn is_event_member(client: &impl GenericClient, person_id: i32, event_id: i32) -> Result<bool, MyError> {
+    let _stmt = "select exists(select 1 from plan where plan.event_id = $2 and plan.planner_id in ($member_planners));";
+    let _stmt = _stmt.replace("$member_planners", MEMBER_PLANNERS).replace("$roles", "'owner', 'admin', 'member'");
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query_one(
+        &statement,
+        &[
+            &person_id,
+            &event_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .get(0))
+}
+
+pub async fn get_organization_role(client: &impl GenericClient, person_id: i32, organization_id: i32) -> Result<Option<OrganizationRole>, MyError> {
+    let _stmt = "select role from affiliation where person_id = $1 and organization_id = $2;";
+    let statement = client.prepare(_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query_opt(
+        &statement,
+        &[
+            &person_id,
+            &organization_id,
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .map(|row| row.get(0)))
+}
+
+// Matches the words of a search both as written and as French stems, like the `search_vector` columns.
+const SEARCH_QUERY: &str = "websearch_to_tsquery('simple', $text) || websearch_to_tsquery('french', $text)";
+
+/// Events are only searched among those of the planners the person belongs to.
+pub async fn search_events(client: &impl GenericClient, person_id: i32, text: &str, limit: i64) -> Result<Vec<EventHit>, MyError> {
+    let _stmt = "with search as (select $search_query as query)
+    select event.event_id, event.event_name, event.starts_at,
+        ts_headline('french', concat_ws(' · ', event.event_name, event.event_location, event.event_description), search.query, $4) as snippet,
+        ts_rank(event.search_vector, search.query) as rank
+    from event, search
+    where event.search_vector @@ search.query
+    and exists (select 1 from plan where plan.event_id = event.event_id and plan.planner_id in ($member_planners))
+    order by rank desc, event.event_id
+    limit $3;";
+    let _stmt = _stmt.replace("$search_query", &SEARCH_QUERY.replace("$text", "$2"))
+        .replace("$member_planners", MEMBER_PLANNERS)
+        .replace("$roles", "'owner', 'admin', 'member', 'viewer'");
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &person_id,
+            &text,
+            &limit,
+            &search::headline_options(),
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(EventHit::from_row_ref)
+    .map(|hit| hit.map(|hit| EventHit { snippet: search::highlight(&hit.snippet), ..hit }))
+    .collect::<Result<Vec<EventHit>, _>>()?)
+}
+
+pub async fn search_persons(client: &impl GenericClient, text: &str, limit: i64) -> Result<Vec<PersonHit>, MyError> {
+    let _stmt = "with search as (select $search_query as query)
+    select person.person_id, person.person_name,
+        ts_headline('simple', person.person_name, search.query, $3) as snippet,
+        ts_rank(person.search_vector, search.query) as rank
+    from person, search
+    where person.search_vector @@ search.query
+    order by rank desc, person.person_id
+    limit $2;";
+    let _stmt = _stmt.replace("$search_query", &SEARCH_QUERY.replace("$text", "$1"));
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &text,
+            &limit,
+            &search::headline_options(),
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(PersonHit::from_row_ref)
+    .map(|hit| hit.map(|hit| PersonHit { snippet: search::highlight(&hit.snippet), ..hit }))
+    .collect::<Result<Vec<PersonHit>, _>>()?)
+}
+
+pub async fn search_organizations(client: &impl GenericClient, text: &str, limit: i64) -> Result<Vec<OrganizationHit>, MyError> {
+    let _stmt = "with search as (select $search_query as query)
+    select organization.organization_id, organization.organization_name,
+        ts_headline('french', organization.organization_name, search.query, $3) as snippet,
+        ts_rank(organization.search_vector, search.query) as rank
+    from organization, search
+    where organization.search_vector @@ search.query
+    order by rank desc, organization.organization_id
+    limit $2;";
+    let _stmt = _stmt.replace("$search_query", &SEARCH_QUERY.replace("$text", "$1"));
+    let statement = client.prepare(&_stmt).await.map_err(MyError::PGError)?;
+
+    Ok(client.query(
+        &statement,
+        &[
+            &text,
+            &limit,
+            &search::headline_options(),
+        ]
+    )
+    .await
+    .map_err(MyError::PGError)?
+    .iter()
+    .map(OrganizationHit::from_row_ref)
+    .map(|hit| hit.map(|hit| OrganizationHit { snippet: search::highlight(&hit.snippet), ..hit }))
+    .collect::<Result<Vec<OrganizationHit>, _>>()?)
+}
+
+pub async fn search(client: &impl GenericClient, person_id: i32, text: &str, limit: i64) -> Result<SearchResults, MyError> {
+    Ok(SearchResults {
+        events: search_events(client, person_id, text, limit).await?,
+        persons: search_persons(client, text, limit).await?,
+        organizations: search_organizations(client, text, limit).await?,
+    })
+}
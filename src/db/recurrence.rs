@@ -0,0 +1,379 @@
+use chrono::{DateTime, Duration, Utc};
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
+
+use crate::{
+    db::errors::MyError,
+    db::models::{
+        Event,
+        EventOccurrence,
+        EventOverride
+    }
+};
+
+// Upper bound on the number of occurrences expanded for a single series.
+const MAX_OCCURRENCES: u16 = 1000;
+
+fn time_zone(event_info: &Event) -> Result<Tz, MyError> {
+    event_info.time_zone.parse::<chrono_tz::Tz>()
+        .map(Tz::Tz)
+        .map_err(|_| MyError::BadRequest(format!("unknown time zone {}", event_info.time_zone)))
+}
+
+pub fn is_recurring(event_info: &Event) -> bool {
+    event_info.recurrence_rule.is_some() || !event_info.recurrence_dates.is_empty()
+}
+
+fn rule_set(event_info: &Event) -> Result<RRuleSet, MyError> {
+    let tz = time_zone(event_info)?;
+    let dt_start = event_info.starts_at.with_timezone(&tz);
+
+    let mut set = match event_info.recurrence_rule {
+        Some(ref rule) => rule.trim_start_matches("RRULE:")
+            .parse::<RRule<Unvalidated>>()
+            .and_then(|rule| rule.build(dt_start))
+            .map_err(|err| MyError::BadRequest(format!("invalid recurrence rule: {}", err)))?,
+        // Without a rule, the first occurrence has to be listed explicitly next to the RDATEs.
+        None => RRuleSet::new(dt_start).rdate(dt_start),
+    };
+
+    for rdate in &event_info.recurrence_dates {
+        set = set.rdate(rdate.with_timezone(&tz));
+    }
+    for exdate in &event_info.exception_dates {
+        set = set.exdate(exdate.with_timezone(&tz));
+    }
+
+    Ok(set)
+}
+
+/// The starts of `set`, or `None` if there are more than `MAX_OCCURRENCES` of them.
+fn all_starts(set: RRuleSet) -> Option<Vec<DateTime<Utc>>> {
+    let result = set.all(MAX_OCCURRENCES + 1);
+    (!result.limited).then(|| result.dates.iter().map(|start| start.with_timezone(&Utc)).collect())
+}
+
+/// Returns when the last occurrence of the series ends, or `None` if the series never ends.
+///
+/// A finite series too long to list is bounded by its `UNTIL` instead; one bounded by a `COUNT`
+/// beyond `MAX_OCCURRENCES` is refused rather than taken for never ending.
+pub fn series_end(event_info: &Event) -> Result<Option<DateTime<Utc>>, MyError> {
+    if !is_recurring(event_info) {
+        return Ok(Some(event_info.ends_at));
+    }
+
+    if event_info.recurrence_dates.iter().any(|rdate| *rdate < event_info.starts_at) {
+        return Err(MyError::BadRequest("recurrence_dates must not be before starts_at".to_string()));
+    }
+
+    let set = rule_set(event_info)?;
+    let rule = set.get_rrule().first();
+    if rule.is_some_and(|rule| rule.get_count().is_none() && rule.get_until().is_none()) {
+        return Ok(None);
+    }
+    let until = rule.and_then(|rule| rule.get_until()).map(|until| until.with_timezone(&Utc));
+    let counted = rule.is_some() && until.is_none();
+
+    let duration = event_info.ends_at - event_info.starts_at;
+    let last_start = match all_starts(set) {
+        Some(starts) => starts.last().copied(),
+        None if counted => {
+            return Err(MyError::BadRequest(format!("recurrence_rule must not yield more than {} occurrences", MAX_OCCURRENCES)));
+        }
+        None => until.into_iter().chain(event_info.recurrence_dates.iter().copied()).max(),
+    };
+
+    Ok(Some(last_start.map(|start| start + duration).unwrap_or(event_info.ends_at)))
+}
+
+/// Checks that `occurrence_starts_at` is one of the occurrences generated by the series.
+pub fn is_occurrence(event_info: &Event, occurrence_starts_at: DateTime<Utc>) -> Result<bool, MyError> {
+    if !is_recurring(event_info) {
+        return Ok(event_info.starts_at == occurrence_starts_at);
+    }
+
+    let tz = time_zone(event_info)?;
+    let around = occurrence_starts_at.with_timezone(&tz);
+    let result = rule_set(event_info)?
+        .after(around - Duration::seconds(1))
+        .before(around + Duration::seconds(1))
+        .all(MAX_OCCURRENCES);
+
+    Ok(result.dates.contains(&around))
+}
+
+fn apply_override(event_info: &Event, occurrence_starts_at: DateTime<Utc>, event_override: Option<&EventOverride>) -> Option<EventOccurrence> {
+    let duration = event_info.ends_at - event_info.starts_at;
+    let mut event = event_info.clone();
+    event.starts_at = occurrence_starts_at;
+    event.ends_at = occurrence_starts_at + duration;
+
+    if let Some(event_override) = event_override {
+        if event_override.cancelled {
+            return None;
+        }
+        if let Some(ref name) = event_override.event_name {
+            event.event_name = name.clone();
+        }
+        if let Some(ref location) = event_override.event_location {
+            event.event_location = location.clone();
+        }
+        if let Some(ref description) = event_override.event_description {
+            event.event_description = description.clone();
+        }
+        if let Some(starts_at) = event_override.starts_at {
+            event.starts_at = starts_at;
+            event.ends_at = starts_at + duration;
+        }
+        if let Some(ends_at) = event_override.ends_at {
+            event.ends_at = ends_at;
+        }
+    }
+
+    Some(EventOccurrence { event, occurrence_starts_at })
+}
+
+/// Expands a series into the occurrences overlapping `[from, to)`, with `overrides` applied.
+///
+/// Windows holding more than `MAX_OCCURRENCES` occurrences of the series are refused rather than
+/// cut short.
+pub fn expand(
+    event_info: &Event,
+    overrides: &[EventOverride],
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<EventOccurrence>, MyError> {
+    let mut starts = if is_recurring(event_info) {
+        let tz = time_zone(event_info)?;
+        let duration = event_info.ends_at - event_info.starts_at;
+
+        let mut set = rule_set(event_info)?;
+        if let Some(from) = from {
+            set = set.after((from - duration - Duration::seconds(1)).with_timezone(&tz));
+        }
+        if let Some(to) = to {
+            set = set.before(to.with_timezone(&tz));
+        }
+
+        all_starts(set)
+            .ok_or_else(|| MyError::BadRequest(format!(
+                "more than {} occurrences of event {} in the requested period; narrow it",
+                MAX_OCCURRENCES,
+                event_info.event_id.unwrap_or_default()
+            )))?
+    } else {
+        vec![event_info.starts_at]
+    };
+
+    // Occurrences moved into the window from outside of it, unless the series has since dropped
+    // them: exclusions win over the overrides of the occurrences they exclude.
+    for event_override in overrides.iter().filter(|o| Some(o.event_id) == event_info.event_id) {
+        if !starts.contains(&event_override.occurrence_starts_at)
+            && (event_override.starts_at.is_some() || event_override.ends_at.is_some())
+            && is_occurrence(event_info, event_override.occurrence_starts_at)?
+        {
+            starts.push(event_override.occurrence_starts_at);
+        }
+    }
+
+    let mut occurrences = starts
+        .into_iter()
+        .filter_map(|start| {
+            let event_override = overrides
+                .iter()
+                .find(|o| Some(o.event_id) == event_info.event_id && o.occurrence_starts_at == start);
+            apply_override(event_info, start, event_override)
+        })
+        .filter(|occurrence| {
+            to.is_none_or(|to| occurrence.event.starts_at < to)
+                && from.is_none_or(|from| occurrence.event.ends_at >= from)
+        })
+        .collect::<Vec<EventOccurrence>>();
+
+    occurrences.sort_by_key(|occurrence| occurrence.event.starts_at);
+
+    Ok(occurrences)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{expand, is_occurrence, series_end, MAX_OCCURRENCES};
+    use crate::db::models::{Event, EventOverride};
+
+    fn standup(rule: Option<&str>) -> Event {
+        Event {
+            event_id: Some(1),
+            event_name: "standup".to_string(),
+            event_location: "Paris".to_string(),
+            event_description: "Daily standup".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 6, 7, 30, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 6, 7, 45, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: rule.map(str::to_string),
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn single_event_is_its_own_occurrence() {
+        let event = standup(None);
+        let occurrences = expand(&event, &[], None, None).unwrap();
+        assert_eq!(occurrences.len(), 1);
+        assert_eq!(occurrences[0].event.starts_at, event.starts_at);
+        assert_eq!(series_end(&event).unwrap(), Some(event.ends_at));
+    }
+
+    #[test]
+    fn weekly_rule_is_expanded_in_window() {
+        let event = standup(Some("FREQ=WEEKLY;BYDAY=MO"));
+        let occurrences = expand(
+            &event,
+            &[],
+            Some(Utc.with_ymd_and_hms(2022, 6, 10, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2022, 7, 1, 0, 0, 0).unwrap()),
+        ).unwrap();
+
+        let starts = occurrences.iter().map(|o| o.event.starts_at).collect::<Vec<_>>();
+        assert_eq!(starts, vec![
+            Utc.with_ymd_and_hms(2022, 6, 13, 7, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2022, 6, 20, 7, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2022, 6, 27, 7, 30, 0).unwrap(),
+        ]);
+        assert!(occurrences.iter().all(|o| o.event.ends_at - o.event.starts_at == chrono::Duration::minutes(15)));
+        assert_eq!(series_end(&event).unwrap(), None);
+    }
+
+    #[test]
+    fn local_time_is_kept_across_dst() {
+        let mut event = standup(Some("FREQ=MONTHLY;COUNT=6"));
+        event.starts_at = Utc.with_ymd_and_hms(2022, 1, 10, 18, 0, 0).unwrap();
+        event.ends_at = Utc.with_ymd_and_hms(2022, 1, 10, 20, 0, 0).unwrap();
+
+        let occurrences = expand(&event, &[], None, None).unwrap();
+        assert_eq!(occurrences.len(), 6);
+        // 19:00 in Paris is 18:00 UTC in winter and 17:00 UTC in summer.
+        assert_eq!(occurrences[5].event.starts_at, Utc.with_ymd_and_hms(2022, 6, 10, 17, 0, 0).unwrap());
+        assert_eq!(series_end(&event).unwrap(), Some(Utc.with_ymd_and_hms(2022, 6, 10, 19, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn rdates_and_exdates_are_applied() {
+        let mut event = standup(Some("FREQ=DAILY;COUNT=3"));
+        event.exception_dates = vec![Utc.with_ymd_and_hms(2022, 6, 7, 7, 30, 0).unwrap()];
+        event.recurrence_dates = vec![Utc.with_ymd_and_hms(2022, 6, 20, 9, 0, 0).unwrap()];
+
+        let starts = expand(&event, &[], None, None).unwrap()
+            .iter()
+            .map(|o| o.event.starts_at)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![
+            Utc.with_ymd_and_hms(2022, 6, 6, 7, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2022, 6, 8, 7, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2022, 6, 20, 9, 0, 0).unwrap(),
+        ]);
+        assert!(is_occurrence(&event, Utc.with_ymd_and_hms(2022, 6, 8, 7, 30, 0).unwrap()).unwrap());
+        assert!(!is_occurrence(&event, Utc.with_ymd_and_hms(2022, 6, 7, 7, 30, 0).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn overrides_cancel_and_move_occurrences() {
+        let event = standup(Some("FREQ=DAILY;COUNT=3"));
+        let overrides = vec![
+            EventOverride {
+                override_id: Some(1),
+                event_id: 1,
+                occurrence_starts_at: Utc.with_ymd_and_hms(2022, 6, 6, 7, 30, 0).unwrap(),
+                cancelled: true,
+                event_name: None,
+                event_location: None,
+                event_description: None,
+                starts_at: None,
+                ends_at: None,
+            },
+            EventOverride {
+                override_id: Some(2),
+                event_id: 1,
+                occurrence_starts_at: Utc.with_ymd_and_hms(2022, 6, 7, 7, 30, 0).unwrap(),
+                cancelled: false,
+                event_name: Some("standup (remote)".to_string()),
+                event_location: None,
+                event_description: None,
+                starts_at: Some(Utc.with_ymd_and_hms(2022, 6, 30, 8, 0, 0).unwrap()),
+                ends_at: None,
+            },
+        ];
+
+        let occurrences = expand(
+            &event,
+            &overrides,
+            Some(Utc.with_ymd_and_hms(2022, 6, 8, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2022, 7, 1, 0, 0, 0).unwrap()),
+        ).unwrap();
+
+        assert_eq!(occurrences.len(), 2);
+        assert_eq!(occurrences[0].occurrence_starts_at, Utc.with_ymd_and_hms(2022, 6, 8, 7, 30, 0).unwrap());
+        assert_eq!(occurrences[1].event.event_name, "standup (remote)");
+        assert_eq!(occurrences[1].event.starts_at, Utc.with_ymd_and_hms(2022, 6, 30, 8, 0, 0).unwrap());
+        assert_eq!(occurrences[1].event.ends_at, Utc.with_ymd_and_hms(2022, 6, 30, 8, 15, 0).unwrap());
+    }
+
+    #[test]
+    fn excluded_occurrences_stay_excluded_when_moved() {
+        let mut event = standup(Some("FREQ=DAILY;COUNT=3"));
+        let moved = EventOverride {
+            override_id: Some(1),
+            event_id: 1,
+            occurrence_starts_at: Utc.with_ymd_and_hms(2022, 6, 7, 7, 30, 0).unwrap(),
+            cancelled: false,
+            event_name: None,
+            event_location: None,
+            event_description: None,
+            starts_at: Some(Utc.with_ymd_and_hms(2022, 6, 30, 8, 0, 0).unwrap()),
+            ends_at: None,
+        };
+        assert_eq!(expand(&event, std::slice::from_ref(&moved), None, None).unwrap().len(), 3);
+
+        event.exception_dates = vec![moved.occurrence_starts_at];
+        let starts = expand(&event, &[moved], None, None).unwrap()
+            .iter()
+            .map(|o| o.event.starts_at)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![
+            Utc.with_ymd_and_hms(2022, 6, 6, 7, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2022, 6, 8, 7, 30, 0).unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn long_series_are_reported_not_truncated() {
+        let event = standup(Some(&format!("FREQ=DAILY;COUNT={}", MAX_OCCURRENCES)));
+        assert_eq!(expand(&event, &[], None, None).unwrap().len(), usize::from(MAX_OCCURRENCES));
+        assert!(series_end(&event).unwrap().is_some());
+
+        let event = standup(Some(&format!("FREQ=DAILY;COUNT={}", MAX_OCCURRENCES + 1)));
+        assert!(series_end(&event).is_err());
+        assert!(expand(&event, &[], None, None).is_err());
+        // A window short enough still lists its occurrences.
+        let window = expand(
+            &event,
+            &[],
+            Some(Utc.with_ymd_and_hms(2022, 6, 10, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2022, 6, 13, 0, 0, 0).unwrap()),
+        ).unwrap();
+        assert_eq!(window.len(), 3);
+
+        // Too long to list, a series with an end is bounded by it.
+        let event = standup(Some("FREQ=DAILY;UNTIL=20280606T073000Z"));
+        assert_eq!(series_end(&event).unwrap(), Some(Utc.with_ymd_and_hms(2028, 6, 6, 7, 45, 0).unwrap()));
+    }
+
+    #[test]
+    fn invalid_rule_is_rejected() {
+        let event = standup(Some("FREQ=SOMETIMES"));
+        assert!(series_end(&event).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! The storage behind the handlers, reached through `web::Data<dyn Repository>`.
+//!
+//! `Store` holds every read and write of `db::query`. A `Repository` hands out connections, and a
+//! connection opens transactions; both are stores, so the same calls run inside a transaction or
+//! outside of one. `postgres` is the implementation served by `main`; `memory` keeps the tables in
+//! process with the same semantics, so that handlers can be tested without a database.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+pub mod memory;
+pub mod postgres;
+
+pub use memory::MemoryRepository;
+pub use postgres::PgRepository;
+
+use crate::db::{
+    dto::{UpdateEvent, UpdateOrganization, UpdatePerson},
+    errors::MyError,
+    filter::Filter,
+    pagination::Pagination,
+    models::{
+        Account,
+        Affiliation,
+        Event,
+        EventOccurrence,
+        EventOverride,
+        Guest,
+        Invitation,
+        Member,
+        Membership,
+        Organization,
+        OrganizationRole,
+        Participant,
+        Participation,
+        Person,
+        Plan,
+        Planner,
+        RsvpStatus,
+        SearchResults
+    }
+};
+
+/// The tables whose rows carry a `version`, which `Store::lock_version` reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Versioned {
+    Event,
+    Person,
+    Affiliation,
+    Participation,
+    Organization,
+}
+
+/// Hands out connections to the storage.
+#[async_trait]
+pub trait Repository: Send + Sync {
+    async fn connect(&self) -> Result<Box<dyn Connection>, MyError>;
+}
+
+#[async_trait]
+pub trait Connection: Store {
+    /// Opens a transaction; dropping it without `commit` rolls it back.
+    async fn transaction(&mut self) -> Result<Box<dyn Transaction + '_>, MyError>;
+}
+
+#[async_trait]
+pub trait Transaction: Store {
+    async fn commit(self: Box<Self>) -> Result<(), MyError>;
+
+    async fn rollback(self: Box<Self>) -> Result<(), MyError>;
+}
+
+/// The queries of `db::query`, with the same arguments and results.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Locks the row of `table` whose key is `id` until the end of the transaction and returns
+    /// its version.
+    async fn lock_version(&self, table: Versioned, id: i32) -> Result<i32, MyError>;
+
+    async fn create_event(&self, event_info: Event) -> Result<Event, MyError>;
+
+    async fn create_imported_event(&self, event_info: Event, planner_id: i32, ical_uid: &str) -> Result<Event, MyError>;
+
+    async fn modify_event(&self, event_id: i32, event_patch: &UpdateEvent) -> Result<Event, MyError>;
+
+    async fn list_events(&self, person_id: i32, filter: &Filter<Event>, pagination: &Pagination) -> Result<Vec<Event>, MyError>;
+
+    async fn get_occurrences_between(&self, person_id: Option<i32>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EventOccurrence>, MyError>;
+
+    async fn get_planner_events(&self, planner_id: i32) -> Result<Vec<Event>, MyError>;
+
+    async fn get_event(&self, event_id: i32) -> Result<Event, MyError>;
+
+    async fn get_event_by_ical_uid(&self, planner_id: i32, ical_uid: &str) -> Result<Option<Event>, MyError>;
+
+    async fn delete_event(&self, event_id: i32) -> Result<u64, MyError>;
+
+    async fn get_event_overrides(&self, event_ids: &[i32]) -> Result<Vec<EventOverride>, MyError>;
+
+    async fn list_event_overrides(&self, event_id: i32, after: i32, limit: i64) -> Result<Vec<EventOverride>, MyError>;
+
+    async fn get_event_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<EventOverride, MyError>;
+
+    async fn create_event_override(&self, override_info: EventOverride) -> Result<EventOverride, MyError>;
+
+    async fn delete_event_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<u64, MyError>;
+
+    async fn create_plan(&self, plan_info: Plan) -> Result<Plan, MyError>;
+
+    async fn ensure_plan(&self, plan_info: Plan) -> Result<Option<Plan>, MyError>;
+
+    async fn get_plan(&self, plan_id: i32) -> Result<Plan, MyError>;
+
+    async fn list_plans(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Plan>, MyError>;
+
+    async fn delete_plan(&self, plan_id: i32) -> Result<u64, MyError>;
+
+    async fn create_planner(&self) -> Result<Planner, MyError>;
+
+    async fn get_planner(&self, planner_id: i32) -> Result<Planner, MyError>;
+
+    async fn list_planners(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Planner>, MyError>;
+
+    async fn delete_planner(&self, planner_id: i32) -> Result<u64, MyError>;
+
+    async fn delete_unused_planner(&self, planner_id: i32) -> Result<u64, MyError>;
+
+    async fn create_person(&self, person_info: Person) -> Result<Person, MyError>;
+
+    async fn get_person(&self, person_id: i32) -> Result<Person, MyError>;
+
+    async fn list_persons(&self, filter: &Filter<Person>, pagination: &Pagination) -> Result<Vec<Person>, MyError>;
+
+    async fn modify_person(&self, person_id: i32, person_patch: &UpdatePerson) -> Result<Person, MyError>;
+
+    async fn delete_person(&self, person_id: i32) -> Result<u64, MyError>;
+
+    async fn create_account(&self, person_id: i32, email: &str, password_hash: &str) -> Result<Account, MyError>;
+
+    async fn get_account_by_email(&self, email: &str) -> Result<Option<Account>, MyError>;
+
+    async fn create_session(&self, session_id: &str, person_id: i32, expires_at: DateTime<Utc>) -> Result<u64, MyError>;
+
+    async fn is_session_active(&self, session_id: &str, person_id: i32) -> Result<bool, MyError>;
+
+    async fn revoke_session(&self, session_id: &str) -> Result<u64, MyError>;
+
+    async fn create_affiliation(&self, affiliation_info: Affiliation) -> Result<Affiliation, MyError>;
+
+    async fn get_affiliation(&self, affiliation_id: i32) -> Result<Affiliation, MyError>;
+
+    async fn list_affiliations(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Affiliation>, MyError>;
+
+    async fn modify_affiliation(&self, affiliation_id: i32, role: OrganizationRole) -> Result<Affiliation, MyError>;
+
+    async fn delete_affiliation(&self, affiliation_id: i32) -> Result<u64, MyError>;
+
+    async fn get_organization_members(&self, organization_id: i32) -> Result<Vec<Member>, MyError>;
+
+    async fn get_person_organizations(&self, person_id: i32) -> Result<Vec<Membership>, MyError>;
+
+    async fn create_participation(&self, participation_info: Participation) -> Result<Participation, MyError>;
+
+    async fn modify_participation(&self, participation_id: i32, person_id: i32, rsvp_status: RsvpStatus) -> Result<Participation, MyError>;
+
+    async fn get_event_participants(&self, event_id: i32) -> Result<Vec<Participant>, MyError>;
+
+    async fn get_person_participations(&self, person_id: i32) -> Result<Vec<Participation>, MyError>;
+
+    async fn get_participation(&self, participation_id: i32) -> Result<Participation, MyError>;
+
+    async fn list_participations(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Participation>, MyError>;
+
+    async fn delete_participation(&self, participation_id: i32) -> Result<u64, MyError>;
+
+    async fn create_organization(&self, organization_info: Organization) -> Result<Organization, MyError>;
+
+    async fn get_organization(&self, organization_id: i32) -> Result<Organization, MyError>;
+
+    async fn list_organizations(&self, filter: &Filter<Organization>, pagination: &Pagination) -> Result<Vec<Organization>, MyError>;
+
+    async fn modify_organization(&self, organization_id: i32, organization_patch: &UpdateOrganization) -> Result<Organization, MyError>;
+
+    async fn delete_organization(&self, organization_id: i32) -> Result<u64, MyError>;
+
+    async fn create_invitation(&self, invitation_info: Invitation) -> Result<Invitation, MyError>;
+
+    async fn get_invitation(&self, invitation_id: i32) -> Result<Invitation, MyError>;
+
+    async fn get_event_invitations(&self, event_id: i32) -> Result<Vec<Invitation>, MyError>;
+
+    async fn get_organization_invitations(&self, organization_id: i32) -> Result<Vec<Invitation>, MyError>;
+
+    async fn revoke_invitation(&self, invitation_id: i32) -> Result<u64, MyError>;
+
+    async fn use_invitation(&self, token: &str) -> Result<Invitation, MyError>;
+
+    async fn get_invitation_by_token(&self, token: &str) -> Result<Invitation, MyError>;
+
+    async fn create_guest(&self, guest_info: Guest) -> Result<Guest, MyError>;
+
+    async fn get_guest(&self, token: &str) -> Result<Guest, MyError>;
+
+    async fn modify_guest(&self, guest_info: Guest) -> Result<Guest, MyError>;
+
+    async fn get_event_guests(&self, event_id: i32) -> Result<Vec<Guest>, MyError>;
+
+    async fn merge_guest(&self, token: &str, person_id: i32) -> Result<Participation, MyError>;
+
+    async fn is_planner_member(&self, person_id: i32, planner_id: i32) -> Result<bool, MyError>;
+
+    async fn is_event_member(&self, person_id: i32, event_id: i32) -> Result<bool, MyError>;
+
+    async fn get_organization_role(&self, person_id: i32, organization_id: i32) -> Result<Option<OrganizationRole>, MyError>;
+
+    async fn search(&self, person_id: i32, text: &str, limit: i64) -> Result<SearchResults, MyError>;
+}
@@ -0,0 +1,1285 @@
+//! A repository kept in process memory, so that handlers can be tested without a database.
+//!
+//! It enforces what the schema does: unique and foreign keys, cascades, the trigger that keeps an
+//! owner in every organization and row versions, with the errors Postgres would raise. Every
+//! statement applies entirely or not at all. A transaction works on a copy of the tables that
+//! `commit` puts back, and holds the tables until it ends, so transactions run one at a time.
+//! Sequences are not transactional, so ids are never handed out twice. Search matches words
+//! and their plurals in place of the stemmers of Postgres, and ranks them by the same weights.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicI32, Ordering as AtomicOrdering},
+        Arc,
+        Mutex as StdMutex,
+        PoisonError,
+    },
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio_postgres::error::SqlState;
+
+use crate::db::{
+    dto::{UpdateEvent, UpdateOrganization, UpdatePerson},
+    errors::MyError,
+    filter::Filter,
+    pagination::Pagination,
+    query,
+    recurrence,
+    repository::{Connection, Repository, Store, Transaction, Versioned},
+    search,
+    models::{
+        Account,
+        Affiliation,
+        Event,
+        EventHit,
+        EventOccurrence,
+        EventOverride,
+        Guest,
+        Invitation,
+        Member,
+        Membership,
+        Organization,
+        OrganizationHit,
+        OrganizationRole,
+        Participant,
+        Participation,
+        Person,
+        PersonHit,
+        Plan,
+        Planner,
+        RsvpStatus,
+        SearchResults
+    }
+};
+
+const ANY_ROLE: &[OrganizationRole] = &[OrganizationRole::Owner, OrganizationRole::Admin, OrganizationRole::Member, OrganizationRole::Viewer];
+const EDITOR_ROLES: &[OrganizationRole] = &[OrganizationRole::Owner, OrganizationRole::Admin, OrganizationRole::Member];
+const MANAGER_ROLES: &[OrganizationRole] = &[OrganizationRole::Owner, OrganizationRole::Admin];
+
+// The default weights of `ts_rank` for the `A`, `B` and `C` labels of `setweight`.
+const WEIGHT_A: f32 = 1.0;
+const WEIGHT_B: f32 = 0.4;
+const WEIGHT_C: f32 = 0.2;
+
+#[derive(Default)]
+pub struct MemoryRepository {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        MemoryRepository::default()
+    }
+}
+
+#[async_trait]
+impl Repository for MemoryRepository {
+    async fn connect(&self) -> Result<Box<dyn Connection>, MyError> {
+        Ok(Box::new(MemoryStore { scope: Shared(self.tables.clone()) }))
+    }
+}
+
+fn unique(taken: bool) -> Result<(), MyError> {
+    if taken {
+        Err(MyError::Violation(SqlState::UNIQUE_VIOLATION))
+    } else {
+        Ok(())
+    }
+}
+
+fn reference(exists: bool) -> Result<(), MyError> {
+    if exists {
+        Ok(())
+    } else {
+        Err(MyError::Violation(SqlState::FOREIGN_KEY_VIOLATION))
+    }
+}
+
+/// The rows of a table by key, and the sequence its keys come from.
+#[derive(Clone)]
+struct Table<T> {
+    rows: BTreeMap<i32, T>,
+    sequence: Arc<AtomicI32>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table { rows: BTreeMap::new(), sequence: Arc::default() }
+    }
+}
+
+impl<T: Clone> Table<T> {
+    fn next_id(&self) -> i32 {
+        self.sequence.fetch_add(1, AtomicOrdering::Relaxed) + 1
+    }
+
+    fn get(&self, id: i32) -> Result<T, MyError> {
+        self.rows.get(&id).cloned().ok_or(MyError::NotFound)
+    }
+
+    fn contains(&self, id: i32) -> bool {
+        self.rows.contains_key(&id)
+    }
+
+    /// Up to `limit` rows keyed after `after`, by key.
+    fn page(&self, after: i32, limit: i64, include: impl Fn(&T) -> bool) -> Vec<T> {
+        self.rows.range(after.saturating_add(1)..)
+            .map(|(_, row)| row)
+            .filter(|row| include(row))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect()
+    }
+
+    fn remove_where(&mut self, remove: impl Fn(&T) -> bool) -> Vec<T> {
+        let keys = self.rows.iter().filter(|(_, row)| remove(row)).map(|(key, _)| *key).collect::<Vec<i32>>();
+        keys.iter().filter_map(|key| self.rows.remove(key)).collect()
+    }
+}
+
+/// An `event` row with the columns that `Event` leaves out.
+#[derive(Clone)]
+struct EventRow {
+    event: Event,
+    series_ends_at: Option<DateTime<Utc>>,
+    ical_planner_id: Option<i32>,
+    ical_uid: Option<String>,
+}
+
+#[derive(Clone)]
+struct Session {
+    person_id: i32,
+    expires_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Default)]
+struct Tables {
+    planners: Table<Planner>,
+    events: Table<EventRow>,
+    overrides: Table<EventOverride>,
+    plans: Table<Plan>,
+    persons: Table<Person>,
+    accounts: Table<Account>,
+    sessions: BTreeMap<String, Session>,
+    affiliations: Table<Affiliation>,
+    participations: Table<Participation>,
+    organizations: Table<Organization>,
+    invitations: Table<Invitation>,
+    guests: Table<Guest>,
+}
+
+/// Whether `[lower, upper]` overlaps `[from, to)`, missing bounds being infinite as in `tstzrange`.
+fn overlaps(lower: Option<DateTime<Utc>>, upper: Option<DateTime<Utc>>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> bool {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return false;
+        }
+    }
+    lower.zip(to).is_none_or(|(lower, to)| lower < to) && from.zip(upper).is_none_or(|(from, upper)| from <= upper)
+}
+
+/// Folds plurals together, standing in for the French stemmer.
+fn stem(word: &str) -> String {
+    let word = word.to_lowercase();
+    match word.strip_suffix(['s', 'x']) {
+        Some(singular) if singular.chars().count() >= 3 => singular.to_string(),
+        _ => word,
+    }
+}
+
+fn word_matches(query_word: &str, word: &str, stemmed: bool) -> bool {
+    query_word == word.to_lowercase() || (stemmed && stem(query_word) == stem(word))
+}
+
+/// A column of a `search_vector`: its text, its weight and whether it is stemmed.
+struct Indexed<'a> {
+    text: &'a str,
+    weight: f32,
+    stemmed: bool,
+}
+
+/// The rank of a row whose columns hold every word of the query, as the mean of the best weight
+/// each word is found with.
+fn rank(query: &[String], columns: &[Indexed]) -> Option<f32> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let mut total = 0.0;
+    for query_word in query {
+        total += columns.iter()
+            .filter(|column| search::words(column.text).any(|word| word_matches(query_word, word, column.stemmed)))
+            .map(|column| column.weight)
+            .reduce(f32::max)?;
+    }
+    Some(total / query.len() as f32)
+}
+
+fn by_rank(a: (f32, i32), b: (f32, i32)) -> std::cmp::Ordering {
+    b.0.total_cmp(&a.0).then(a.1.cmp(&b.1))
+}
+
+impl Tables {
+    fn lock_version(&self, table: Versioned, id: i32) -> Result<i32, MyError> {
+        match table {
+            Versioned::Event => self.events.get(id).map(|row| row.event.version),
+            Versioned::Person => self.persons.get(id).map(|person| person.version),
+            Versioned::Affiliation => self.affiliations.get(id).map(|affiliation| affiliation.version),
+            Versioned::Participation => self.participations.get(id).map(|participation| participation.version),
+            Versioned::Organization => self.organizations.get(id).map(|organization| organization.version),
+        }
+    }
+
+    /// The planners whose events the person may act on with one of `roles` in organizations.
+    fn member_planners(&self, person_id: i32, roles: &[OrganizationRole]) -> Vec<i32> {
+        let own = self.persons.rows.get(&person_id).and_then(|person| person.planner_id);
+        let organizations = self.affiliations.rows.values()
+            .filter(|affiliation| affiliation.person_id == person_id && roles.contains(&affiliation.role))
+            .filter_map(|affiliation| self.organizations.rows.get(&affiliation.organization_id)?.planner_id);
+        own.into_iter().chain(organizations).collect()
+    }
+
+    fn planned_events(&self, planner_id: Option<i32>) -> Vec<Event> {
+        let mut events = self.plans.rows.values()
+            .filter(|plan| Some(plan.planner_id) == planner_id)
+            .filter_map(|plan| self.events.rows.get(&plan.event_id))
+            .map(|row| row.event.clone())
+            .collect::<Vec<Event>>();
+        events.sort_by_key(|event| (event.starts_at, event.event_id));
+        events
+    }
+
+    fn own_planner(&self, person_id: Option<i32>) -> Option<i32> {
+        self.persons.rows.get(&person_id?)?.planner_id
+    }
+
+    fn insert_event(&mut self, event_info: Event, ical_planner_id: Option<i32>, ical_uid: Option<&str>) -> Result<Event, MyError> {
+        let series_ends_at = query::check_event_schedule(&event_info)?;
+
+        let event_id = self.events.next_id();
+        unique(ical_uid.is_some() && self.events.rows.values().any(|row| {
+            row.ical_planner_id == ical_planner_id && row.ical_uid.as_deref() == ical_uid
+        }))?;
+        reference(ical_planner_id.is_none_or(|planner_id| self.planners.contains(planner_id)))?;
+
+        let event = Event { event_id: Some(event_id), version: 1, ..event_info };
+        let row = EventRow { event: event.clone(), series_ends_at, ical_planner_id, ical_uid: ical_uid.map(str::to_string) };
+        self.events.rows.insert(event_id, row);
+        Ok(event)
+    }
+
+    fn modify_event(&mut self, event_id: i32, event_patch: &UpdateEvent) -> Result<Event, MyError> {
+        let row = self.events.rows.get_mut(&event_id).ok_or(MyError::NotFound)?;
+
+        let mut event_info = row.event.clone();
+        event_patch.apply(&mut event_info);
+        let series_ends_at = query::check_event_schedule(&event_info)?;
+
+        let supplied = event_patch.changes_schedule()
+            || event_patch.event_name.is_supplied()
+            || event_patch.event_location.is_supplied()
+            || event_patch.event_description.is_supplied();
+        if !supplied {
+            return Ok(event_info);
+        }
+
+        if event_patch.changes_schedule() {
+            row.series_ends_at = series_ends_at;
+        }
+        event_info.version += 1;
+        row.event = event_info.clone();
+        Ok(event_info)
+    }
+
+    fn get_events_between(&self, person_id: Option<i32>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<Event> {
+        let mut events = self.planned_events(self.own_planner(person_id));
+        events.retain(|event| {
+            let event_id = event.event_id.unwrap_or_default();
+            let series_ends_at = self.events.rows.get(&event_id).and_then(|row| row.series_ends_at);
+            overlaps(Some(event.starts_at), series_ends_at, from, to)
+                || self.overrides.rows.values().any(|event_override| {
+                    event_override.event_id == event_id && overlaps(event_override.starts_at, event_override.ends_at, from, to)
+                })
+        });
+        events
+    }
+
+    fn get_occurrences_between(&self, person_id: Option<i32>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EventOccurrence>, MyError> {
+        let events = self.get_events_between(person_id, from, to);
+
+        let event_ids = events.iter().filter_map(|event| event.event_id).collect::<Vec<i32>>();
+        let overrides = self.get_event_overrides(&event_ids);
+
+        let mut occurrences = Vec::new();
+        for event in events.iter() {
+            occurrences.extend(recurrence::expand(event, &overrides, from, to)?);
+        }
+        occurrences.sort_by_key(|occurrence| occurrence.event.starts_at);
+
+        Ok(occurrences)
+    }
+
+    fn delete_event(&mut self, event_id: i32) -> u64 {
+        if self.events.rows.remove(&event_id).is_none() {
+            return 0;
+        }
+        self.plans.remove_where(|plan| plan.event_id == event_id);
+        self.overrides.remove_where(|event_override| event_override.event_id == event_id);
+        self.participations.remove_where(|participation| participation.event_id == event_id);
+        self.invitations.remove_where(|invitation| invitation.event_id == Some(event_id));
+        self.guests.remove_where(|guest| guest.event_id == event_id);
+        1
+    }
+
+    fn get_event_overrides(&self, event_ids: &[i32]) -> Vec<EventOverride> {
+        self.overrides.rows.values()
+            .filter(|event_override| event_ids.contains(&event_override.event_id))
+            .cloned()
+            .collect()
+    }
+
+    fn find_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Option<&EventOverride> {
+        self.overrides.rows.values()
+            .find(|event_override| event_override.event_id == event_id && event_override.occurrence_starts_at == occurrence_starts_at)
+    }
+
+    fn create_event_override(&mut self, override_info: EventOverride) -> Result<EventOverride, MyError> {
+        reference(self.events.contains(override_info.event_id))?;
+
+        let override_id = match self.find_override(override_info.event_id, override_info.occurrence_starts_at) {
+            Some(existing) => existing.override_id.unwrap_or_default(),
+            None => self.overrides.next_id(),
+        };
+        let event_override = EventOverride { override_id: Some(override_id), ..override_info };
+        self.overrides.rows.insert(override_id, event_override.clone());
+        Ok(event_override)
+    }
+
+    fn delete_event_override(&mut self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> u64 {
+        self.overrides.remove_where(|event_override| {
+            event_override.event_id == event_id && event_override.occurrence_starts_at == occurrence_starts_at
+        }).len() as u64
+    }
+
+    fn has_plan(&self, event_id: i32, planner_id: i32) -> bool {
+        self.plans.rows.values().any(|plan| plan.event_id == event_id && plan.planner_id == planner_id)
+    }
+
+    fn create_plan(&mut self, plan_info: Plan) -> Result<Plan, MyError> {
+        unique(self.has_plan(plan_info.event_id, plan_info.planner_id))?;
+        reference(self.events.contains(plan_info.event_id) && self.planners.contains(plan_info.planner_id))?;
+
+        let plan_id = self.plans.next_id();
+        let plan = Plan { plan_id: Some(plan_id), ..plan_info };
+        self.plans.rows.insert(plan_id, plan.clone());
+        Ok(plan)
+    }
+
+    fn ensure_plan(&mut self, plan_info: Plan) -> Result<Option<Plan>, MyError> {
+        if self.has_plan(plan_info.event_id, plan_info.planner_id) {
+            return Ok(None);
+        }
+        self.create_plan(plan_info).map(Some)
+    }
+
+    fn create_planner(&mut self) -> Planner {
+        let planner = Planner { planner_id: self.planners.next_id() };
+        self.planners.rows.insert(planner.planner_id, planner.clone());
+        planner
+    }
+
+    fn planner_in_use(&self, planner_id: i32) -> bool {
+        self.persons.rows.values().any(|person| person.planner_id == Some(planner_id))
+            || self.organizations.rows.values().any(|organization| organization.planner_id == Some(planner_id))
+    }
+
+    fn remove_planner(&mut self, planner_id: i32) -> u64 {
+        if self.planners.rows.remove(&planner_id).is_none() {
+            return 0;
+        }
+        self.plans.remove_where(|plan| plan.planner_id == planner_id);
+        for row in self.events.rows.values_mut().filter(|row| row.ical_planner_id == Some(planner_id)) {
+            row.ical_planner_id = None;
+        }
+        1
+    }
+
+    fn delete_planner(&mut self, planner_id: i32) -> Result<u64, MyError> {
+        if self.planners.contains(planner_id) && self.planner_in_use(planner_id) {
+            return Err(MyError::Conflict(query::PLANNER_IN_USE.to_string()));
+        }
+        Ok(self.remove_planner(planner_id))
+    }
+
+    fn delete_unused_planner(&mut self, planner_id: i32) -> u64 {
+        if self.planner_in_use(planner_id) {
+            return 0;
+        }
+        self.remove_planner(planner_id)
+    }
+
+    fn create_person(&mut self, person_info: Person) -> Result<Person, MyError> {
+        let person_id = self.persons.next_id();
+        reference(person_info.planner_id.is_none_or(|planner_id| self.planners.contains(planner_id)))?;
+
+        let person = Person { person_id: Some(person_id), version: 1, ..person_info };
+        self.persons.rows.insert(person_id, person.clone());
+        Ok(person)
+    }
+
+    fn modify_person(&mut self, person_id: i32, person_patch: &UpdatePerson) -> Result<Person, MyError> {
+        let person = self.persons.rows.get_mut(&person_id).ok_or(MyError::NotFound)?;
+        if !person_patch.person_name.is_supplied() {
+            return Ok(person.clone());
+        }
+
+        person_patch.apply(person);
+        person.version += 1;
+        Ok(person.clone())
+    }
+
+    /// The owner trigger: an organization that still exists must keep an owner.
+    fn keep_owner(&self, organization_id: i32) -> Result<(), MyError> {
+        let has_owner = self.affiliations.rows.values()
+            .any(|affiliation| affiliation.organization_id == organization_id && affiliation.role == OrganizationRole::Owner);
+        if self.organizations.contains(organization_id) && !has_owner {
+            return Err(MyError::Conflict(query::OWNER_REQUIRED.to_string()));
+        }
+        Ok(())
+    }
+
+    fn delete_person(&mut self, person_id: i32) -> Result<u64, MyError> {
+        if self.persons.rows.remove(&person_id).is_none() {
+            return Ok(0);
+        }
+
+        let affiliations = self.affiliations.remove_where(|affiliation| affiliation.person_id == person_id);
+        for affiliation in affiliations.iter().filter(|affiliation| affiliation.role == OrganizationRole::Owner) {
+            self.keep_owner(affiliation.organization_id)?;
+        }
+        self.participations.remove_where(|participation| participation.person_id == person_id);
+        self.accounts.remove_where(|account| account.person_id == person_id);
+        self.sessions.retain(|_, session| session.person_id != person_id);
+        let invitations = self.invitations.remove_where(|invitation| invitation.created_by == person_id);
+        for guest in self.guests.rows.values_mut() {
+            if invitations.iter().any(|invitation| invitation.invitation_id == guest.invitation_id) {
+                guest.invitation_id = None;
+            }
+        }
+        self.guests.remove_where(|guest| guest.person_id == Some(person_id));
+        Ok(1)
+    }
+
+    fn create_account(&mut self, person_id: i32, email: &str, password_hash: &str) -> Result<Account, MyError> {
+        let account_id = self.accounts.next_id();
+        unique(self.accounts.rows.values().any(|account| account.person_id == person_id || account.email.to_lowercase() == email.to_lowercase()))?;
+        reference(self.persons.contains(person_id))?;
+
+        let account = Account { account_id, person_id, email: email.to_string(), password_hash: password_hash.to_string() };
+        self.accounts.rows.insert(account_id, account.clone());
+        Ok(account)
+    }
+
+    fn get_account_by_email(&self, email: &str) -> Option<Account> {
+        self.accounts.rows.values()
+            .find(|account| account.email.to_lowercase() == email.to_lowercase())
+            .cloned()
+    }
+
+    fn create_session(&mut self, session_id: &str, person_id: i32, expires_at: DateTime<Utc>) -> Result<u64, MyError> {
+        unique(self.sessions.contains_key(session_id))?;
+        reference(self.persons.contains(person_id))?;
+
+        self.sessions.insert(session_id.to_string(), Session { person_id, expires_at, revoked_at: None });
+        Ok(1)
+    }
+
+    fn is_session_active(&self, session_id: &str, person_id: i32) -> bool {
+        self.sessions.get(session_id).is_some_and(|session| {
+            session.person_id == person_id && session.revoked_at.is_none() && session.expires_at > Utc::now()
+        })
+    }
+
+    fn revoke_session(&mut self, session_id: &str) -> u64 {
+        match self.sessions.get_mut(session_id) {
+            Some(session) if session.revoked_at.is_none() => {
+                session.revoked_at = Some(Utc::now());
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    fn create_affiliation(&mut self, affiliation_info: Affiliation) -> Result<Affiliation, MyError> {
+        let affiliation_id = self.affiliations.next_id();
+        unique(self.affiliations.rows.values().any(|affiliation| {
+            affiliation.person_id == affiliation_info.person_id && affiliation.organization_id == affiliation_info.organization_id
+        }))?;
+        reference(self.persons.contains(affiliation_info.person_id) && self.organizations.contains(affiliation_info.organization_id))?;
+
+        let affiliation = Affiliation { affiliation_id: Some(affiliation_id), version: 1, ..affiliation_info };
+        self.affiliations.rows.insert(affiliation_id, affiliation.clone());
+        Ok(affiliation)
+    }
+
+    fn modify_affiliation(&mut self, affiliation_id: i32, role: OrganizationRole) -> Result<Affiliation, MyError> {
+        let affiliation = self.affiliations.rows.get_mut(&affiliation_id).ok_or(MyError::NotFound)?;
+        let previous_role = affiliation.role;
+
+        affiliation.role = role;
+        affiliation.version += 1;
+        let affiliation = affiliation.clone();
+
+        if previous_role == OrganizationRole::Owner {
+            self.keep_owner(affiliation.organization_id)?;
+        }
+        Ok(affiliation)
+    }
+
+    fn delete_affiliation(&mut self, affiliation_id: i32) -> Result<u64, MyError> {
+        match self.affiliations.rows.remove(&affiliation_id) {
+            Some(affiliation) => {
+                if affiliation.role == OrganizationRole::Owner {
+                    self.keep_owner(affiliation.organization_id)?;
+                }
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn get_organization_members(&self, organization_id: i32) -> Vec<Member> {
+        let mut members = self.affiliations.rows.values()
+            .filter(|affiliation| affiliation.organization_id == organization_id)
+            .filter_map(|affiliation| {
+                let person = self.persons.rows.get(&affiliation.person_id)?;
+                Some(Member {
+                    affiliation_id: affiliation.affiliation_id.unwrap_or_default(),
+                    organization_id: affiliation.organization_id,
+                    person_id: affiliation.person_id,
+                    person_name: person.person_name.clone(),
+                    role: affiliation.role,
+                })
+            })
+            .collect::<Vec<Member>>();
+        // Enums sort in the order of their values, as `organization_role` does.
+        members.sort_by(|a, b| {
+            (a.role as u8, &a.person_name, a.affiliation_id).cmp(&(b.role as u8, &b.person_name, b.affiliation_id))
+        });
+        members
+    }
+
+    fn get_person_organizations(&self, person_id: i32) -> Vec<Membership> {
+        let mut memberships = self.affiliations.rows.values()
+            .filter(|affiliation| affiliation.person_id == person_id)
+            .filter_map(|affiliation| {
+                let organization = self.organizations.rows.get(&affiliation.organization_id)?;
+                Some(Membership {
+                    affiliation_id: affiliation.affiliation_id.unwrap_or_default(),
+                    person_id: affiliation.person_id,
+                    organization_id: affiliation.organization_id,
+                    organization_name: organization.organization_name.clone(),
+                    role: affiliation.role,
+                })
+            })
+            .collect::<Vec<Membership>>();
+        memberships.sort_by(|a, b| (&a.organization_name, a.affiliation_id).cmp(&(&b.organization_name, b.affiliation_id)));
+        memberships
+    }
+
+    fn create_participation(&mut self, participation_info: Participation) -> Result<Participation, MyError> {
+        let participation_id = self.participations.next_id();
+        unique(self.participations.rows.values().any(|participation| {
+            participation.event_id == participation_info.event_id && participation.person_id == participation_info.person_id
+        }))?;
+        reference(self.events.contains(participation_info.event_id) && self.persons.contains(participation_info.person_id))?;
+
+        let responded_at = match participation_info.rsvp_status {
+            RsvpStatus::Invited => None,
+            _ => Some(Utc::now()),
+        };
+        let participation = Participation { participation_id: Some(participation_id), responded_at, version: 1, ..participation_info };
+        self.participations.rows.insert(participation_id, participation.clone());
+        Ok(participation)
+    }
+
+    fn modify_participation(&mut self, participation_id: i32, person_id: i32, rsvp_status: RsvpStatus) -> Result<Participation, MyError> {
+        let participation = self.participations.rows.get_mut(&participation_id)
+            .filter(|participation| participation.person_id == person_id)
+            .ok_or(MyError::NotFound)?;
+
+        participation.responded_at = match rsvp_status {
+            RsvpStatus::Invited => None,
+            _ if participation.rsvp_status == rsvp_status => participation.responded_at,
+            _ => Some(Utc::now()),
+        };
+        participation.rsvp_status = rsvp_status;
+        participation.version += 1;
+        Ok(participation.clone())
+    }
+
+    fn get_event_participants(&self, event_id: i32) -> Vec<Participant> {
+        self.participations.rows.values()
+            .filter(|participation| participation.event_id == event_id)
+            .filter_map(|participation| {
+                let person = self.persons.rows.get(&participation.person_id)?;
+                Some(Participant {
+                    participation_id: participation.participation_id.unwrap_or_default(),
+                    event_id: participation.event_id,
+                    person_id: participation.person_id,
+                    person_name: person.person_name.clone(),
+                    rsvp_status: participation.rsvp_status,
+                    responded_at: participation.responded_at,
+                })
+            })
+            .collect()
+    }
+
+    fn create_organization(&mut self, organization_info: Organization) -> Result<Organization, MyError> {
+        let organization_id = self.organizations.next_id();
+        reference(organization_info.planner_id.is_none_or(|planner_id| self.planners.contains(planner_id)))?;
+
+        let organization = Organization { organization_id: Some(organization_id), version: 1, ..organization_info };
+        self.organizations.rows.insert(organization_id, organization.clone());
+        Ok(organization)
+    }
+
+    fn modify_organization(&mut self, organization_id: i32, organization_patch: &UpdateOrganization) -> Result<Organization, MyError> {
+        let organization = self.organizations.rows.get_mut(&organization_id).ok_or(MyError::NotFound)?;
+        if !organization_patch.organization_name.is_supplied() {
+            return Ok(organization.clone());
+        }
+
+        organization_patch.apply(organization);
+        organization.version += 1;
+        Ok(organization.clone())
+    }
+
+    fn delete_organization(&mut self, organization_id: i32) -> u64 {
+        if self.organizations.rows.remove(&organization_id).is_none() {
+            return 0;
+        }
+        self.affiliations.remove_where(|affiliation| affiliation.organization_id == organization_id);
+        self.invitations.remove_where(|invitation| invitation.organization_id == Some(organization_id));
+        1
+    }
+
+    fn create_invitation(&mut self, invitation_info: Invitation) -> Result<Invitation, MyError> {
+        let invitation_id = self.invitations.next_id();
+        let grants_one = match (invitation_info.event_id, invitation_info.organization_id) {
+            (Some(_), None) => invitation_info.rsvp_status.is_some() && invitation_info.role.is_none(),
+            (None, Some(_)) => invitation_info.role.is_some() && invitation_info.rsvp_status.is_none(),
+            _ => false,
+        };
+        if !grants_one || invitation_info.max_uses < 1 {
+            return Err(MyError::Violation(SqlState::CHECK_VIOLATION));
+        }
+        unique(self.invitations.rows.values().any(|invitation| invitation.token == invitation_info.token))?;
+        reference(
+            invitation_info.event_id.is_none_or(|event_id| self.events.contains(event_id))
+                && invitation_info.organization_id.is_none_or(|organization_id| self.organizations.contains(organization_id))
+                && self.persons.contains(invitation_info.created_by)
+        )?;
+
+        let invitation = Invitation { invitation_id: Some(invitation_id), uses: 0, revoked_at: None, ..invitation_info };
+        self.invitations.rows.insert(invitation_id, invitation.clone());
+        Ok(invitation)
+    }
+
+    fn outstanding_invitations(&self, include: impl Fn(&Invitation) -> bool) -> Vec<Invitation> {
+        let now = Utc::now();
+        self.invitations.page(0, i64::MAX, |invitation| include(invitation) && invitation.is_outstanding(now))
+    }
+
+    fn revoke_invitation(&mut self, invitation_id: i32) -> u64 {
+        match self.invitations.rows.get_mut(&invitation_id) {
+            Some(invitation) if invitation.revoked_at.is_none() => {
+                invitation.revoked_at = Some(Utc::now());
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    fn use_invitation(&mut self, token: &str) -> Result<Invitation, MyError> {
+        let now = Utc::now();
+        let invitation = self.invitations.rows.values_mut()
+            .find(|invitation| invitation.token == token && invitation.is_outstanding(now))
+            .ok_or(MyError::NotFound)?;
+        invitation.uses += 1;
+        Ok(invitation.clone())
+    }
+
+    fn get_invitation_by_token(&self, token: &str) -> Result<Invitation, MyError> {
+        let now = Utc::now();
+        self.invitations.rows.values()
+            .find(|invitation| invitation.token == token && invitation.is_outstanding(now))
+            .cloned()
+            .ok_or(MyError::NotFound)
+    }
+
+    /// Whether another guest of the event has the email, ignoring case.
+    fn guest_email_taken(&self, event_id: i32, email: &str, token: &str) -> bool {
+        self.guests.rows.values().any(|guest| {
+            guest.event_id == event_id && guest.token != token && guest.email.to_lowercase() == email.to_lowercase()
+        })
+    }
+
+    fn create_guest(&mut self, guest_info: Guest) -> Result<Guest, MyError> {
+        let guest_id = self.guests.next_id();
+        if guest_info.plus_ones < 0 {
+            return Err(MyError::Violation(SqlState::CHECK_VIOLATION));
+        }
+        unique(self.guests.rows.values().any(|guest| guest.token == guest_info.token))?;
+        unique(self.guest_email_taken(guest_info.event_id, &guest_info.email, &guest_info.token))?;
+        reference(
+            self.events.contains(guest_info.event_id)
+                && guest_info.invitation_id.is_none_or(|invitation_id| self.invitations.contains(invitation_id))
+        )?;
+
+        let guest = Guest { guest_id: Some(guest_id), responded_at: Utc::now(), person_id: None, ..guest_info };
+        self.guests.rows.insert(guest_id, guest.clone());
+        Ok(guest)
+    }
+
+    fn unmerged_guest(&mut self, token: &str) -> Option<&mut Guest> {
+        self.guests.rows.values_mut().find(|guest| guest.token == token && guest.person_id.is_none())
+    }
+
+    fn modify_guest(&mut self, guest_info: Guest) -> Result<Guest, MyError> {
+        let event_id = self.unmerged_guest(&guest_info.token).ok_or(MyError::NotFound)?.event_id;
+        if guest_info.plus_ones < 0 {
+            return Err(MyError::Violation(SqlState::CHECK_VIOLATION));
+        }
+        unique(self.guest_email_taken(event_id, &guest_info.email, &guest_info.token))?;
+
+        let guest = self.unmerged_guest(&guest_info.token).ok_or(MyError::NotFound)?;
+        if guest.rsvp_status != guest_info.rsvp_status {
+            guest.responded_at = Utc::now();
+        }
+        guest.guest_name = guest_info.guest_name;
+        guest.email = guest_info.email;
+        guest.rsvp_status = guest_info.rsvp_status;
+        guest.plus_ones = guest_info.plus_ones;
+        Ok(guest.clone())
+    }
+
+    fn merge_guest(&mut self, token: &str, person_id: i32) -> Result<Participation, MyError> {
+        let guest = self.unmerged_guest(token).ok_or(MyError::NotFound)?.clone();
+        let participation_id = self.participations.next_id();
+        unique(self.participations.rows.values().any(|participation| {
+            participation.event_id == guest.event_id && participation.person_id == person_id
+        }))?;
+        reference(self.persons.contains(person_id))?;
+
+        let participation = Participation {
+            participation_id: Some(participation_id),
+            event_id: guest.event_id,
+            person_id,
+            rsvp_status: guest.rsvp_status,
+            responded_at: Some(guest.responded_at),
+            version: 1,
+        };
+        self.participations.rows.insert(participation_id, participation.clone());
+        if let Some(guest) = self.unmerged_guest(token) {
+            guest.person_id = Some(person_id);
+        }
+        Ok(participation)
+    }
+
+    fn is_event_member(&self, person_id: i32, event_id: i32) -> bool {
+        let planners = self.member_planners(person_id, EDITOR_ROLES);
+        planners.iter().any(|planner_id| self.has_plan(event_id, *planner_id))
+    }
+
+    fn get_organization_role(&self, person_id: i32, organization_id: i32) -> Option<OrganizationRole> {
+        self.affiliations.rows.values()
+            .find(|affiliation| affiliation.person_id == person_id && affiliation.organization_id == organization_id)
+            .map(|affiliation| affiliation.role)
+    }
+
+    fn search(&self, person_id: i32, text: &str, limit: i64) -> SearchResults {
+        let query = search::words(text).map(str::to_lowercase).collect::<Vec<String>>();
+        let matches = |stemmed: bool| {
+            let query = &query;
+            move |word: &str| query.iter().any(|query_word| word_matches(query_word, word, stemmed))
+        };
+        let limit = limit.max(0) as usize;
+
+        let planners = self.member_planners(person_id, ANY_ROLE);
+        let mut events = self.events.rows.values()
+            .map(|row| &row.event)
+            .filter(|event| planners.iter().any(|planner_id| self.has_plan(event.event_id.unwrap_or_default(), *planner_id)))
+            .filter_map(|event| {
+                let rank = rank(&query, &[
+                    Indexed { text: &event.event_name, weight: WEIGHT_A, stemmed: true },
+                    Indexed { text: &event.event_location, weight: WEIGHT_B, stemmed: true },
+                    Indexed { text: &event.event_description, weight: WEIGHT_C, stemmed: true },
+                ])?;
+                let text = [event.event_name.as_str(), &event.event_location, &event.event_description].join(" · ");
+                Some(EventHit {
+                    event_id: event.event_id.unwrap_or_default(),
+                    event_name: event.event_name.clone(),
+                    starts_at: event.starts_at,
+                    snippet: search::headline(&text, matches(true)),
+                    rank,
+                })
+            })
+            .collect::<Vec<EventHit>>();
+        events.sort_by(|a, b| by_rank((a.rank, a.event_id), (b.rank, b.event_id)));
+        events.truncate(limit);
+
+        let mut persons = self.persons.rows.values()
+            .filter_map(|person| {
+                let rank = rank(&query, &[Indexed { text: &person.person_name, weight: WEIGHT_A, stemmed: false }])?;
+                Some(PersonHit {
+                    person_id: person.person_id.unwrap_or_default(),
+                    person_name: person.person_name.clone(),
+                    snippet: search::headline(&person.person_name, matches(false)),
+                    rank,
+                })
+            })
+            .collect::<Vec<PersonHit>>();
+        persons.sort_by(|a, b| by_rank((a.rank, a.person_id), (b.rank, b.person_id)));
+        persons.truncate(limit);
+
+        let mut organizations = self.organizations.rows.values()
+            .filter_map(|organization| {
+                let rank = rank(&query, &[Indexed { text: &organization.organization_name, weight: WEIGHT_A, stemmed: true }])?;
+                Some(OrganizationHit {
+                    organization_id: organization.organization_id.unwrap_or_default(),
+                    organization_name: organization.organization_name.clone(),
+                    snippet: search::headline(&organization.organization_name, matches(true)),
+                    rank,
+                })
+            })
+            .collect::<Vec<OrganizationHit>>();
+        organizations.sort_by(|a, b| by_rank((a.rank, a.organization_id), (b.rank, b.organization_id)));
+        organizations.truncate(limit);
+
+        SearchResults { events, persons, organizations }
+    }
+}
+
+/// Runs `statement` on a copy of `tables` that replaces them only if it succeeds.
+fn execute<R>(tables: &mut Tables, statement: impl FnOnce(&mut Tables) -> Result<R, MyError>) -> Result<R, MyError> {
+    let mut changed = tables.clone();
+    let result = statement(&mut changed)?;
+    *tables = changed;
+    Ok(result)
+}
+
+/// Where the statements of a store run: on the shared tables, or in a transaction.
+trait Scope: Send + Sync {
+    fn read<R: Send>(&self, query: impl FnOnce(&Tables) -> Result<R, MyError> + Send) -> impl Future<Output = Result<R, MyError>> + Send;
+
+    fn write<R: Send>(&self, statement: impl FnOnce(&mut Tables) -> Result<R, MyError> + Send) -> impl Future<Output = Result<R, MyError>> + Send;
+}
+
+struct Shared(Arc<Mutex<Tables>>);
+
+impl Scope for Shared {
+    async fn read<R: Send>(&self, query: impl FnOnce(&Tables) -> Result<R, MyError> + Send) -> Result<R, MyError> {
+        query(&*self.0.lock().await)
+    }
+
+    async fn write<R: Send>(&self, statement: impl FnOnce(&mut Tables) -> Result<R, MyError> + Send) -> Result<R, MyError> {
+        execute(&mut *self.0.lock().await, statement)
+    }
+}
+
+/// A transaction: the lock on the shared tables, and the working copy that `commit` stores.
+struct Open {
+    lock: OwnedMutexGuard<Tables>,
+    working: StdMutex<Working>,
+}
+
+struct Working {
+    tables: Tables,
+    // Like Postgres, a transaction in which a statement broke a constraint runs nothing more and
+    // is rolled back even by `commit`.
+    failed: bool,
+}
+
+impl Working {
+    fn run<R>(&mut self, statement: impl FnOnce(&mut Tables) -> Result<R, MyError>) -> Result<R, MyError> {
+        if self.failed {
+            return Err(MyError::Violation(SqlState::IN_FAILED_SQL_TRANSACTION));
+        }
+        let result = execute(&mut self.tables, statement);
+        if let Err(MyError::Violation(_) | MyError::Conflict(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+
+impl Scope for Open {
+    async fn read<R: Send>(&self, query: impl FnOnce(&Tables) -> Result<R, MyError> + Send) -> Result<R, MyError> {
+        let working = self.working.lock().unwrap_or_else(PoisonError::into_inner);
+        if working.failed {
+            return Err(MyError::Violation(SqlState::IN_FAILED_SQL_TRANSACTION));
+        }
+        query(&working.tables)
+    }
+
+    async fn write<R: Send>(&self, statement: impl FnOnce(&mut Tables) -> Result<R, MyError> + Send) -> Result<R, MyError> {
+        let mut working = self.working.lock().unwrap_or_else(PoisonError::into_inner);
+        working.run(statement)
+    }
+}
+
+struct MemoryStore<S> {
+    scope: S,
+}
+
+#[async_trait]
+impl Connection for MemoryStore<Shared> {
+    async fn transaction(&mut self) -> Result<Box<dyn Transaction + '_>, MyError> {
+        let lock = self.scope.0.clone().lock_owned().await;
+        let tables = lock.clone();
+        Ok(Box::new(MemoryStore { scope: Open { lock, working: StdMutex::new(Working { tables, failed: false }) } }))
+    }
+}
+
+#[async_trait]
+impl Transaction for MemoryStore<Open> {
+    async fn commit(self: Box<Self>) -> Result<(), MyError> {
+        let Open { mut lock, working } = self.scope;
+        let working = working.into_inner().unwrap_or_else(PoisonError::into_inner);
+        if !working.failed {
+            *lock = working.tables;
+        }
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), MyError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: Scope> Store for MemoryStore<S> {
+    async fn lock_version(&self, table: Versioned, id: i32) -> Result<i32, MyError> {
+        self.scope.read(|tables| tables.lock_version(table, id)).await
+    }
+
+    async fn create_event(&self, event_info: Event) -> Result<Event, MyError> {
+        self.scope.write(|tables| tables.insert_event(event_info, None, None)).await
+    }
+
+    async fn create_imported_event(&self, event_info: Event, planner_id: i32, ical_uid: &str) -> Result<Event, MyError> {
+        self.scope.write(|tables| tables.insert_event(event_info, Some(planner_id), Some(ical_uid))).await
+    }
+
+    async fn modify_event(&self, event_id: i32, event_patch: &UpdateEvent) -> Result<Event, MyError> {
+        self.scope.write(|tables| tables.modify_event(event_id, event_patch)).await
+    }
+
+    async fn list_events(&self, person_id: i32, filter: &Filter<Event>, pagination: &Pagination) -> Result<Vec<Event>, MyError> {
+        self.scope.read(|tables| {
+            let events = tables.planned_events(tables.own_planner(Some(person_id)));
+            let anchor = tables.events.rows.get(&pagination.after).map(|row| &row.event);
+            Ok(filter.apply(events, anchor, pagination))
+        }).await
+    }
+
+    async fn get_occurrences_between(&self, person_id: Option<i32>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EventOccurrence>, MyError> {
+        self.scope.read(|tables| tables.get_occurrences_between(person_id, from, to)).await
+    }
+
+    async fn get_planner_events(&self, planner_id: i32) -> Result<Vec<Event>, MyError> {
+        self.scope.read(|tables| Ok(tables.planned_events(Some(planner_id)))).await
+    }
+
+    async fn get_event(&self, event_id: i32) -> Result<Event, MyError> {
+        self.scope.read(|tables| tables.events.get(event_id).map(|row| row.event)).await
+    }
+
+    async fn get_event_by_ical_uid(&self, planner_id: i32, ical_uid: &str) -> Result<Option<Event>, MyError> {
+        self.scope.read(|tables| {
+            Ok(tables.events.rows.values()
+                .find(|row| row.ical_planner_id == Some(planner_id) && row.ical_uid.as_deref() == Some(ical_uid))
+                .map(|row| row.event.clone()))
+        }).await
+    }
+
+    async fn delete_event(&self, event_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.delete_event(event_id))).await
+    }
+
+    async fn get_event_overrides(&self, event_ids: &[i32]) -> Result<Vec<EventOverride>, MyError> {
+        self.scope.read(|tables| Ok(tables.get_event_overrides(event_ids))).await
+    }
+
+    async fn list_event_overrides(&self, event_id: i32, after: i32, limit: i64) -> Result<Vec<EventOverride>, MyError> {
+        self.scope.read(|tables| Ok(tables.overrides.page(after, limit, |event_override| event_override.event_id == event_id))).await
+    }
+
+    async fn get_event_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<EventOverride, MyError> {
+        self.scope.read(|tables| tables.find_override(event_id, occurrence_starts_at).cloned().ok_or(MyError::NotFound)).await
+    }
+
+    async fn create_event_override(&self, override_info: EventOverride) -> Result<EventOverride, MyError> {
+        self.scope.write(|tables| tables.create_event_override(override_info)).await
+    }
+
+    async fn delete_event_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.delete_event_override(event_id, occurrence_starts_at))).await
+    }
+
+    async fn create_plan(&self, plan_info: Plan) -> Result<Plan, MyError> {
+        self.scope.write(|tables| tables.create_plan(plan_info)).await
+    }
+
+    async fn ensure_plan(&self, plan_info: Plan) -> Result<Option<Plan>, MyError> {
+        self.scope.write(|tables| tables.ensure_plan(plan_info)).await
+    }
+
+    async fn get_plan(&self, plan_id: i32) -> Result<Plan, MyError> {
+        self.scope.read(|tables| tables.plans.get(plan_id)).await
+    }
+
+    async fn list_plans(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Plan>, MyError> {
+        self.scope.read(|tables| {
+            let planners = tables.member_planners(person_id, ANY_ROLE);
+            Ok(tables.plans.page(after, limit, |plan| planners.contains(&plan.planner_id)))
+        }).await
+    }
+
+    async fn delete_plan(&self, plan_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.plans.rows.remove(&plan_id).map_or(0, |_| 1))).await
+    }
+
+    async fn create_planner(&self) -> Result<Planner, MyError> {
+        self.scope.write(|tables| Ok(tables.create_planner())).await
+    }
+
+    async fn get_planner(&self, planner_id: i32) -> Result<Planner, MyError> {
+        self.scope.read(|tables| tables.planners.get(planner_id)).await
+    }
+
+    async fn list_planners(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Planner>, MyError> {
+        self.scope.read(|tables| {
+            let planners = tables.member_planners(person_id, ANY_ROLE);
+            Ok(tables.planners.page(after, limit, |planner| planners.contains(&planner.planner_id)))
+        }).await
+    }
+
+    async fn delete_planner(&self, planner_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| tables.delete_planner(planner_id)).await
+    }
+
+    async fn delete_unused_planner(&self, planner_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.delete_unused_planner(planner_id))).await
+    }
+
+    async fn create_person(&self, person_info: Person) -> Result<Person, MyError> {
+        self.scope.write(|tables| tables.create_person(person_info)).await
+    }
+
+    async fn get_person(&self, person_id: i32) -> Result<Person, MyError> {
+        self.scope.read(|tables| tables.persons.get(person_id)).await
+    }
+
+    async fn list_persons(&self, filter: &Filter<Person>, pagination: &Pagination) -> Result<Vec<Person>, MyError> {
+        self.scope.read(|tables| {
+            let persons = tables.persons.rows.values().cloned().collect();
+            Ok(filter.apply(persons, tables.persons.rows.get(&pagination.after), pagination))
+        }).await
+    }
+
+    async fn modify_person(&self, person_id: i32, person_patch: &UpdatePerson) -> Result<Person, MyError> {
+        self.scope.write(|tables| tables.modify_person(person_id, person_patch)).await
+    }
+
+    async fn delete_person(&self, person_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| tables.delete_person(person_id)).await
+    }
+
+    async fn create_account(&self, person_id: i32, email: &str, password_hash: &str) -> Result<Account, MyError> {
+        self.scope.write(|tables| tables.create_account(person_id, email, password_hash)).await
+    }
+
+    async fn get_account_by_email(&self, email: &str) -> Result<Option<Account>, MyError> {
+        self.scope.read(|tables| Ok(tables.get_account_by_email(email))).await
+    }
+
+    async fn create_session(&self, session_id: &str, person_id: i32, expires_at: DateTime<Utc>) -> Result<u64, MyError> {
+        self.scope.write(|tables| tables.create_session(session_id, person_id, expires_at)).await
+    }
+
+    async fn is_session_active(&self, session_id: &str, person_id: i32) -> Result<bool, MyError> {
+        self.scope.read(|tables| Ok(tables.is_session_active(session_id, person_id))).await
+    }
+
+    async fn revoke_session(&self, session_id: &str) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.revoke_session(session_id))).await
+    }
+
+    async fn create_affiliation(&self, affiliation_info: Affiliation) -> Result<Affiliation, MyError> {
+        self.scope.write(|tables| tables.create_affiliation(affiliation_info)).await
+    }
+
+    async fn get_affiliation(&self, affiliation_id: i32) -> Result<Affiliation, MyError> {
+        self.scope.read(|tables| tables.affiliations.get(affiliation_id)).await
+    }
+
+    async fn list_affiliations(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Affiliation>, MyError> {
+        self.scope.read(|tables| {
+            let organizations = tables.affiliations.rows.values()
+                .filter(|affiliation| affiliation.person_id == person_id)
+                .map(|affiliation| affiliation.organization_id)
+                .collect::<Vec<i32>>();
+            Ok(tables.affiliations.page(after, limit, |affiliation| organizations.contains(&affiliation.organization_id)))
+        }).await
+    }
+
+    async fn modify_affiliation(&self, affiliation_id: i32, role: OrganizationRole) -> Result<Affiliation, MyError> {
+        self.scope.write(|tables| tables.modify_affiliation(affiliation_id, role)).await
+    }
+
+    async fn delete_affiliation(&self, affiliation_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| tables.delete_affiliation(affiliation_id)).await
+    }
+
+    async fn get_organization_members(&self, organization_id: i32) -> Result<Vec<Member>, MyError> {
+        self.scope.read(|tables| Ok(tables.get_organization_members(organization_id))).await
+    }
+
+    async fn get_person_organizations(&self, person_id: i32) -> Result<Vec<Membership>, MyError> {
+        self.scope.read(|tables| Ok(tables.get_person_organizations(person_id))).await
+    }
+
+    async fn create_participation(&self, participation_info: Participation) -> Result<Participation, MyError> {
+        self.scope.write(|tables| tables.create_participation(participation_info)).await
+    }
+
+    async fn modify_participation(&self, participation_id: i32, person_id: i32, rsvp_status: RsvpStatus) -> Result<Participation, MyError> {
+        self.scope.write(|tables| tables.modify_participation(participation_id, person_id, rsvp_status)).await
+    }
+
+    async fn get_event_participants(&self, event_id: i32) -> Result<Vec<Participant>, MyError> {
+        self.scope.read(|tables| Ok(tables.get_event_participants(event_id))).await
+    }
+
+    async fn get_person_participations(&self, person_id: i32) -> Result<Vec<Participation>, MyError> {
+        self.scope.read(|tables| Ok(tables.participations.page(0, i64::MAX, |participation| participation.person_id == person_id))).await
+    }
+
+    async fn get_participation(&self, participation_id: i32) -> Result<Participation, MyError> {
+        self.scope.read(|tables| tables.participations.get(participation_id)).await
+    }
+
+    async fn list_participations(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Participation>, MyError> {
+        self.scope.read(|tables| {
+            let planners = tables.member_planners(person_id, ANY_ROLE);
+            Ok(tables.participations.page(after, limit, |participation| {
+                participation.person_id == person_id
+                    || planners.iter().any(|planner_id| tables.has_plan(participation.event_id, *planner_id))
+            }))
+        }).await
+    }
+
+    async fn delete_participation(&self, participation_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.participations.rows.remove(&participation_id).map_or(0, |_| 1))).await
+    }
+
+    async fn create_organization(&self, organization_info: Organization) -> Result<Organization, MyError> {
+        self.scope.write(|tables| tables.create_organization(organization_info)).await
+    }
+
+    async fn get_organization(&self, organization_id: i32) -> Result<Organization, MyError> {
+        self.scope.read(|tables| tables.organizations.get(organization_id)).await
+    }
+
+    async fn list_organizations(&self, filter: &Filter<Organization>, pagination: &Pagination) -> Result<Vec<Organization>, MyError> {
+        self.scope.read(|tables| {
+            let organizations = tables.organizations.rows.values().cloned().collect();
+            Ok(filter.apply(organizations, tables.organizations.rows.get(&pagination.after), pagination))
+        }).await
+    }
+
+    async fn modify_organization(&self, organization_id: i32, organization_patch: &UpdateOrganization) -> Result<Organization, MyError> {
+        self.scope.write(|tables| tables.modify_organization(organization_id, organization_patch)).await
+    }
+
+    async fn delete_organization(&self, organization_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.delete_organization(organization_id))).await
+    }
+
+    async fn create_invitation(&self, invitation_info: Invitation) -> Result<Invitation, MyError> {
+        self.scope.write(|tables| tables.create_invitation(invitation_info)).await
+    }
+
+    async fn get_invitation(&self, invitation_id: i32) -> Result<Invitation, MyError> {
+        self.scope.read(|tables| tables.invitations.get(invitation_id)).await
+    }
+
+    async fn get_event_invitations(&self, event_id: i32) -> Result<Vec<Invitation>, MyError> {
+        self.scope.read(|tables| Ok(tables.outstanding_invitations(|invitation| invitation.event_id == Some(event_id)))).await
+    }
+
+    async fn get_organization_invitations(&self, organization_id: i32) -> Result<Vec<Invitation>, MyError> {
+        self.scope.read(|tables| Ok(tables.outstanding_invitations(|invitation| invitation.organization_id == Some(organization_id)))).await
+    }
+
+    async fn revoke_invitation(&self, invitation_id: i32) -> Result<u64, MyError> {
+        self.scope.write(|tables| Ok(tables.revoke_invitation(invitation_id))).await
+    }
+
+    async fn use_invitation(&self, token: &str) -> Result<Invitation, MyError> {
+        self.scope.write(|tables| tables.use_invitation(token)).await
+    }
+
+    async fn get_invitation_by_token(&self, token: &str) -> Result<Invitation, MyError> {
+        self.scope.read(|tables| tables.get_invitation_by_token(token)).await
+    }
+
+    async fn create_guest(&self, guest_info: Guest) -> Result<Guest, MyError> {
+        self.scope.write(|tables| tables.create_guest(guest_info)).await
+    }
+
+    async fn get_guest(&self, token: &str) -> Result<Guest, MyError> {
+        self.scope.read(|tables| {
+            tables.guests.rows.values()
+                .find(|guest| guest.token == token && guest.person_id.is_none())
+                .cloned()
+                .ok_or(MyError::NotFound)
+        }).await
+    }
+
+    async fn modify_guest(&self, guest_info: Guest) -> Result<Guest, MyError> {
+        self.scope.write(|tables| tables.modify_guest(guest_info)).await
+    }
+
+    async fn get_event_guests(&self, event_id: i32) -> Result<Vec<Guest>, MyError> {
+        self.scope.read(|tables| Ok(tables.guests.page(0, i64::MAX, |guest| guest.event_id == event_id))).await
+    }
+
+    async fn merge_guest(&self, token: &str, person_id: i32) -> Result<Participation, MyError> {
+        self.scope.write(|tables| tables.merge_guest(token, person_id)).await
+    }
+
+    async fn is_planner_member(&self, person_id: i32, planner_id: i32) -> Result<bool, MyError> {
+        self.scope.read(|tables| Ok(tables.member_planners(person_id, MANAGER_ROLES).contains(&planner_id))).await
+    }
+
+    async fn is_event_member(&self, person_id: i32, event_id: i32) -> Result<bool, MyError> {
+        self.scope.read(|tables| Ok(tables.is_event_member(person_id, event_id))).await
+    }
+
+    async fn get_organization_role(&self, person_id: i32, organization_id: i32) -> Result<Option<OrganizationRole>, MyError> {
+        self.scope.read(|tables| Ok(tables.get_organization_role(person_id, organization_id))).await
+    }
+
+    async fn search(&self, person_id: i32, text: &str, limit: i64) -> Result<SearchResults, MyError> {
+        self.scope.read(|tables| Ok(tables.search(person_id, text, limit))).await
+    }
+}
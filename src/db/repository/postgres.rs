@@ -0,0 +1,365 @@
+//! The repository served by `main`: a pool of Postgres connections running `db::query`.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Client, GenericClient, Pool, Transaction as PgTransaction};
+
+use crate::db::{
+    dto::{UpdateEvent, UpdateOrganization, UpdatePerson},
+    errors::MyError,
+    filter::Filter,
+    pagination::Pagination,
+    query,
+    repository::{Connection, Repository, Store, Transaction, Versioned},
+    models::{
+        Account,
+        Affiliation,
+        Event,
+        EventOccurrence,
+        EventOverride,
+        Guest,
+        Invitation,
+        Member,
+        Membership,
+        Organization,
+        OrganizationRole,
+        Participant,
+        Participation,
+        Person,
+        Plan,
+        Planner,
+        RsvpStatus,
+        SearchResults
+    }
+};
+
+pub struct PgRepository {
+    pool: Pool,
+}
+
+impl PgRepository {
+    pub fn new(pool: Pool) -> Self {
+        PgRepository { pool }
+    }
+}
+
+/// A pooled connection, or a transaction open on one.
+pub struct PgStore<C> {
+    client: C,
+}
+
+#[async_trait]
+impl Repository for PgRepository {
+    async fn connect(&self) -> Result<Box<dyn Connection>, MyError> {
+        let client = self.pool.get().await.map_err(MyError::PoolError)?;
+        Ok(Box::new(PgStore { client }))
+    }
+}
+
+#[async_trait]
+impl Connection for PgStore<Client> {
+    async fn transaction(&mut self) -> Result<Box<dyn Transaction + '_>, MyError> {
+        let client = self.client.transaction().await.map_err(MyError::PGError)?;
+        Ok(Box::new(PgStore { client }))
+    }
+}
+
+#[async_trait]
+impl Transaction for PgStore<PgTransaction<'_>> {
+    async fn commit(self: Box<Self>) -> Result<(), MyError> {
+        self.client.commit().await.map_err(MyError::PGError)
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), MyError> {
+        self.client.rollback().await.map_err(MyError::PGError)
+    }
+}
+
+#[async_trait]
+impl<C: GenericClient + Send + Sync> Store for PgStore<C> {
+    async fn lock_version(&self, table: Versioned, id: i32) -> Result<i32, MyError> {
+        match table {
+            Versioned::Event => query::lock_version::<Event>(&self.client, "event_id", id).await,
+            Versioned::Person => query::lock_version::<Person>(&self.client, "person_id", id).await,
+            Versioned::Affiliation => query::lock_version::<Affiliation>(&self.client, "affiliation_id", id).await,
+            Versioned::Participation => query::lock_version::<Participation>(&self.client, "participation_id", id).await,
+            Versioned::Organization => query::lock_version::<Organization>(&self.client, "organization_id", id).await,
+        }
+    }
+
+    async fn create_event(&self, event_info: Event) -> Result<Event, MyError> {
+        query::create_event(&self.client, event_info).await
+    }
+
+    async fn create_imported_event(&self, event_info: Event, planner_id: i32, ical_uid: &str) -> Result<Event, MyError> {
+        query::create_imported_event(&self.client, event_info, planner_id, ical_uid).await
+    }
+
+    async fn modify_event(&self, event_id: i32, event_patch: &UpdateEvent) -> Result<Event, MyError> {
+        query::modify_event(&self.client, event_id, event_patch).await
+    }
+
+    async fn list_events(&self, person_id: i32, filter: &Filter<Event>, pagination: &Pagination) -> Result<Vec<Event>, MyError> {
+        query::list_events(&self.client, person_id, filter, pagination).await
+    }
+
+    async fn get_occurrences_between(&self, person_id: Option<i32>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EventOccurrence>, MyError> {
+        query::get_occurrences_between(&self.client, person_id, from, to).await
+    }
+
+    async fn get_planner_events(&self, planner_id: i32) -> Result<Vec<Event>, MyError> {
+        query::get_planner_events(&self.client, planner_id).await
+    }
+
+    async fn get_event(&self, event_id: i32) -> Result<Event, MyError> {
+        query::get_event(&self.client, event_id).await
+    }
+
+    async fn get_event_by_ical_uid(&self, planner_id: i32, ical_uid: &str) -> Result<Option<Event>, MyError> {
+        query::get_event_by_ical_uid(&self.client, planner_id, ical_uid).await
+    }
+
+    async fn delete_event(&self, event_id: i32) -> Result<u64, MyError> {
+        query::delete_event(&self.client, event_id).await
+    }
+
+    async fn get_event_overrides(&self, event_ids: &[i32]) -> Result<Vec<EventOverride>, MyError> {
+        query::get_event_overrides(&self.client, event_ids).await
+    }
+
+    async fn list_event_overrides(&self, event_id: i32, after: i32, limit: i64) -> Result<Vec<EventOverride>, MyError> {
+        query::list_event_overrides(&self.client, event_id, after, limit).await
+    }
+
+    async fn get_event_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<EventOverride, MyError> {
+        query::get_event_override(&self.client, event_id, occurrence_starts_at).await
+    }
+
+    async fn create_event_override(&self, override_info: EventOverride) -> Result<EventOverride, MyError> {
+        query::create_event_override(&self.client, override_info).await
+    }
+
+    async fn delete_event_override(&self, event_id: i32, occurrence_starts_at: DateTime<Utc>) -> Result<u64, MyError> {
+        query::delete_event_override(&self.client, event_id, occurrence_starts_at).await
+    }
+
+    async fn create_plan(&self, plan_info: Plan) -> Result<Plan, MyError> {
+        query::create_plan(&self.client, plan_info).await
+    }
+
+    async fn ensure_plan(&self, plan_info: Plan) -> Result<Option<Plan>, MyError> {
+        query::ensure_plan(&self.client, plan_info).await
+    }
+
+    async fn get_plan(&self, plan_id: i32) -> Result<Plan, MyError> {
+        query::get_plan(&self.client, plan_id).await
+    }
+
+    async fn list_plans(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Plan>, MyError> {
+        query::list_plans(&self.client, person_id, after, limit).await
+    }
+
+    async fn delete_plan(&self, plan_id: i32) -> Result<u64, MyError> {
+        query::delete_plan(&self.client, plan_id).await
+    }
+
+    async fn create_planner(&self) -> Result<Planner, MyError> {
+        query::create_planner(&self.client).await
+    }
+
+    async fn get_planner(&self, planner_id: i32) -> Result<Planner, MyError> {
+        query::get_planner(&self.client, planner_id).await
+    }
+
+    async fn list_planners(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Planner>, MyError> {
+        query::list_planners(&self.client, person_id, after, limit).await
+    }
+
+    async fn delete_planner(&self, planner_id: i32) -> Result<u64, MyError> {
+        query::delete_planner(&self.client, planner_id).await
+    }
+
+    async fn delete_unused_planner(&self, planner_id: i32) -> Result<u64, MyError> {
+        query::delete_unused_planner(&self.client, planner_id).await
+    }
+
+    async fn create_person(&self, person_info: Person) -> Result<Person, MyError> {
+        query::create_person(&self.client, person_info).await
+    }
+
+    async fn get_person(&self, person_id: i32) -> Result<Person, MyError> {
+        query::get_person(&self.client, person_id).await
+    }
+
+    async fn list_persons(&self, filter: &Filter<Person>, pagination: &Pagination) -> Result<Vec<Person>, MyError> {
+        query::list_persons(&self.client, filter, pagination).await
+    }
+
+    async fn modify_person(&self, person_id: i32, person_patch: &UpdatePerson) -> Result<Person, MyError> {
+        query::modify_person(&self.client, person_id, person_patch).await
+    }
+
+    async fn delete_person(&self, person_id: i32) -> Result<u64, MyError> {
+        query::delete_person(&self.client, person_id).await
+    }
+
+    async fn create_account(&self, person_id: i32, email: &str, password_hash: &str) -> Result<Account, MyError> {
+        query::create_account(&self.client, person_id, email, password_hash).await
+    }
+
+    async fn get_account_by_email(&self, email: &str) -> Result<Option<Account>, MyError> {
+        query::get_account_by_email(&self.client, email).await
+    }
+
+    async fn create_session(&self, session_id: &str, person_id: i32, expires_at: DateTime<Utc>) -> Result<u64, MyError> {
+        query::create_session(&self.client, session_id, person_id, expires_at).await
+    }
+
+    async fn is_session_active(&self, session_id: &str, person_id: i32) -> Result<bool, MyError> {
+        query::is_session_active(&self.client, session_id, person_id).await
+    }
+
+    async fn revoke_session(&self, session_id: &str) -> Result<u64, MyError> {
+        query::revoke_session(&self.client, session_id).await
+    }
+
+    async fn create_affiliation(&self, affiliation_info: Affiliation) -> Result<Affiliation, MyError> {
+        query::create_affiliation(&self.client, affiliation_info).await
+    }
+
+    async fn get_affiliation(&self, affiliation_id: i32) -> Result<Affiliation, MyError> {
+        query::get_affiliation(&self.client, affiliation_id).await
+    }
+
+    async fn list_affiliations(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Affiliation>, MyError> {
+        query::list_affiliations(&self.client, person_id, after, limit).await
+    }
+
+    async fn modify_affiliation(&self, affiliation_id: i32, role: OrganizationRole) -> Result<Affiliation, MyError> {
+        query::modify_affiliation(&self.client, affiliation_id, role).await
+    }
+
+    async fn delete_affiliation(&self, affiliation_id: i32) -> Result<u64, MyError> {
+        query::delete_affiliation(&self.client, affiliation_id).await
+    }
+
+    async fn get_organization_members(&self, organization_id: i32) -> Result<Vec<Member>, MyError> {
+        query::get_organization_members(&self.client, organization_id).await
+    }
+
+    async fn get_person_organizations(&self, person_id: i32) -> Result<Vec<Membership>, MyError> {
+        query::get_person_organizations(&self.client, person_id).await
+    }
+
+    async fn create_participation(&self, participation_info: Participation) -> Result<Participation, MyError> {
+        query::create_participation(&self.client, participation_info).await
+    }
+
+    async fn modify_participation(&self, participation_id: i32, person_id: i32, rsvp_status: RsvpStatus) -> Result<Participation, MyError> {
+        query::modify_participation(&self.client, participation_id, person_id, rsvp_status).await
+    }
+
+    async fn get_event_participants(&self, event_id: i32) -> Result<Vec<Participant>, MyError> {
+        query::get_event_participants(&self.client, event_id).await
+    }
+
+    async fn get_person_participations(&self, person_id: i32) -> Result<Vec<Participation>, MyError> {
+        query::get_person_participations(&self.client, person_id).await
+    }
+
+    async fn get_participation(&self, participation_id: i32) -> Result<Participation, MyError> {
+        query::get_participation(&self.client, participation_id).await
+    }
+
+    async fn list_participations(&self, person_id: i32, after: i32, limit: i64) -> Result<Vec<Participation>, MyError> {
+        query::list_participations(&self.client, person_id, after, limit).await
+    }
+
+    async fn delete_participation(&self, participation_id: i32) -> Result<u64, MyError> {
+        query::delete_participation(&self.client, participation_id).await
+    }
+
+    async fn create_organization(&self, organization_info: Organization) -> Result<Organization, MyError> {
+        query::create_organization(&self.client, organization_info).await
+    }
+
+    async fn get_organization(&self, organization_id: i32) -> Result<Organization, MyError> {
+        query::get_organization(&self.client, organization_id).await
+    }
+
+    async fn list_organizations(&self, filter: &Filter<Organization>, pagination: &Pagination) -> Result<Vec<Organization>, MyError> {
+        query::list_organizations(&self.client, filter, pagination).await
+    }
+
+    async fn modify_organization(&self, organization_id: i32, organization_patch: &UpdateOrganization) -> Result<Organization, MyError> {
+        query::modify_organization(&self.client, organization_id, organization_patch).await
+    }
+
+    async fn delete_organization(&self, organization_id: i32) -> Result<u64, MyError> {
+        query::delete_organization(&self.client, organization_id).await
+    }
+
+    async fn create_invitation(&self, invitation_info: Invitation) -> Result<Invitation, MyError> {
+        query::create_invitation(&self.client, invitation_info).await
+    }
+
+    async fn get_invitation(&self, invitation_id: i32) -> Result<Invitation, MyError> {
+        query::get_invitation(&self.client, invitation_id).await
+    }
+
+    async fn get_event_invitations(&self, event_id: i32) -> Result<Vec<Invitation>, MyError> {
+        query::get_event_invitations(&self.client, event_id).await
+    }
+
+    async fn get_organization_invitations(&self, organization_id: i32) -> Result<Vec<Invitation>, MyError> {
+        query::get_organization_invitations(&self.client, organization_id).await
+    }
+
+    async fn revoke_invitation(&self, invitation_id: i32) -> Result<u64, MyError> {
+        query::revoke_invitation(&self.client, invitation_id).await
+    }
+
+    async fn use_invitation(&self, token: &str) -> Result<Invitation, MyError> {
+        query::use_invitation(&self.client, token).await
+    }
+
+    async fn get_invitation_by_token(&self, token: &str) -> Result<Invitation, MyError> {
+        query::get_invitation_by_token(&self.client, token).await
+    }
+
+    async fn create_guest(&self, guest_info: Guest) -> Result<Guest, MyError> {
+        query::create_guest(&self.client, guest_info).await
+    }
+
+    async fn get_guest(&self, token: &str) -> Result<Guest, MyError> {
+        query::get_guest(&self.client, token).await
+    }
+
+    async fn modify_guest(&self, guest_info: Guest) -> Result<Guest, MyError> {
+        query::modify_guest(&self.client, guest_info).await
+    }
+
+    async fn get_event_guests(&self, event_id: i32) -> Result<Vec<Guest>, MyError> {
+        query::get_event_guests(&self.client, event_id).await
+    }
+
+    async fn merge_guest(&self, token: &str, person_id: i32) -> Result<Participation, MyError> {
+        query::merge_guest(&self.client, token, person_id).await
+    }
+
+    async fn is_planner_member(&self, person_id: i32, planner_id: i32) -> Result<bool, MyError> {
+        query::is_planner_member(&self.client, person_id, planner_id).await
+    }
+
+    async fn is_event_member(&self, person_id: i32, event_id: i32) -> Result<bool, MyError> {
+        query::is_event_member(&self.client, person_id, event_id).await
+    }
+
+    async fn get_organization_role(&self, person_id: i32, organization_id: i32) -> Result<Option<OrganizationRole>, MyError> {
+        query::get_organization_role(&self.client, person_id, organization_id).await
+    }
+
+    async fn search(&self, person_id: i32, text: &str, limit: i64) -> Result<SearchResults, MyError> {
+        query::search(&self.client, person_id, text, limit).await
+    }
+}
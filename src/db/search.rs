@@ -0,0 +1,83 @@
+//! Helpers for `GET /search`, which runs Postgres full-text search over the `search_vector`
+//! columns of events, persons and organizations.
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 10;
+pub const MAX_SEARCH_LIMIT: i64 = 50;
+pub const MAX_SEARCH_LENGTH: usize = 200;
+
+// `ts_headline` wraps matches in these, which cannot be mistaken for markup once the snippet is escaped.
+const START_MARK: char = '\u{2}';
+const STOP_MARK: char = '\u{3}';
+
+/// Options handed to `ts_headline`: up to two short fragments around the matches.
+pub fn headline_options() -> String {
+    format!(
+        "StartSel=\"{}\", StopSel=\"{}\", MaxFragments=2, MaxWords=20, MinWords=8, FragmentDelimiter=\" … \"",
+        START_MARK, STOP_MARK
+    )
+}
+
+/// Escapes a `ts_headline` snippet for HTML and highlights its matches with `<mark>`.
+pub fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            START_MARK => html.push_str("<mark>"),
+            STOP_MARK => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+/// The words of `text` as full-text search splits them: runs of letters and digits.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty())
+}
+
+/// Highlights the words of `text` that `matches` accepts, the way `highlight` renders a
+/// `ts_headline` snippet; used where searches do not run in Postgres.
+pub fn headline(text: &str, matches: impl Fn(&str) -> bool) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut rest = text;
+    for word in words(text) {
+        let start = rest.find(word).unwrap_or_default();
+        marked.push_str(&rest[..start]);
+        if matches(word) {
+            marked.push(START_MARK);
+            marked.push_str(word);
+            marked.push(STOP_MARK);
+        } else {
+            marked.push_str(word);
+        }
+        rest = &rest[start + word.len()..];
+    }
+    marked.push_str(rest);
+    highlight(&marked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{headline, highlight};
+
+    #[test]
+    fn snippets_are_escaped_and_highlighted() {
+        assert_eq!(
+            highlight("Soirée \u{2}gala\u{3} <script>alert('x')</script> & co"),
+            "Soirée <mark>gala</mark> &lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; co"
+        );
+    }
+
+    #[test]
+    fn headlines_mark_matching_words() {
+        assert_eq!(
+            headline("Gala <b>d'été</b> · galas", |word| word.to_lowercase().starts_with("gala")),
+            "<mark>Gala</mark> &lt;b&gt;d&#39;été&lt;/b&gt; · <mark>galas</mark>"
+        );
+    }
+}
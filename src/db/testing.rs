@@ -0,0 +1,207 @@
+//! A throwaway Postgres for the tests that exercise the SQL of `db::query`.
+//!
+//! The first `TestDatabase` of a test run starts a cluster of its own: `initdb` in a temporary
+//! directory, then `pg_ctl` with a server that only listens on a socket in that directory, so that
+//! it neither needs a port nor clashes with another run. Every `TestDatabase` is a database of that
+//! cluster with the migrations applied, dropped along with the value. A watchdog process stops the
+//! cluster and removes its directory once the test process is gone, however it ended.
+//!
+//! The binaries are looked up in `PG_BIN` if set, else in `PATH`. Postgres refuses to run as root,
+//! so when the tests do, the cluster runs as the `PG_TEST_USER` account, `postgres` by default.
+
+use std::{
+    ffi::OsString,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        OnceLock,
+    },
+};
+
+use actix_web::web;
+use deadpool_postgres::{Config, Pool};
+use tokio_postgres::NoTls;
+
+use crate::db::{
+    migrations,
+    repository::{PgRepository, Repository}
+};
+
+const SUPERUSER: &str = "postgres";
+const PORT: u16 = 5432;
+
+static CLUSTER: OnceLock<Cluster> = OnceLock::new();
+static DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+struct Cluster {
+    dir: PathBuf,
+    // The account the server runs as, when it cannot be the one running the tests.
+    owner: Option<String>,
+}
+
+fn is_root() -> bool {
+    fs::metadata("/proc/self").is_ok_and(|metadata| metadata.uid() == 0)
+}
+
+fn program(name: &str) -> OsString {
+    match std::env::var_os("PG_BIN") {
+        Some(bin) => Path::new(&bin).join(name).into_os_string(),
+        None => name.into(),
+    }
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().unwrap_or_else(|err| panic!("cannot run {:?}: {}", command, err));
+    if !output.status.success() {
+        panic!("{:?} failed: {}", command, String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+impl Cluster {
+    fn start() -> Self {
+        let dir = std::env::temp_dir().join(format!("praecipio-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("cannot create the directory of the test cluster");
+
+        let owner = is_root().then(|| std::env::var("PG_TEST_USER").unwrap_or_else(|_| SUPERUSER.to_string()));
+        if let Some(owner) = &owner {
+            run(Command::new("chown").arg(owner).arg(&dir));
+        }
+        let cluster = Cluster { dir, owner };
+
+        run(cluster.command("initdb")
+            .arg("--pgdata").arg(cluster.data())
+            .args(["--username", SUPERUSER, "--auth", "trust", "--encoding", "UTF8", "--no-sync"]));
+        run(cluster.command("pg_ctl")
+            .arg("--pgdata").arg(cluster.data())
+            .arg("--log").arg(cluster.dir.join("server.log"))
+            .arg("--options").arg(format!("-k {} -c listen_addresses= -p {} -F", cluster.dir.display(), PORT))
+            .args(["--wait", "start"]));
+
+        cluster.watch(process::id());
+        cluster
+    }
+
+    fn data(&self) -> PathBuf {
+        self.dir.join("data")
+    }
+
+    fn command(&self, name: &str) -> Command {
+        match &self.owner {
+            Some(owner) => {
+                let mut command = Command::new("runuser");
+                command.args(["-u", owner, "--"]).arg(program(name));
+                command
+            }
+            None => Command::new(program(name)),
+        }
+    }
+
+    /// Tears the cluster down after the process `pid` exits; statics are never dropped.
+    // The watchdog outlives the tests by design, so it is never waited for.
+    #[allow(clippy::zombie_processes)]
+    fn watch(&self, pid: u32) {
+        let stop = self.command("pg_ctl");
+        let stop = std::iter::once(stop.get_program())
+            .chain(stop.get_args())
+            .map(|arg| format!("'{}'", arg.to_string_lossy()))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let script = format!(
+            "while kill -0 {pid} 2>/dev/null; do sleep 1; done; {stop} --pgdata '{data}' --mode immediate stop; rm -rf '{dir}'",
+            data = self.data().display(),
+            dir = self.dir.display(),
+        );
+
+        Command::new("sh").arg("-c").arg(script)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("cannot start the watchdog of the test cluster");
+    }
+
+    fn config(&self, dbname: &str) -> Config {
+        Config {
+            host: Some(self.dir.display().to_string()),
+            port: Some(PORT),
+            user: Some(SUPERUSER.to_string()),
+            dbname: Some(dbname.to_string()),
+            ..Config::default()
+        }
+    }
+}
+
+/// A migrated database of its own for one test, dropped with it.
+pub struct TestDatabase {
+    name: String,
+    pool: Pool,
+}
+
+impl TestDatabase {
+    pub async fn create() -> Self {
+        let cluster = CLUSTER.get_or_init(Cluster::start);
+        let name = format!("test_{}", DATABASES.fetch_add(1, Ordering::Relaxed));
+
+        let admin = cluster.config("postgres").create_pool(None, NoTls).unwrap();
+        admin.get().await.unwrap()
+            .batch_execute(&format!("CREATE DATABASE {};", name))
+            .await
+            .unwrap();
+
+        let pool = cluster.config(&name).create_pool(None, NoTls).unwrap();
+        migrations::migrate_up(&mut pool.get().await.unwrap()).await.unwrap();
+
+        TestDatabase { name, pool }
+    }
+
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    pub fn repository(&self) -> web::Data<dyn Repository> {
+        web::Data::from(Arc::new(PgRepository::new(self.pool.clone())) as Arc<dyn Repository>)
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        self.pool.close();
+
+        if let Some(cluster) = CLUSTER.get() {
+            // `--force` ends the connections that the handlers of the test may still hold.
+            let _ = Command::new(program("dropdb"))
+                .arg("--host").arg(&cluster.dir)
+                .args(["--port", &PORT.to_string(), "--username", SUPERUSER, "--force", &self.name])
+                .output();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TestDatabase, CLUSTER};
+    use crate::db::errors::MyError;
+
+    #[actix_web::test]
+    async fn databases_are_separate_and_dropped() {
+        let first = TestDatabase::create().await;
+        let second = TestDatabase::create().await;
+
+        let planner = first.repository().connect().await.unwrap().create_planner().await.unwrap();
+        let planners = second.repository().connect().await.unwrap();
+        assert!(matches!(planners.get_planner(planner.planner_id).await, Err(MyError::NotFound)));
+
+        let name = first.name.clone();
+        drop(first);
+
+        let client = second.pool().get().await.unwrap();
+        let remaining = client.query("SELECT 1 FROM pg_database WHERE datname = $1;", &[&name]).await.unwrap();
+        assert!(remaining.is_empty());
+        assert!(CLUSTER.get().unwrap().dir.exists());
+    }
+}
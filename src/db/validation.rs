@@ -0,0 +1,357 @@
+//! Checks request bodies before they reach `db::query`.
+//!
+//! Text is trimmed and put in Unicode normalization form C before its length is checked, so that
+//! stored values compare and count the same whichever way clients composed them. Every problem is
+//! collected per field and reported at once as a 422.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::Utc;
+
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::db::{
+    dto::{CreateEvent, CreateOrganization, GuestRsvp, UpdateEvent, UpdateOrganization, UpdatePerson},
+    errors::MyError,
+    patch::Patch,
+    models::{
+        Affiliation,
+        Credentials,
+        EventOverride,
+        InvitationRequest,
+        Participation,
+        Plan,
+        Planner,
+        Registration,
+        RsvpStatus
+    }
+};
+
+pub const NAME_MAX: usize = 200;
+pub const LOCATION_MAX: usize = 500;
+pub const DESCRIPTION_MAX: usize = 10_000;
+pub const TIME_ZONE_MAX: usize = 64;
+pub const RECURRENCE_RULE_MAX: usize = 1_000;
+pub const DATES_MAX: usize = 1_000;
+pub const EMAIL_MAX: usize = 254;
+pub const PASSWORD_MIN: usize = 8;
+// Hashing is deliberately slow, so huge passwords are refused before they get there.
+pub const PASSWORD_MAX: usize = 1_024;
+pub const INVITATION_USES_MAX: i32 = 10_000;
+pub const PLUS_ONES_MAX: i32 = 20;
+
+/// Error messages by field name.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FieldErrors(BTreeMap<String, Vec<String>>);
+
+impl FieldErrors {
+    pub fn single(field: &str, message: impl Into<String>) -> Self {
+        let mut errors = FieldErrors::default();
+        errors.add(field, message);
+        errors
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.entry(field.to_string()).or_default().push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Each message prefixed with its field, in field order.
+    pub fn messages(&self) -> impl Iterator<Item = String> + '_ {
+        self.0.iter()
+            .flat_map(|(field, messages)| messages.iter().map(move |message| format!("{} {}", field, message)))
+    }
+
+    /// Normalizes `value` in place, then checks that it is at most `max` characters long and, if
+    /// `required`, not empty.
+    pub fn text(&mut self, field: &str, value: &mut String, required: bool, max: usize) {
+        *value = normalize(value);
+        if required && value.is_empty() {
+            self.add(field, "must not be empty");
+        }
+        if value.chars().count() > max {
+            self.add(field, format!("must be at most {} characters long", max));
+        }
+    }
+
+    pub fn optional_text(&mut self, field: &str, value: &mut Option<String>, max: usize) {
+        if let Some(value) = value.as_mut() {
+            self.text(field, value, true, max);
+        }
+    }
+
+    /// Checks a merge patch field of text that cannot be null.
+    pub fn patched_text(&mut self, field: &str, value: &mut Patch<String>, required: bool, max: usize) {
+        match value {
+            Patch::Absent => {}
+            Patch::Null => self.add(field, "must not be null"),
+            Patch::Value(value) => self.text(field, value, required, max),
+        }
+    }
+
+    pub fn not_null<T>(&mut self, field: &str, value: &Patch<T>) {
+        if let Patch::Null = value {
+            self.add(field, "must not be null");
+        }
+    }
+
+    pub fn id(&mut self, field: &str, id: i32) {
+        if id < 1 {
+            self.add(field, "must be a positive id");
+        }
+    }
+
+    pub fn optional_id(&mut self, field: &str, id: Option<i32>) {
+        if let Some(id) = id {
+            self.id(field, id);
+        }
+    }
+
+    pub fn email(&mut self, field: &str, value: &mut String) {
+        self.text(field, value, true, EMAIL_MAX);
+        if !value.is_empty() && !value.contains('@') {
+            self.add(field, "must be an email address");
+        }
+    }
+
+    pub fn count(&mut self, field: &str, count: usize, max: usize) {
+        if count > max {
+            self.add(field, format!("must hold at most {} values", max));
+        }
+    }
+}
+
+impl Display for FieldErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.messages().collect::<Vec<String>>().join("; "))
+    }
+}
+
+pub fn normalize(value: &str) -> String {
+    value.trim().nfc().collect()
+}
+
+pub trait Validate: Sized {
+    /// Normalizes the fields of `self` and records what is wrong with them in `errors`.
+    fn check(&mut self, errors: &mut FieldErrors);
+
+    fn validated(mut self) -> Result<Self, MyError> {
+        let mut errors = FieldErrors::default();
+        self.check(&mut errors);
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(MyError::Invalid(errors))
+        }
+    }
+}
+
+impl Validate for CreateEvent {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.text("event_name", &mut self.event_name, true, NAME_MAX);
+        errors.text("event_location", &mut self.event_location, false, LOCATION_MAX);
+        errors.text("event_description", &mut self.event_description, false, DESCRIPTION_MAX);
+        errors.text("time_zone", &mut self.time_zone, true, TIME_ZONE_MAX);
+        errors.optional_text("recurrence_rule", &mut self.recurrence_rule, RECURRENCE_RULE_MAX);
+        errors.count("recurrence_dates", self.recurrence_dates.len(), DATES_MAX);
+        errors.count("exception_dates", self.exception_dates.len(), DATES_MAX);
+    }
+}
+
+/// Only `recurrence_rule` may be cleared; clearing the date lists empties them.
+impl Validate for UpdateEvent {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.patched_text("event_name", &mut self.event_name, true, NAME_MAX);
+        errors.patched_text("event_location", &mut self.event_location, false, LOCATION_MAX);
+        errors.patched_text("event_description", &mut self.event_description, false, DESCRIPTION_MAX);
+        errors.not_null("starts_at", &self.starts_at);
+        errors.not_null("ends_at", &self.ends_at);
+        errors.patched_text("time_zone", &mut self.time_zone, true, TIME_ZONE_MAX);
+        errors.not_null("all_day", &self.all_day);
+        if let Patch::Value(rule) = &mut self.recurrence_rule {
+            errors.text("recurrence_rule", rule, true, RECURRENCE_RULE_MAX);
+        }
+        if let Patch::Value(dates) = &self.recurrence_dates {
+            errors.count("recurrence_dates", dates.len(), DATES_MAX);
+        }
+        if let Patch::Value(dates) = &self.exception_dates {
+            errors.count("exception_dates", dates.len(), DATES_MAX);
+        }
+    }
+}
+
+impl Validate for EventOverride {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.optional_id("override_id", self.override_id);
+        errors.id("event_id", self.event_id);
+        errors.optional_text("event_name", &mut self.event_name, NAME_MAX);
+        if let Some(location) = self.event_location.as_mut() {
+            errors.text("event_location", location, false, LOCATION_MAX);
+        }
+        if let Some(description) = self.event_description.as_mut() {
+            errors.text("event_description", description, false, DESCRIPTION_MAX);
+        }
+    }
+}
+
+impl Validate for UpdatePerson {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.patched_text("person_name", &mut self.person_name, true, NAME_MAX);
+    }
+}
+
+impl Validate for CreateOrganization {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.text("organization_name", &mut self.organization_name, true, NAME_MAX);
+    }
+}
+
+impl Validate for UpdateOrganization {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.patched_text("organization_name", &mut self.organization_name, true, NAME_MAX);
+    }
+}
+
+impl Validate for Planner {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.id("planner_id", self.planner_id);
+    }
+}
+
+impl Validate for Plan {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.optional_id("plan_id", self.plan_id);
+        errors.id("event_id", self.event_id);
+        errors.id("planner_id", self.planner_id);
+    }
+}
+
+impl Validate for Affiliation {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.optional_id("affiliation_id", self.affiliation_id);
+        errors.id("person_id", self.person_id);
+        errors.id("organization_id", self.organization_id);
+    }
+}
+
+impl Validate for Participation {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.optional_id("participation_id", self.participation_id);
+        errors.id("event_id", self.event_id);
+        errors.id("person_id", self.person_id);
+    }
+}
+
+impl Validate for InvitationRequest {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        if self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            errors.add("expires_at", "must be in the future");
+        }
+        if !(1..=INVITATION_USES_MAX).contains(&self.max_uses) {
+            errors.add("max_uses", format!("must be between 1 and {}", INVITATION_USES_MAX));
+        }
+    }
+}
+
+impl Validate for GuestRsvp {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.text("guest_name", &mut self.guest_name, true, NAME_MAX);
+        errors.email("email", &mut self.email);
+        if self.rsvp_status == RsvpStatus::Invited {
+            errors.add("rsvp_status", "must be an answer: accepted, declined or tentative");
+        }
+        if !(0..=PLUS_ONES_MAX).contains(&self.plus_ones) {
+            errors.add("plus_ones", format!("must be between 0 and {}", PLUS_ONES_MAX));
+        }
+    }
+}
+
+/// Passwords are taken exactly as typed: neither trimmed nor normalized.
+impl Validate for Registration {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.text("person_name", &mut self.person_name, true, NAME_MAX);
+        errors.email("email", &mut self.email);
+        let length = self.password.chars().count();
+        if !(PASSWORD_MIN..=PASSWORD_MAX).contains(&length) {
+            errors.add("password", format!("must be between {} and {} characters long", PASSWORD_MIN, PASSWORD_MAX));
+        }
+    }
+}
+
+impl Validate for Credentials {
+    fn check(&mut self, errors: &mut FieldErrors) {
+        errors.text("email", &mut self.email, true, EMAIL_MAX);
+        if self.password.chars().count() > PASSWORD_MAX {
+            errors.add("password", format!("must be at most {} characters long", PASSWORD_MAX));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{FieldErrors, Validate, DESCRIPTION_MAX};
+    use crate::db::{
+        dto::{CreateEvent, CreateOrganization},
+        errors::MyError,
+        models::{Participation, Registration, RsvpStatus}
+    };
+
+    fn errors<T: Validate>(value: T) -> FieldErrors {
+        match value.validated() {
+            Err(MyError::Invalid(errors)) => errors,
+            _ => FieldErrors::default(),
+        }
+    }
+
+    #[test]
+    fn text_is_trimmed_and_normalized() {
+        let organization = CreateOrganization { organization_name: "  Cafe\u{301} Zoe\u{308}\n".to_string() };
+        let organization = organization.validated().ok().unwrap();
+        assert_eq!(organization.organization_name, "Caf\u{e9} Zo\u{eb}");
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let event = CreateEvent {
+            event_name: " \t ".to_string(),
+            event_location: "Paris".to_string(),
+            event_description: "x".repeat(DESCRIPTION_MAX + 1),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+
+        let mut expected = FieldErrors::single("event_name", "must not be empty");
+        expected.add("event_description", "must be at most 10000 characters long");
+        assert_eq!(errors(event), expected);
+
+        let participation = Participation { participation_id: None, event_id: 0, person_id: 3, rsvp_status: RsvpStatus::Invited, responded_at: None, version: 1 };
+        assert_eq!(errors(participation), FieldErrors::single("event_id", "must be a positive id"));
+    }
+
+    #[test]
+    fn passwords_are_kept_as_typed() {
+        let registration = Registration {
+            person_name: "GDVCB".to_string(),
+            email: " gdvcb@example.org ".to_string(),
+            password: " secret  ".to_string(),
+        };
+        let registration = registration.validated().ok().unwrap();
+        assert_eq!(registration.email, "gdvcb@example.org");
+        assert_eq!(registration.password, " secret  ");
+
+        let registration = Registration { person_name: "GDVCB".to_string(), email: "nobody".to_string(), password: "short".to_string() };
+        let errors = errors(registration).to_string();
+        assert_eq!(errors, "email must be an email address; password must be between 8 and 1024 characters long");
+    }
+}
@@ -3,154 +3,2219 @@ pub mod db;
 #[cfg(test)]
 mod tests {
     use crate::db::models::{
+        Invitation,
         Person,
-        Event,
-        Organization
+        Participant,
+        Participation,
+        Affiliation,
+        Member,
+        Membership,
+        Organization,
+        OrganizationRole,
+        Plan,
+        Planner,
+        RsvpStatus,
+        SearchResults,
+        SessionToken
     };
+    use crate::db::dto::{CreateEvent, CreateOrganization, EventResponse, GuestResponse, OrganizationResponse, PersonResponse, RsvpPage, UpdateAffiliation, UpdateParticipation, UpdatePerson};
+    use crate::db::patch::Patch;
+    use crate::db::pagination::{Page, PageSettings};
     use crate::db::handlers::{
-        create_person,
         delete_person,
         create_event,
         delete_event,
+        get_person_events,
+        create_plan,
+        create_participation,
+        modify_participation,
+        delete_participation,
+        get_event_participants,
+        get_person_participations,
         create_organization,
+        modify_organization,
         delete_organization,
+        get_events,
+        get_event,
+        get_persons,
+        get_plans,
+        get_affiliations,
+        get_participations,
+        get_plan,
+        get_planner,
+        get_affiliation,
+        get_participation,
+        get_planners,
+        delete_planner,
+        legacy,
+        modify_event,
+        get_event_overrides,
+        get_event_override,
+        modify_person,
+        create_affiliation,
+        modify_affiliation,
+        delete_affiliation,
+        get_organization_members,
+        get_person_organizations,
+        get_person_calendar,
+        get_organization_calendar,
+        import_calendar,
+        create_event_invitation,
+        get_event_invitations,
+        create_organization_invitation,
+        get_organization_invitations,
+        revoke_invitation,
+        accept_invitation,
+        get_rsvp_page,
+        create_guest_rsvp,
+        get_guest_rsvp,
+        modify_guest_rsvp,
+        merge_guest_rsvp,
+        get_event_guests,
+        register,
+        login,
+        logout,
+        search,
     };
 
+    use crate::db::{auth, errors::MyError, repository::MemoryRepository, testing::TestDatabase};
+
     use super::*;
-    use actix_web::http::StatusCode;
+    use actix_web::http::{header, StatusCode};
     use actix_web::test;
+    use chrono::{TimeZone, Utc};
+
+    /// A repository of its own, so that tests neither need a database nor see each other's rows.
+    fn repository() -> web::Data<dyn Repository> {
+        web::Data::from(Arc::new(MemoryRepository::new()) as Arc<dyn Repository>)
+    }
+
+    fn signer() -> web::Data<TokenSigner> {
+        web::Data::new(TokenSigner::new(b"test secret"))
+    }
+
+    /// Creates a person with a live session and returns it along with its bearer token.
+    async fn sign_in(repository: &web::Data<dyn Repository>, signer: &TokenSigner) -> (Person, String) {
+        let client = repository.connect().await.unwrap();
+
+        let planner = client.create_planner().await.unwrap();
+        let person = Person {
+            person_id: None,
+            person_name: "GDVCB".to_string(),
+            planner_id: Some(planner.planner_id),
+            version: 1,
+        };
+        let person = client.create_person(person).await.unwrap();
+
+        let claims = auth::TokenClaims {
+            session_id: auth::new_session_id(),
+            person_id: person.person_id.unwrap(),
+            expires_at: auth::session_expiry(Utc::now()),
+        };
+        client.create_session(&claims.session_id, claims.person_id, claims.expires_at).await.unwrap();
+
+        (person, signer.sign(&claims))
+    }
+
+    fn bearer(token: &str) -> (header::HeaderName, String) {
+        (header::AUTHORIZATION, format!("Bearer {}", token))
+    }
+
+    #[actix_web::test]
+    async fn test_delete_person() {
+        let database = TestDatabase::create().await;
+        let repository = database.repository();
+        let signer = signer();
+        let (me, token) = sign_in(&repository, &signer).await;
+        
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/users/{person_id}")
+                    .route(web::delete().to(delete_person))
+                )
+        ).await;
+
+        // Only the person themselves may delete their account.
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users/{}", me.person_id.unwrap()))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let client = database.pool().get().await.unwrap();
+        let planners = client.query("SELECT 1 FROM planner WHERE planner_id = $1;", &[&me.planner_id]).await.unwrap();
+        assert!(planners.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_create_delete_event() {
+        let event = CreateEvent {
+            event_name : "anniv GDVCB".to_string(),
+            event_description: "Petit anniv".to_string(),
+            event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+        
+        let database = TestDatabase::create().await;
+        let repository = database.repository();
+        let signer = signer();
+        let (_, token) = sign_in(&repository, &signer).await;
+        
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::delete().to(delete_event))
+                )
+        ).await;
+
+        // Create the request that inserts a new event in the table
+        let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
+            .uri("/events")
+            .set_json(event)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body: EventResponse = test::read_body_json(resp).await;
+        
+        // Create the request that delete the previsouly inserted person in the database
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/events/{}", body.event_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_create_event_ending_before_start() {
+        let event = CreateEvent {
+            event_name : "anniv GDVCB".to_string(),
+            event_description: "Petit anniv".to_string(),
+            event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+
+        let repository = repository();
+        let signer = signer();
+        let (_, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+        ).await;
+
+        let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
+            .uri("/events")
+            .set_json(event)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_person_events_window() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+        let (_, stranger_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/users/{person_id}")
+                    .route(web::delete().to(delete_person))
+                )
+                .service(web::resource("/users/{person_id}/events")
+                    .route(web::get().to(get_person_events))
+                )
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::delete().to(delete_event))
+                )
+        ).await;
+
+        let mut events = Vec::new();
+        for day in [18, 25] {
+            let event = CreateEvent {
+                event_name : format!("soiree du {}", day),
+                event_description: "Soiree".to_string(),
+                event_location: "Paris".to_string(),
+                starts_at: Utc.with_ymd_and_hms(2022, 6, day, 19, 0, 0).unwrap(),
+                ends_at: Utc.with_ymd_and_hms(2022, 6, day, 23, 0, 0).unwrap(),
+                time_zone: "Europe/Paris".to_string(),
+                all_day: false,
+                recurrence_rule: None,
+                recurrence_dates: vec![],
+                exception_dates: vec![],
+            };
+            let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(event).to_request();
+            // New events land in their creator's planner.
+            let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+            events.push(event);
+        }
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!(
+                "/users/{}/events?from=2022-06-18T00:00:00Z&to=2022-06-20T00:00:00Z",
+                person.person_id.unwrap()
+            ))
+            .to_request();
+        let window: Vec<EventResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].event_id, events[0].event_id);
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users/{}/events", person.person_id.unwrap()))
+            .to_request();
+        let all: Vec<EventResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(all.len(), 2);
+        assert!(all[0].starts_at < all[1].starts_at);
+
+        // Nobody else gets to see the schedule.
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&stranger_token))
+            .uri(&format!("/users/{}/events", person.person_id.unwrap()))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        for event in events {
+            let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/events/{}", event.event_id)).to_request();
+            test::call_service(&app, req).await;
+        }
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/users/{}", person.person_id.unwrap())).to_request();
+        test::call_service(&app, req).await;
+    }
+
+    #[actix_web::test]
+    async fn test_participation_rsvp() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+        let (stranger, stranger_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/users/{person_id}")
+                    .route(web::delete().to(delete_person))
+                )
+                .service(web::resource("/users/{person_id}/participations")
+                    .route(web::get().to(get_person_participations))
+                )
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::delete().to(delete_event))
+                )
+                .service(web::resource("/events/{event_id}/participants")
+                    .route(web::get().to(get_event_participants))
+                )
+                .service(web::resource("/participations")
+                    .route(web::post().to(create_participation))
+                )
+                .service(web::resource("/participations/{participation_id}")
+                    .route(web::patch().to(modify_participation))
+                    .route(web::delete().to(delete_participation))
+                )
+        ).await;
+
+        let event = CreateEvent {
+            event_name : "anniv GDVCB".to_string(),
+            event_description: "Petit anniv".to_string(),
+            event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(event).to_request();
+        let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+
+        let participation = Participation {
+            participation_id: None,
+            event_id: event.event_id,
+            person_id: person.person_id.unwrap(),
+            rsvp_status: RsvpStatus::Invited,
+            responded_at: None,
+            version: 1,
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/participations").set_json(participation).to_request();
+        let participation: Participation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participation.rsvp_status, RsvpStatus::Invited);
+        assert!(participation.responded_at.is_none());
+
+        let answer = UpdateParticipation { rsvp_status: RsvpStatus::Accepted };
+
+        // Only the participating person may answer for themselves.
+        let req = test::TestRequest::patch().insert_header(bearer(&stranger_token)).uri(&format!("/participations/{}", participation.participation_id.unwrap())).set_json(&answer).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::patch().insert_header(bearer(&token)).uri(&format!("/participations/{}", participation.participation_id.unwrap())).set_json(&answer).to_request();
+        let participation: Participation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participation.rsvp_status, RsvpStatus::Accepted);
+        assert!(participation.responded_at.is_some());
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/events/{}/participants", event.event_id))
+            .to_request();
+        let participants: Vec<Participant> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participants.len(), 1);
+        assert_eq!(participants[0].person_name, "GDVCB");
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users/{}/participations", person.person_id.unwrap()))
+            .to_request();
+        let participations: Vec<Participation> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participations.len(), 1);
+
+        // Who attends, and how they answered, stays among the event's members and the participant.
+        for uri in [format!("/events/{}/participants", event.event_id), format!("/users/{}/participations", person.person_id.unwrap())] {
+            let req = test::TestRequest::get().insert_header(bearer(&stranger_token)).uri(&uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        }
+
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/participations/{}", participation.participation_id.unwrap())).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/events/{}", event.event_id)).to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::delete().insert_header(bearer(&stranger_token)).uri(&format!("/users/{}", stranger.person_id.unwrap())).to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/users/{}", person.person_id.unwrap())).to_request();
+        test::call_service(&app, req).await;
+    }
+
+    #[actix_web::test]
+    async fn test_permissions() {
+        let repository = repository();
+        let signer = signer();
+        let (owner, owner_token) = sign_in(&repository, &signer).await;
+        let (other, other_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::patch().to(modify_event))
+                    .route(web::delete().to(delete_event))
+                )
+                .service(web::resource("/events/{event_id}/occurrences")
+                    .route(web::get().to(get_event_overrides))
+                )
+                .service(web::resource("/events/{event_id}/occurrences/{occurrence_starts_at}")
+                    .route(web::get().to(get_event_override))
+                )
+                .service(web::resource("/plans")
+                    .route(web::post().to(create_plan))
+                )
+                .service(web::resource("/users/{person_id}")
+                    .route(web::patch().to(modify_person))
+                    .route(web::delete().to(delete_person))
+                )
+                .service(web::resource("/affiliations")
+                    .route(web::post().to(create_affiliation))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}")
+                    .route(web::delete().to(delete_organization))
+                )
+        ).await;
+
+        let event = CreateEvent {
+            event_name : "anniv GDVCB".to_string(),
+            event_description: "Petit anniv".to_string(),
+            event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&owner_token)).uri("/events").set_json(event).to_request();
+        let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+
+        // Someone outside the event's planners can neither change it nor plan it elsewhere.
+        let req = test::TestRequest::patch().insert_header(bearer(&other_token)).uri(&format!("/events/{}", event.event_id)).set_json(&event).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().insert_header(bearer(&other_token)).uri(&format!("/events/{}", event.event_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        // Nor read its occurrences, whether they were moved or not.
+        let overrides_uri = format!("/events/{}/occurrences", event.event_id);
+        let override_uri = format!("{}/2022-06-18T19:00:00Z", overrides_uri);
+        let req = test::TestRequest::get().insert_header(bearer(&owner_token)).uri(&overrides_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        for uri in [&overrides_uri, &override_uri] {
+            let req = test::TestRequest::get().insert_header(bearer(&other_token)).uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        }
+
+        let plan = Plan { plan_id: None, event_id: event.event_id, planner_id: other.planner_id.unwrap() };
+        let req = test::TestRequest::post().insert_header(bearer(&other_token)).uri("/plans").set_json(plan).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::patch().insert_header(bearer(&other_token)).uri(&format!("/users/{}", owner.person_id.unwrap())).set_json(serde_json::json!({ "person_name": owner.person_name })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().insert_header(bearer(&other_token)).uri(&format!("/users/{}", owner.person_id.unwrap())).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let organization = CreateOrganization {
+            organization_name: "festival_a".to_string(),
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&owner_token)).uri("/organizations").set_json(organization).to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+
+        let affiliation = Affiliation {
+            affiliation_id: None,
+            person_id: other.person_id.unwrap(),
+            organization_id: organization.organization_id,
+            role: OrganizationRole::Admin,
+            version: 1,
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&other_token)).uri("/affiliations").set_json(&affiliation).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let affiliation = Affiliation { role: OrganizationRole::Member, ..affiliation };
+        let req = test::TestRequest::post().insert_header(bearer(&owner_token)).uri("/affiliations").set_json(&affiliation).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        // Members of an organization share the events of its planner.
+        let plan = Plan { plan_id: None, event_id: event.event_id, planner_id: organization.planner_id.unwrap() };
+        let req = test::TestRequest::post().insert_header(bearer(&owner_token)).uri("/plans").set_json(plan).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::patch().insert_header(bearer(&other_token)).uri(&format!("/events/{}", event.event_id)).set_json(&event).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&other_token)).uri(&format!("/organizations/{}", organization.organization_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().insert_header(bearer(&owner_token)).uri(&format!("/organizations/{}", organization.organization_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&owner_token)).uri(&format!("/events/{}", event.event_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::delete().insert_header(bearer(&other_token)).uri(&format!("/users/{}", other.person_id.unwrap())).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::delete().insert_header(bearer(&owner_token)).uri(&format!("/users/{}", owner.person_id.unwrap())).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_organization_roles() {
+        let repository = repository();
+        let signer = signer();
+        let (owner, owner_token) = sign_in(&repository, &signer).await;
+        let (admin, admin_token) = sign_in(&repository, &signer).await;
+        let (member, member_token) = sign_in(&repository, &signer).await;
+        let (_, stranger_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/users/{person_id}")
+                    .route(web::delete().to(delete_person))
+                )
+                .service(web::resource("/users/{person_id}/organizations")
+                    .route(web::get().to(get_person_organizations))
+                )
+                .service(web::resource("/affiliations")
+                    .route(web::post().to(create_affiliation))
+                )
+                .service(web::resource("/affiliations/{affiliation_id}")
+                    .route(web::patch().to(modify_affiliation))
+                    .route(web::delete().to(delete_affiliation))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}")
+                    .route(web::patch().to(modify_organization))
+                    .route(web::delete().to(delete_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}/members")
+                    .route(web::get().to(get_organization_members))
+                )
+        ).await;
+
+        let organization = CreateOrganization {
+            organization_name: "festival_a".to_string(),
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&owner_token)).uri("/organizations").set_json(organization).to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+        let organization_id = organization.organization_id;
+
+        let affiliation = Affiliation {
+            affiliation_id: None,
+            person_id: admin.person_id.unwrap(),
+            organization_id,
+            role: OrganizationRole::Admin,
+            version: 1,
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&owner_token)).uri("/affiliations").set_json(affiliation).to_request();
+        let admin_affiliation: Affiliation = test::call_and_read_body_json(&app, req).await;
+
+        // Admins manage members but cannot hand out ownership.
+        let affiliation = Affiliation {
+            affiliation_id: None,
+            person_id: member.person_id.unwrap(),
+            organization_id,
+            role: OrganizationRole::Owner,
+            version: 1,
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&admin_token)).uri("/affiliations").set_json(&affiliation).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let affiliation = Affiliation { role: OrganizationRole::Member, ..affiliation };
+        let req = test::TestRequest::post().insert_header(bearer(&admin_token)).uri("/affiliations").set_json(&affiliation).to_request();
+        let member_affiliation: Affiliation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(member_affiliation.role, OrganizationRole::Member);
+
+        let promotion = UpdateAffiliation { role: OrganizationRole::Admin };
+        let req = test::TestRequest::patch().insert_header(bearer(&member_token)).uri(&format!("/affiliations/{}", member_affiliation.affiliation_id.unwrap())).set_json(&promotion).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&member_token))
+            .uri(&format!("/organizations/{}/members", organization_id))
+            .to_request();
+        let members: Vec<Member> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            members.iter().map(|m| m.role).collect::<Vec<_>>(),
+            vec![OrganizationRole::Owner, OrganizationRole::Admin, OrganizationRole::Member]
+        );
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&member_token))
+            .uri(&format!("/users/{}/organizations", member.person_id.unwrap()))
+            .to_request();
+        let memberships: Vec<Membership> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(memberships.len(), 1);
+        assert_eq!(memberships[0].organization_name, "festival_a");
+
+        // The roster stays within the organization, and memberships with the person.
+        for (uri, token) in [
+            (format!("/organizations/{}/members", organization_id), &stranger_token),
+            (format!("/users/{}/organizations", member.person_id.unwrap()), &stranger_token),
+            (format!("/users/{}/organizations", owner.person_id.unwrap()), &member_token),
+        ] {
+            let req = test::TestRequest::get().insert_header(bearer(token)).uri(&uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        }
+
+        // Managers rename the organization; its planner stays.
+        let rename = serde_json::json!({ "organization_name": " festival_b " });
+        let req = test::TestRequest::patch().insert_header(bearer(&member_token)).uri(&format!("/organizations/{}", organization_id)).set_json(&rename).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::patch().insert_header(bearer(&admin_token)).uri(&format!("/organizations/{}", organization_id)).set_json(&rename).to_request();
+        let renamed: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(renamed.organization_name, "festival_b");
+        assert_eq!(renamed.planner_id, organization.planner_id);
+
+        // The last owner can neither leave nor step down.
+        let owner_affiliation_id = members.iter().find(|m| m.role == OrganizationRole::Owner).unwrap().affiliation_id;
+        let step_down = UpdateAffiliation { role: OrganizationRole::Member };
+        let req = test::TestRequest::patch().insert_header(bearer(&owner_token)).uri(&format!("/affiliations/{}", owner_affiliation_id)).set_json(&step_down).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+        let req = test::TestRequest::delete().insert_header(bearer(&owner_token)).uri(&format!("/affiliations/{}", owner_affiliation_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&admin_token)).uri(&format!("/organizations/{}", organization.organization_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        // Once ownership is handed over, the former owner may leave.
+        let promotion = UpdateAffiliation { role: OrganizationRole::Owner };
+        let req = test::TestRequest::patch().insert_header(bearer(&owner_token)).uri(&format!("/affiliations/{}", admin_affiliation.affiliation_id.unwrap())).set_json(&promotion).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::delete().insert_header(bearer(&owner_token)).uri(&format!("/affiliations/{}", owner_affiliation_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&admin_token)).uri(&format!("/organizations/{}", organization.organization_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        for (person, token) in [(owner, owner_token), (admin, admin_token), (member, member_token)] {
+            let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/users/{}", person.person_id.unwrap())).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_legacy_body_routes() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                    .route(web::patch().to(legacy::modify_event).wrap(deprecated()))
+                    .route(web::delete().to(legacy::delete_event).wrap(deprecated()))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::get().to(get_event))
+                )
+                .service(web::resource("/users/{person_id}")
+                    .route(web::delete().to(delete_person))
+                )
+        ).await;
+
+        let event = CreateEvent {
+            event_name : "anniv GDVCB".to_string(),
+            event_description: "Petit anniv".to_string(),
+            event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        let mut event: EventResponse = test::call_and_read_body_json(&app, req).await;
+
+        // The id is required in the body of the old routes.
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri("/events")
+            .set_json(serde_json::json!({ "event_name": event.event_name }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(resp.headers().get("Deprecation").unwrap(), "true");
+
+        event.event_name = "anniv GDVCB renamed".to_string();
+        let req = test::TestRequest::patch().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Deprecation").unwrap(), "true");
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/events/{}", event.event_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().get("Deprecation").is_none());
+        let fetched: EventResponse = test::read_body_json(resp).await;
+        assert_eq!(fetched.event_name, "anniv GDVCB renamed");
+
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        // A deleted event is as out of reach as anyone else's.
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/events/{}", event.event_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&format!("/users/{}", person.person_id.unwrap())).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_register_login_logout() {
+        let repository = repository();
+        let signer = signer();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/auth/register")
+                    .route(web::post().to(register))
+                )
+                .service(web::resource("/auth/login")
+                    .route(web::post().to(login))
+                )
+                .service(web::resource("/auth/logout")
+                    .route(web::post().to(logout))
+                )
+                .service(web::resource("/events")
+                    .route(web::get().to(get_events))
+                )
+        ).await;
+
+        let email = format!("gdvcb-{}@example.org", auth::new_session_id());
+        let registration = serde_json::json!({
+            "person_name": "GDVCB",
+            "email": email,
+            "password": "correct horse battery staple",
+        });
+        let req = test::TestRequest::post().uri("/auth/register").set_json(&registration).to_request();
+        let person: PersonResponse = test::call_and_read_body_json(&app, req).await;
+
+        // Emails are taken regardless of case.
+        for email in [email.clone(), email.to_uppercase()] {
+            let registration = serde_json::json!({
+                "person_name": "GDVCB",
+                "email": email,
+                "password": "correct horse battery staple",
+            });
+            let req = test::TestRequest::post().uri("/auth/register").set_json(&registration).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::CONFLICT);
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(serde_json::json!({ "email": email, "password": "wrong" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(serde_json::json!({ "email": email.to_uppercase(), "password": "correct horse battery staple" }))
+            .to_request();
+        let session: SessionToken = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(session.person_id, person.person_id);
+
+        let req = test::TestRequest::get().uri("/events").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get().insert_header(bearer(&session.token)).uri("/events").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post().insert_header(bearer(&session.token)).uri("/auth/logout").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().insert_header(bearer(&session.token)).uri("/events").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let client = repository.connect().await.unwrap();
+        client.delete_person(person.person_id).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_list_pagination() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .app_data(web::Data::new(PageSettings { page_size: 2, max_page_size: 3 }))
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                    .route(web::get().to(get_events))
+                )
+                .service(web::resource("/plans")
+                    .route(web::get().to(get_plans))
+                )
+                .service(web::resource("/plans/{plan_id}")
+                    .route(web::get().to(get_plan))
+                )
+                .service(web::resource("/planner")
+                    .route(web::get().to(get_planners))
+                )
+                .service(web::resource("/users")
+                    .route(web::get().to(get_persons))
+                )
+        ).await;
+
+        let mut event_ids = vec![];
+        for name in ["one", "two", "three", "four"] {
+            let event = serde_json::json!({
+                "event_name": name,
+                "event_location": "Paris",
+                "event_description": "",
+                "starts_at": "2022-06-18T19:00:00Z",
+                "ends_at": "2022-06-18T21:00:00Z",
+            });
+            let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(event).to_request();
+            let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+            event_ids.push(event.event_id);
+        }
+
+        // The configured page size applies by default.
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri("/events").to_request();
+        let page: Page<EventResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.iter().map(|event| event.event_id).collect::<Vec<_>>(), event_ids[..2]);
+        let cursor = page.next_cursor.unwrap();
+
+        // Larger limits are capped at the maximum, and the last page has no cursor.
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/events?after={}&limit=50", cursor))
+            .to_request();
+        let page: Page<EventResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.iter().map(|event| event.event_id).collect::<Vec<_>>(), event_ids[2..]);
+        assert_eq!(page.next_cursor, None);
+
+        for uri in ["/events?limit=0", "/events?after=nonsense"] {
+            let req = test::TestRequest::get().insert_header(bearer(&token)).uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri("/planner").to_request();
+        let page: Page<Planner> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.iter().map(|planner| planner.planner_id).collect::<Vec<_>>(), vec![person.planner_id.unwrap()]);
+
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri("/plans?limit=3").to_request();
+        let page: Page<Plan> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.len(), 3);
+        assert!(page.next_cursor.is_some());
+
+        let plan = &page.items[0];
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/plans/{}", plan.plan_id.unwrap()))
+            .to_request();
+        let fetched: Plan = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(fetched.event_id, event_ids[0]);
+
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri("/users?limit=1").to_request();
+        let page: Page<PersonResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.len(), 1);
+
+        let client = repository.connect().await.unwrap();
+        for event_id in event_ids {
+            client.delete_event(event_id).await.unwrap();
+        }
+        client.delete_person(person.person_id.unwrap()).await.unwrap();
+    }
+
+    /// Lists and reads by id only show rows to people they concern, on both stores.
+    async fn check_list_scoping(repository: web::Data<dyn Repository>) {
+        let signer = signer();
+        let (owner, owner_token) = sign_in(&repository, &signer).await;
+        let (member, member_token) = sign_in(&repository, &signer).await;
+        let (stranger, stranger_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/affiliations")
+                    .route(web::get().to(get_affiliations))
+                )
+                .service(web::resource("/participations")
+                    .route(web::get().to(get_participations))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::get().to(get_event))
+                )
+                .service(web::resource("/plans/{plan_id}")
+                    .route(web::get().to(get_plan))
+                )
+                .service(web::resource("/planner/{planner_id}")
+                    .route(web::get().to(get_planner))
+                )
+                .service(web::resource("/affiliations/{affiliation_id}")
+                    .route(web::get().to(get_affiliation))
+                )
+                .service(web::resource("/participations/{participation_id}")
+                    .route(web::get().to(get_participation))
+                )
+        ).await;
+
+        let client = repository.connect().await.unwrap();
+        let planner = client.create_planner().await.unwrap();
+        let organization = Organization {
+            organization_id: None,
+            organization_name: "festival_a".to_string(),
+            planner_id: Some(planner.planner_id),
+            version: 1,
+        };
+        let organization_id = client.create_organization(organization).await.unwrap().organization_id.unwrap();
+        let mut affiliations = Vec::new();
+        for (person, role) in [(&owner, OrganizationRole::Owner), (&member, OrganizationRole::Member)] {
+            let affiliation = Affiliation { affiliation_id: None, person_id: person.person_id.unwrap(), organization_id, role, version: 1 };
+            affiliations.push(client.create_affiliation(affiliation).await.unwrap().affiliation_id.unwrap());
+        }
+
+        // The owner plans a party both others take part in; the stranger also has an event of their own.
+        let mut events = Vec::new();
+        let mut plans = Vec::new();
+        let mut participations = Vec::new();
+        for (planner_id, guests) in [(owner.planner_id, vec![&member, &stranger]), (stranger.planner_id, vec![&stranger])] {
+            let event = CreateEvent {
+                event_name: "anniv GDVCB".to_string(),
+                event_description: "".to_string(),
+                event_location: "Paris".to_string(),
+                starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+                ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+                time_zone: "Europe/Paris".to_string(),
+                all_day: false,
+                recurrence_rule: None,
+                recurrence_dates: vec![],
+                exception_dates: vec![],
+            };
+            let event_id = client.create_event(event.into()).await.unwrap().event_id.unwrap();
+            let plan = client.create_plan(Plan { plan_id: None, event_id, planner_id: planner_id.unwrap() }).await.unwrap();
+            events.push(event_id);
+            plans.push(plan.plan_id.unwrap());
+            for guest in guests {
+                let participation = Participation {
+                    participation_id: None,
+                    event_id,
+                    person_id: guest.person_id.unwrap(),
+                    rsvp_status: RsvpStatus::Invited,
+                    responded_at: None,
+                    version: 1,
+                };
+                participations.push(client.create_participation(participation).await.unwrap().participation_id);
+            }
+        }
+
+        for (token, affiliations) in [(&owner_token, 2), (&member_token, 2), (&stranger_token, 0)] {
+            let req = test::TestRequest::get().insert_header(bearer(token)).uri("/affiliations").to_request();
+            let page: Page<Affiliation> = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(page.items.len(), affiliations);
+        }
+
+        // Participants see their own answers, members of a planner those to its events.
+        for (token, expected) in [(&owner_token, &participations[..2]), (&member_token, &participations[..1]), (&stranger_token, &participations[1..])] {
+            let req = test::TestRequest::get().insert_header(bearer(token)).uri("/participations").to_request();
+            let page: Page<Participation> = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(page.items.iter().map(|participation| participation.participation_id).collect::<Vec<_>>(), expected);
+        }
+
+        // Reads by id follow the same scope, and unknown ids are as forbidden as those of others.
+        let participations = participations.into_iter().map(Option::unwrap).collect::<Vec<i32>>();
+        let missing = i32::MAX;
+        for (uri, token, status) in [
+            (format!("/events/{}", events[0]), &owner_token, StatusCode::OK),
+            (format!("/events/{}", events[0]), &member_token, StatusCode::FORBIDDEN),
+            (format!("/events/{}", events[1]), &stranger_token, StatusCode::OK),
+            (format!("/events/{}", events[1]), &owner_token, StatusCode::FORBIDDEN),
+            (format!("/events/{}", missing), &owner_token, StatusCode::FORBIDDEN),
+            (format!("/plans/{}", plans[0]), &owner_token, StatusCode::OK),
+            (format!("/plans/{}", plans[0]), &stranger_token, StatusCode::FORBIDDEN),
+            (format!("/plans/{}", missing), &owner_token, StatusCode::FORBIDDEN),
+            (format!("/planner/{}", owner.planner_id.unwrap()), &owner_token, StatusCode::OK),
+            (format!("/planner/{}", owner.planner_id.unwrap()), &stranger_token, StatusCode::FORBIDDEN),
+            (format!("/planner/{}", planner.planner_id), &owner_token, StatusCode::OK),
+            (format!("/planner/{}", planner.planner_id), &member_token, StatusCode::FORBIDDEN),
+            (format!("/planner/{}", missing), &owner_token, StatusCode::FORBIDDEN),
+            (format!("/affiliations/{}", affiliations[1]), &member_token, StatusCode::OK),
+            (format!("/affiliations/{}", affiliations[1]), &owner_token, StatusCode::OK),
+            (format!("/affiliations/{}", affiliations[1]), &stranger_token, StatusCode::FORBIDDEN),
+            (format!("/affiliations/{}", missing), &owner_token, StatusCode::FORBIDDEN),
+            (format!("/participations/{}", participations[0]), &member_token, StatusCode::OK),
+            (format!("/participations/{}", participations[0]), &owner_token, StatusCode::OK),
+            (format!("/participations/{}", participations[0]), &stranger_token, StatusCode::FORBIDDEN),
+            (format!("/participations/{}", participations[1]), &member_token, StatusCode::FORBIDDEN),
+            (format!("/participations/{}", participations[2]), &owner_token, StatusCode::FORBIDDEN),
+            (format!("/participations/{}", missing), &owner_token, StatusCode::FORBIDDEN),
+        ] {
+            let req = test::TestRequest::get().insert_header(bearer(token)).uri(&uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), status, "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_list_scoping() {
+        check_list_scoping(repository()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_list_scoping_in_postgres() {
+        let database = TestDatabase::create().await;
+        check_list_scoping(database.repository()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_list_filters() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                    .route(web::get().to(get_events))
+                )
+                .service(web::resource("/users")
+                    .route(web::get().to(get_persons))
+                )
+        ).await;
+
+        let tag = auth::new_session_id();
+        let mut event_ids = vec![];
+        for (name, location, day) in [("Gala", "Paris", 1), ("Small gala", "Lyon", 2), ("Dinner", "Paris", 3), ("gala night", "Paris", 4)] {
+            let event = serde_json::json!({
+                "event_name": format!("{} {}", name, tag),
+                "event_location": location,
+                "event_description": "",
+                "starts_at": Utc.with_ymd_and_hms(2022, 6, day, 19, 0, 0).unwrap(),
+                "ends_at": Utc.with_ymd_and_hms(2022, 6, day, 21, 0, 0).unwrap(),
+            });
+            let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(event).to_request();
+            let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+            event_ids.push(event.event_id);
+        }
+
+        // Matching is case insensitive, and the latest event comes first.
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri("/events?name~=gala&location=Paris&sort=-starts_at")
+            .to_request();
+        let page: Page<EventResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.iter().map(|event| event.event_id).collect::<Vec<_>>(), vec![event_ids[3], event_ids[0]]);
+
+        // Sorted lists page through the cursor just like unsorted ones.
+        let mut seen = vec![];
+        let mut uri = "/events?starts_at%3E=2022-06-02&sort=-starts_at&limit=2".to_string();
+        loop {
+            let req = test::TestRequest::get().insert_header(bearer(&token)).uri(&uri).to_request();
+            let page: Page<EventResponse> = test::call_and_read_body_json(&app, req).await;
+            seen.extend(page.items.iter().map(|event| event.event_id));
+            match page.next_cursor {
+                Some(cursor) => uri = format!("/events?starts_at%3E=2022-06-02&sort=-starts_at&limit=2&after={}", cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec![event_ids[3], event_ids[2], event_ids[1]]);
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri("/events?name;drop%20table%20event=x")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        for (uri, parameter) in [("/events?planner_id=1", "planner_id"), ("/events?name%3E=a", "name>"), ("/users?sort=person_id", "sort")] {
+            let req = test::TestRequest::get().insert_header(bearer(&token)).uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["parameter"], parameter);
+        }
+
+        let client = repository.connect().await.unwrap();
+        let renamed = UpdatePerson { person_name: Patch::Value(format!("Filtered {}", tag)) };
+        let renamed = client.modify_person(person.person_id.unwrap(), &renamed).await.unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users?name~=FILTERED%20{}", tag))
+            .to_request();
+        let page: Page<PersonResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.items.iter().map(|person| person.person_id).collect::<Vec<_>>(), vec![renamed.person_id.unwrap()]);
+
+        for event_id in event_ids {
+            client.delete_event(event_id).await.unwrap();
+        }
+        client.delete_person(renamed.person_id.unwrap()).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_search() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+        let (stranger, stranger_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/search")
+                    .route(web::get().to(search))
+                )
+        ).await;
+
+        // A word of its own, so that other tests' rows cannot match.
+        let tag = auth::new_session_id().chars().filter(char::is_ascii_lowercase).collect::<String>();
+
+        let mut event_ids = vec![];
+        for (name, description) in [("Grands concerts", "Le festival"), ("Dîner", "Tom & Jerry au concert")] {
+            let event = serde_json::json!({
+                "event_name": format!("{} {}", name, tag),
+                "event_location": "Paris",
+                "event_description": description,
+                "starts_at": "2022-06-18T19:00:00Z",
+                "ends_at": "2022-06-18T21:00:00Z",
+            });
+            let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(event).to_request();
+            let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+            event_ids.push(event.event_id);
+        }
+
+        let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
+            .uri("/organizations")
+            .set_json(serde_json::json!({ "organization_name": format!("Concerts {}", tag) }))
+            .to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+
+        let client = repository.connect().await.unwrap();
+        let renamed = UpdatePerson { person_name: Patch::Value(format!("Camille {}", tag)) };
+        let person = client.modify_person(person.person_id.unwrap(), &renamed).await.unwrap();
+
+        // `concert` matches `concerts` through the French stemmer, and names rank above descriptions.
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/search?q=concert%20{}", tag))
+            .to_request();
+        let results: SearchResults = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(results.events.iter().map(|hit| hit.event_id).collect::<Vec<_>>(), event_ids);
+        assert!(results.events[0].rank > results.events[1].rank);
+        assert!(results.events[0].snippet.contains("<mark>concerts</mark>"));
+        assert!(results.events[1].snippet.contains("Tom &amp; Jerry"));
+        assert_eq!(results.organizations.iter().map(|hit| hit.organization_id).collect::<Vec<_>>(), vec![organization.organization_id]);
+        assert!(results.persons.is_empty());
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .uri(&format!("/search?q={}&limit=1", tag))
+            .to_request();
+        let results: SearchResults = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(results.events.len(), 1);
+        assert_eq!(results.persons.iter().map(|hit| Some(hit.person_id)).collect::<Vec<_>>(), vec![person.person_id]);
+        assert_eq!(results.persons[0].snippet, format!("Camille <mark>{}</mark>", tag));
+
+        // Events are only found by the members of their planners.
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&stranger_token))
+            .uri(&format!("/search?q={}", tag))
+            .to_request();
+        let results: SearchResults = test::call_and_read_body_json(&app, req).await;
+        assert!(results.events.is_empty());
+        assert_eq!(results.organizations.len(), 1);
+
+        for uri in ["/search?q=%20", "/search?q=concert&limit=0", "/search"] {
+            let req = test::TestRequest::get().insert_header(bearer(&token)).uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+        }
+
+        for event_id in event_ids {
+            client.delete_event(event_id).await.unwrap();
+        }
+        client.delete_organization(organization.organization_id).await.unwrap();
+        client.delete_person(person.person_id.unwrap()).await.unwrap();
+        client.delete_person(stranger.person_id.unwrap()).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_composite_writes_are_atomic() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+
+        // Nothing written in a transaction survives its rollback.
+        let mut client = repository.connect().await.unwrap();
+        let transaction = client.transaction().await.unwrap();
+        let planner = transaction.create_planner().await.unwrap();
+        let orphan = Person { person_id: None, person_name: "GDVCB".to_string(), planner_id: Some(planner.planner_id), version: 1 };
+        transaction.create_person(orphan).await.unwrap();
+        transaction.rollback().await.unwrap();
+        assert!(matches!(client.get_planner(planner.planner_id).await, Err(MyError::NotFound)));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}")
+                    .route(web::delete().to(delete_organization))
+                )
+                .service(web::resource("/users/{person_id}")
+                    .route(web::delete().to(delete_person))
+                )
+        ).await;
+
+        let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
+            .uri("/organizations")
+            .set_json(serde_json::json!({ "organization_name": "festival_a" }))
+            .to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+        let members = client.get_organization_members(organization.organization_id).await.unwrap();
+        assert_eq!(members.iter().map(|member| Some(member.person_id)).collect::<Vec<_>>(), vec![person.person_id]);
+
+        // Deleting an organization or a person takes their planner along.
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/organizations/{}", organization.organization_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        assert!(matches!(client.get_planner(organization.planner_id.unwrap()).await, Err(MyError::NotFound)));
+
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users/{}", person.person_id.unwrap()))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        assert!(matches!(client.get_planner(person.planner_id.unwrap()).await, Err(MyError::NotFound)));
+    }
+
+    #[actix_web::test]
+    async fn test_problem_responses() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .app_data(web::JsonConfig::default().error_handler(extractor_error))
+                .app_data(web::PathConfig::default().error_handler(extractor_error))
+                .service(web::resource("/events/{event_id}")
+                    .route(web::get().to(get_event))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}")
+                    .route(web::delete().to(delete_organization))
+                )
+                .service(web::resource("/affiliations")
+                    .route(web::post().to(create_affiliation))
+                )
+                .service(web::resource("/planner/{planner_id}")
+                    .route(web::delete().to(delete_planner))
+                )
+                .service(web::resource("/rsvp/{token}")
+                    .route(web::get().to(get_rsvp_page))
+                )
+        ).await;
+
+        async fn problem(resp: actix_web::dev::ServiceResponse, status: StatusCode, code: &str) -> serde_json::Value {
+            assert_eq!(resp.status(), status);
+            assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/problem+json");
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["type"], "about:blank");
+            assert_eq!(body["status"], status.as_u16());
+            assert_eq!(body["code"], code);
+            body
+        }
+
+        let req = test::TestRequest::get().uri("/events/1").to_request();
+        problem(test::call_service(&app, req).await, StatusCode::UNAUTHORIZED, "unauthorized").await;
+
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri("/events/0").to_request();
+        problem(test::call_service(&app, req).await, StatusCode::FORBIDDEN, "forbidden").await;
+
+        let req = test::TestRequest::get().uri("/rsvp/unknown").to_request();
+        problem(test::call_service(&app, req).await, StatusCode::NOT_FOUND, "not_found").await;
+
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri("/events/soon").to_request();
+        problem(test::call_service(&app, req).await, StatusCode::BAD_REQUEST, "bad_request").await;
+
+        let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
+            .uri("/organizations")
+            .set_json(serde_json::json!({ "organization_id": "one" }))
+            .to_request();
+        problem(test::call_service(&app, req).await, StatusCode::BAD_REQUEST, "bad_request").await;
+
+        let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
+            .uri("/organizations")
+            .set_json(serde_json::json!({ "organization_name": "festival_a" }))
+            .to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+
+        // The creator is already affiliated; the unique violation is reported without its details.
+        let affiliation = serde_json::json!({
+            "person_id": person.person_id,
+            "organization_id": organization.organization_id,
+        });
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/affiliations").set_json(&affiliation).to_request();
+        let body = problem(test::call_service(&app, req).await, StatusCode::CONFLICT, "already_exists").await;
+        assert!(!body.to_string().contains("affiliation_person_id"));
+        assert!(!body.to_string().contains("duplicate key"));
+
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/planner/{}", person.planner_id.unwrap()))
+            .to_request();
+        problem(test::call_service(&app, req).await, StatusCode::CONFLICT, "conflict").await;
+
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/organizations/{}", organization.organization_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let client = repository.connect().await.unwrap();
+        client.delete_person(person.person_id.unwrap()).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_merge_patch() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::patch().to(modify_event))
+                    .route(web::delete().to(delete_event))
+                )
+                .service(web::resource("/users/{person_id}")
+                    .route(web::patch().to(modify_person))
+                )
+        ).await;
+
+        let event = serde_json::json!({
+            "event_name": "anniv GDVCB",
+            "event_location": "Paris",
+            "event_description": "Petit anniv",
+            "starts_at": "2022-06-18T19:00:00Z",
+            "ends_at": "2022-06-18T23:00:00Z",
+            "recurrence_rule": "FREQ=YEARLY",
+        });
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+        let uri = format!("/events/{}", event.event_id);
+
+        // Absent fields are kept and null clears the rule.
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+            .uri(&uri)
+            .set_payload(r#"{ "event_location": "Lyon", "recurrence_rule": null }"#)
+            .to_request();
+        let patched: EventResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(patched.event_name, "anniv GDVCB");
+        assert_eq!(patched.event_description, "Petit anniv");
+        assert_eq!(patched.event_location, "Lyon");
+        assert_eq!(patched.recurrence_rule, None);
+        assert_eq!(patched.starts_at, event.starts_at);
+
+        let req = test::TestRequest::patch().insert_header(bearer(&token)).uri(&uri).set_json(serde_json::json!({})).to_request();
+        let unchanged: EventResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(unchanged.event_location, "Lyon");
+
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .uri(&uri)
+            .set_json(serde_json::json!({ "event_name": null, "starts_at": null }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["errors"]["event_name"], serde_json::json!(["must not be null"]));
+        assert_eq!(body["errors"]["starts_at"], serde_json::json!(["must not be null"]));
+
+        // The merged event is checked as a whole: moving the end alone must keep it after the start.
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .uri(&uri)
+            .set_json(serde_json::json!({ "ends_at": "2022-06-18T18:00:00Z" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users/{}", person.person_id.unwrap()))
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let unchanged: PersonResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(unchanged.person_name, person.person_name);
+        assert_eq!(unchanged.planner_id, person.planner_id);
+
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri(&uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_etags() {
+        let repository = repository();
+        let signer = signer();
+        let (_person, token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::get().to(get_event))
+                    .route(web::patch().to(modify_event))
+                    .route(web::delete().to(delete_event))
+                )
+        ).await;
 
-    #[actix_web::test]
-    async fn test_create_delete_person() {
-        let person = Person {
-            person_id: None,
-            person_name : "GDVCB".to_string(),
-            planner_id: None
-        };
-        
-        dotenv().ok();
+        let event = serde_json::json!({
+            "event_name": "anniv GDVCB",
+            "event_location": "Paris",
+            "event_description": "Petit anniv",
+            "starts_at": "2022-06-18T19:00:00Z",
+            "ends_at": "2022-06-18T23:00:00Z",
+        });
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"1\"");
+        let event: EventResponse = test::read_body_json(resp).await;
+        assert_eq!(event.version, 1);
+        let uri = format!("/events/{}", event.event_id);
+
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
 
-        let config_ = Config::builder()
-            .add_source(::config::Environment::default())
-            .build()
-            .unwrap();
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .uri(&uri)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
 
-        let config: ExampleConfig = config_.try_deserialize().unwrap();
+        // Each write bumps the version, so the tag read before it no longer matches.
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .insert_header((header::IF_MATCH, etag.clone()))
+            .uri(&uri)
+            .set_json(serde_json::json!({ "event_location": "Lyon" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"2\"");
+
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .insert_header((header::IF_MATCH, etag.clone()))
+            .uri(&uri)
+            .set_json(serde_json::json!({ "event_location": "Nice" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "precondition_failed");
+
+        let req = test::TestRequest::get()
+            .insert_header(bearer(&token))
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .uri(&uri)
+            .to_request();
+        let current: EventResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!((current.event_location.as_str(), current.version), ("Lyon", 2));
+
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .insert_header((header::IF_MATCH, etag))
+            .uri(&uri)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PRECONDITION_FAILED);
+
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .insert_header((header::IF_MATCH, "\"2\""))
+            .uri(&uri)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_validation_errors() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
 
-        let pool = config.pg.create_pool(None, NoTls).unwrap();
-        
         let app = test::init_service(
             App::new()
-                .app_data(web::Data::new(pool.clone()))
-                .service(web::resource("/users")
-                    .route(web::post().to(create_person))
-                    .route(web::delete().to(delete_person))
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}")
+                    .route(web::patch().to(modify_event))
+                    .route(web::delete().to(delete_event))
+                )
+                .service(web::resource("/users/{person_id}")
+                    .route(web::patch().to(modify_person))
+                )
+                .service(web::resource("/auth/register")
+                    .route(web::post().to(register))
                 )
         ).await;
 
-        // Create the request that inserts a new person in the table
-        let req = test::TestRequest::post()
-            .uri("/users")
-            .set_json(person)
+        let event = serde_json::json!({
+            "event_name": "   ",
+            "event_location": "Paris",
+            "event_description": "x".repeat(20_000),
+            "starts_at": "2022-06-18T19:00:00Z",
+            "ends_at": "2022-06-19T02:00:00Z",
+        });
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["errors"]["event_name"], serde_json::json!(["must not be empty"]));
+        assert_eq!(body["errors"]["event_description"], serde_json::json!(["must be at most 10000 characters long"]));
+
+        // Names are stored trimmed.
+        let event = serde_json::json!({
+            "event_name": "  anniv GDVCB  ",
+            "event_location": "Paris",
+            "event_description": "Petit anniv",
+            "starts_at": "2022-06-18T19:00:00Z",
+            "ends_at": "2022-06-19T02:00:00Z",
+        });
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(&event).to_request();
+        let created: EventResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created.event_name, "anniv GDVCB");
+
+        // Unknown and negative ids are refused, not panicked on.
+        let req = test::TestRequest::patch().insert_header(bearer(&token)).uri("/events/-4").set_json(&event).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().insert_header(bearer(&token)).uri("/events/0").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::patch()
+            .insert_header(bearer(&token))
+            .uri(&format!("/users/{}", person.person_id.unwrap()))
+            .set_json(serde_json::json!({ "person_name": "" }))
             .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["errors"]["person_name"], serde_json::json!(["must not be empty"]));
 
+        let req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(serde_json::json!({ "person_name": "GDVCB", "email": "nobody", "password": "short" }))
+            .to_request();
         let resp = test::call_service(&app, req).await;
-        println!("{:?}", resp.response().body());
-        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["errors"]["email"].is_array());
+        assert!(body["errors"]["password"].is_array());
 
-        // let body: Person  = test::read_body_json(resp).await;
-        
-        // // Create the request that delete the previsouly inserted person in the database
-        // let req = test::TestRequest::delete()
-        //     .uri("/users")
-        //     .set_json(body)
-        //     .to_request();
+        let req = test::TestRequest::delete()
+            .insert_header(bearer(&token))
+            .uri(&format!("/events/{}", created.event_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_calendar_export() {
+        let repository = repository();
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+        let (member, member_token) = sign_in(&repository, &signer).await;
+        let (_, stranger_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/plans")
+                    .route(web::post().to(create_plan))
+                )
+                .service(web::resource("/affiliations")
+                    .route(web::post().to(create_affiliation))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/users/{person_id}/calendar.ics")
+                    .route(web::get().to(get_person_calendar))
+                )
+                .service(web::resource("/organizations/{organization_id}/calendar.ics")
+                    .route(web::get().to(get_organization_calendar))
+                )
+        ).await;
+
+        let mut event_ids = Vec::new();
+        for (event_name, recurrence_rule) in [("anniv GDVCB", None), ("standup", Some("FREQ=WEEKLY;BYDAY=MO".to_string()))] {
+            let event = CreateEvent {
+                event_name: event_name.to_string(),
+                event_description: "".to_string(),
+                event_location: "Paris".to_string(),
+                starts_at: Utc.with_ymd_and_hms(2022, 6, 6, 7, 30, 0).unwrap(),
+                ends_at: Utc.with_ymd_and_hms(2022, 6, 6, 7, 45, 0).unwrap(),
+                time_zone: "Europe/Paris".to_string(),
+                all_day: false,
+                recurrence_rule,
+                recurrence_dates: vec![],
+                exception_dates: vec![],
+            };
+            let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/events").set_json(event).to_request();
+            let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+            event_ids.push(event.event_id);
+        }
+
+        let organization = CreateOrganization {
+            organization_name: "festival_a".to_string(),
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/organizations").set_json(organization).to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+        let plan = Plan { plan_id: None, event_id: event_ids[1], planner_id: organization.planner_id.unwrap() };
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/plans").set_json(plan).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let affiliation = Affiliation {
+            affiliation_id: None,
+            person_id: member.person_id.unwrap(),
+            organization_id: organization.organization_id,
+            role: OrganizationRole::Member,
+            version: 1,
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri("/affiliations").set_json(affiliation).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let person_uri = format!("/users/{}/calendar.ics", person.person_id.unwrap());
+        let organization_uri = format!("/organizations/{}/calendar.ics", organization.organization_id);
+        for (uri, token, events) in [(&person_uri, &token, 2), (&organization_uri, &token, 1), (&organization_uri, &member_token, 1)] {
+            let req = test::TestRequest::get().insert_header(bearer(token)).uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "text/calendar; charset=utf-8");
+            let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+            assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+            assert_eq!(body.matches("BEGIN:VEVENT\r\n").count(), events);
+        }
+
+        // Only the person sees their own calendar, and only members that of an organization.
+        for (uri, token) in [(&person_uri, &member_token), (&person_uri, &stranger_token), (&organization_uri, &stranger_token)] {
+            let req = test::TestRequest::get().insert_header(bearer(token)).uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        }
+    }
+
+    /// Imports calendars on both stores: the SQL unique index is what keeps UIDs apart per planner.
+    async fn check_calendar_import(repository: web::Data<dyn Repository>) {
+        let signer = signer();
+        let (person, token) = sign_in(&repository, &signer).await;
+        let (other, other_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/planner/{planner_id}/import")
+                    .route(web::post().to(import_calendar))
+                )
+                .service(web::resource("/users/{person_id}/calendar.ics")
+                    .route(web::get().to(get_person_calendar))
+                )
+        ).await;
+
+        let ics = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:concert@example.com\r\n\
+DTSTART:20220714T200000Z\r\n\
+DTEND:20220714T220000Z\r\n\
+SUMMARY:concert\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:standup@example.com\r\n\
+DTSTART:20220606T073000Z\r\n\
+DTEND:20220606T074500Z\r\n\
+RRULE:FREQ=WEEKLY;COUNT=4\r\n\
+SUMMARY:standup\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:standup@example.com\r\n\
+RECURRENCE-ID:20220613T073000Z\r\n\
+DTSTART:20220613T080000Z\r\n\
+DTEND:20220613T081500Z\r\n\
+SUMMARY:late standup\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
 
-        // let resp = test::call_service(&app, req).await;
-        // assert_eq!(resp.status(), StatusCode::OK);
+        let import = |token: &str, planner_id: Option<i32>, body: String| {
+            test::TestRequest::post()
+                .insert_header(bearer(token))
+                .uri(&format!("/planner/{}/import", planner_id.unwrap()))
+                .set_payload(body)
+                .to_request()
+        };
+        let counts = |report: &serde_json::Value| {
+            ["created", "skipped", "failed"].map(|key| report[key].as_array().unwrap().len())
+        };
+
+        // Planners of others are forbidden alike, whether they exist or not.
+        for planner_id in [other.planner_id, Some(other.planner_id.unwrap() + 1000)] {
+            let resp = test::call_service(&app, import(&token, planner_id, ics.to_string())).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+
+        let report: serde_json::Value = test::call_and_read_body_json(&app, import(&token, person.planner_id, ics.to_string())).await;
+        assert_eq!(counts(&report), [3, 0, 0]);
 
+        // Importing the same file again neither creates nor updates anything.
+        let report: serde_json::Value = test::call_and_read_body_json(&app, import(&token, person.planner_id, ics.to_string())).await;
+        assert_eq!(counts(&report), [0, 3, 0]);
+
+        // The same UIDs are someone else's events in another planner.
+        let report: serde_json::Value = test::call_and_read_body_json(&app, import(&other_token, other.planner_id, ics.to_string())).await;
+        assert_eq!(counts(&report), [3, 0, 0]);
+
+        // An export comes back as the events it was made from...
+        let req = test::TestRequest::get().insert_header(bearer(&token)).uri(&format!("/users/{}/calendar.ics", person.person_id.unwrap())).to_request();
+        let exported = String::from_utf8(test::read_body(test::call_service(&app, req).await).await.to_vec()).unwrap();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, import(&token, person.planner_id, exported.clone())).await;
+        assert_eq!(counts(&report), [0, 3, 0]);
+
+        // ...but as new ones for whoever does not take part in them.
+        let report: serde_json::Value = test::call_and_read_body_json(&app, import(&other_token, other.planner_id, exported)).await;
+        assert_eq!(counts(&report), [3, 0, 0]);
     }
 
     #[actix_web::test]
-    async fn test_create_delete_event() {
-        let event = Event {
-            event_id: None,
-            event_name : "anniv GDVCB".to_string(),
+    async fn test_calendar_import() {
+        check_calendar_import(repository()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_calendar_import_in_postgres() {
+        let database = TestDatabase::create().await;
+        check_calendar_import(database.repository()).await;
+    }
+
+    /// Runs through invitations on both stores: the SQL of `use_invitation` is what enforces the limits.
+    async fn check_invitations(repository: web::Data<dyn Repository>) {
+        let signer = signer();
+        let (host, host_token) = sign_in(&repository, &signer).await;
+        let (guest, guest_token) = sign_in(&repository, &signer).await;
+        let (other, other_token) = sign_in(&repository, &signer).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/events")
+                    .route(web::post().to(create_event))
+                )
+                .service(web::resource("/events/{event_id}/participants")
+                    .route(web::get().to(get_event_participants))
+                )
+                .service(web::resource("/events/{event_id}/invitations")
+                    .route(web::get().to(get_event_invitations))
+                    .route(web::post().to(create_event_invitation))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}/members")
+                    .route(web::get().to(get_organization_members))
+                )
+                .service(web::resource("/organizations/{organization_id}/invitations")
+                    .route(web::get().to(get_organization_invitations))
+                    .route(web::post().to(create_organization_invitation))
+                )
+                .service(web::resource("/invitations/{invitation_id}")
+                    .route(web::delete().to(revoke_invitation))
+                )
+                .service(web::resource("/invitations/{token}/accept")
+                    .route(web::post().to(accept_invitation))
+                )
+        ).await;
+
+        let event = CreateEvent {
+            event_name: "anniv GDVCB".to_string(),
             event_description: "Petit anniv".to_string(),
             event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
         };
-        
-        dotenv().ok();
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri("/events").set_json(event).to_request();
+        let event: EventResponse = test::call_and_read_body_json(&app, req).await;
+        let invitations_uri = format!("/events/{}/invitations", event.event_id);
 
-        let config_ = Config::builder()
-            .add_source(::config::Environment::default())
-            .build()
-            .unwrap();
+        // Only members of the event may invite to it.
+        let request = serde_json::json!({ "max_uses": 2, "rsvp_status": "accepted" });
+        let req = test::TestRequest::post().insert_header(bearer(&guest_token)).uri(&invitations_uri).set_json(&request).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
 
-        let config: ExampleConfig = config_.try_deserialize().unwrap();
+        for invalid in [
+            serde_json::json!({ "max_uses": 0 }),
+            serde_json::json!({ "expires_at": "2022-06-18T19:00:00Z" }),
+            serde_json::json!({ "role": "member" }),
+        ] {
+            let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&invitations_uri).set_json(&invalid).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&invitations_uri).set_json(&request).to_request();
+        let invitation: Invitation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(invitation.rsvp_status, Some(RsvpStatus::Accepted));
+        assert_eq!(invitation.created_by, host.person_id.unwrap());
+        assert!(invitation.expires_at > Utc::now() + chrono::Duration::days(13));
+
+        let req = test::TestRequest::post().insert_header(bearer(&guest_token)).uri(&format!("/invitations/{}/accept", invitation.token)).to_request();
+        let participation: Participation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participation.person_id, guest.person_id.unwrap());
+        assert_eq!(participation.rsvp_status, RsvpStatus::Accepted);
+
+        // Accepting twice fails without spending the last use.
+        let req = test::TestRequest::post().insert_header(bearer(&guest_token)).uri(&format!("/invitations/{}/accept", invitation.token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&invitations_uri).to_request();
+        let invitations: Vec<Invitation> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(invitations.len(), 1);
+        assert_eq!(invitations[0].uses, 1);
+
+        let req = test::TestRequest::post().insert_header(bearer(&other_token)).uri(&format!("/invitations/{}/accept", invitation.token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&format!("/events/{}/participants", event.event_id)).to_request();
+        let participants: Vec<Participant> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participants.len(), 2);
+
+        // Used up, the invitation is no longer listed nor accepted.
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&invitations_uri).to_request();
+        let invitations: Vec<Invitation> = test::call_and_read_body_json(&app, req).await;
+        assert!(invitations.is_empty());
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&format!("/invitations/{}/accept", invitation.token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let organization = CreateOrganization {
+            organization_name: "festival_a".to_string(),
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri("/organizations").set_json(organization).to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+        let invitations_uri = format!("/organizations/{}/invitations", organization.organization_id);
+
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&invitations_uri).set_json(serde_json::json!({ "rsvp_status": "accepted" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&invitations_uri).set_json(serde_json::json!({ "role": "admin" })).to_request();
+        let invitation: Invitation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(invitation.role, Some(OrganizationRole::Admin));
+        assert_eq!(invitation.max_uses, 1);
+
+        let req = test::TestRequest::post().insert_header(bearer(&guest_token)).uri(&format!("/invitations/{}/accept", invitation.token)).to_request();
+        let affiliation: Affiliation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(affiliation.role, OrganizationRole::Admin);
+
+        // Admins may invite, but only the owner can invite owners or revoke their invitations.
+        let req = test::TestRequest::post().insert_header(bearer(&guest_token)).uri(&invitations_uri).set_json(serde_json::json!({ "role": "owner" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&invitations_uri).set_json(serde_json::json!({ "role": "owner" })).to_request();
+        let invitation: Invitation = test::call_and_read_body_json(&app, req).await;
+        let invitation_uri = format!("/invitations/{}", invitation.invitation_id.unwrap());
+
+        let req = test::TestRequest::delete().insert_header(bearer(&guest_token)).uri(&invitation_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().insert_header(bearer(&host_token)).uri(&invitation_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::delete().insert_header(bearer(&host_token)).uri(&invitation_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::post().insert_header(bearer(&other_token)).uri(&format!("/invitations/{}/accept", invitation.token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&format!("/organizations/{}/members", organization.organization_id)).to_request();
+        let members: Vec<Member> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(members.len(), 2);
+        assert!(members.iter().all(|member| member.person_id != other.person_id.unwrap()));
+    }
+
+    #[actix_web::test]
+    async fn test_invitations() {
+        check_invitations(repository()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_invitations_in_postgres() {
+        let database = TestDatabase::create().await;
+        check_invitations(database.repository()).await;
+    }
+
+    /// Guests answer through an invitation without an account and become participants with their token.
+    async fn check_guest_rsvp(repository: web::Data<dyn Repository>) {
+        let signer = signer();
+        let (_, host_token) = sign_in(&repository, &signer).await;
+        let (_, stranger_token) = sign_in(&repository, &signer).await;
 
-        let pool = config.pg.create_pool(None, NoTls).unwrap();
-        
         let app = test::init_service(
             App::new()
-                .app_data(web::Data::new(pool.clone()))
+                .app_data(repository.clone())
+                .app_data(signer.clone())
+                .service(web::resource("/auth/register")
+                    .route(web::post().to(register))
+                )
                 .service(web::resource("/events")
                     .route(web::post().to(create_event))
-                    .route(web::delete().to(delete_event))
+                )
+                .service(web::resource("/events/{event_id}/participants")
+                    .route(web::get().to(get_event_participants))
+                )
+                .service(web::resource("/events/{event_id}/invitations")
+                    .route(web::post().to(create_event_invitation))
+                )
+                .service(web::resource("/events/{event_id}/guests")
+                    .route(web::get().to(get_event_guests))
+                )
+                .service(web::resource("/organizations")
+                    .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}/invitations")
+                    .route(web::post().to(create_organization_invitation))
+                )
+                .service(web::resource("/rsvp/{token}")
+                    .route(web::get().to(get_rsvp_page))
+                    .route(web::post().to(create_guest_rsvp))
+                )
+                .service(web::resource("/rsvp/guests/{guest_token}")
+                    .route(web::get().to(get_guest_rsvp))
+                    .route(web::put().to(modify_guest_rsvp))
+                )
+                .service(web::resource("/rsvp/guests/{guest_token}/merge")
+                    .route(web::post().to(merge_guest_rsvp))
                 )
         ).await;
 
-        // Create the request that inserts a new event in the table
-        let req = test::TestRequest::post()
-            .uri("/events")
-            .set_json(event)
-            .to_request();
+        let event = CreateEvent {
+            event_name: "anniv GDVCB".to_string(),
+            event_description: "Petit anniv".to_string(),
+            event_location: "Paris".to_string(),
+            starts_at: Utc.with_ymd_and_hms(2022, 6, 18, 19, 0, 0).unwrap(),
+            ends_at: Utc.with_ymd_and_hms(2022, 6, 19, 2, 0, 0).unwrap(),
+            time_zone: "Europe/Paris".to_string(),
+            all_day: false,
+            recurrence_rule: None,
+            recurrence_dates: vec![],
+            exception_dates: vec![],
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri("/events").set_json(event).to_request();
+        let event: EventResponse = test::call_and_read_body_json(&app, req).await;
 
-        let resp = test::call_service(&app, req).await;
-        assert_eq!(resp.status(), StatusCode::OK);
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&format!("/events/{}/invitations", event.event_id)).set_json(serde_json::json!({ "max_uses": 2 })).to_request();
+        let invitation: Invitation = test::call_and_read_body_json(&app, req).await;
+        let rsvp_uri = format!("/rsvp/{}", invitation.token);
 
-        let body: Event  = test::read_body_json(resp).await;
-        
-        // Create the request that delete the previsouly inserted person in the database
-        let req = test::TestRequest::delete()
-            .uri("/events")
-            .set_json(body)
-            .to_request();
+        // The page needs no session, only the token.
+        let req = test::TestRequest::get().uri(&rsvp_uri).to_request();
+        let page: RsvpPage = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page.event.event_name, "anniv GDVCB");
+        assert_eq!(page.expires_at, invitation.expires_at);
+        let req = test::TestRequest::get().uri("/rsvp/unknown").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
 
-        let resp = test::call_service(&app, req).await;
-        assert_eq!(resp.status(), StatusCode::OK);
+        for invalid in [
+            serde_json::json!({ "guest_name": " ", "email": "camille@example.org", "rsvp_status": "accepted" }),
+            serde_json::json!({ "guest_name": "Camille", "email": "camille", "rsvp_status": "accepted" }),
+            serde_json::json!({ "guest_name": "Camille", "email": "camille@example.org", "rsvp_status": "invited" }),
+            serde_json::json!({ "guest_name": "Camille", "email": "camille@example.org", "rsvp_status": "accepted", "plus_ones": -1 }),
+        ] {
+            let req = test::TestRequest::post().uri(&rsvp_uri).set_json(&invalid).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let rsvp = serde_json::json!({ "guest_name": "Camille", "email": "camille@example.org", "rsvp_status": "accepted", "plus_ones": 2 });
+        let req = test::TestRequest::post().uri(&rsvp_uri).set_json(&rsvp).to_request();
+        let guest: GuestResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!((guest.rsvp_status, guest.plus_ones, guest.person_id), (RsvpStatus::Accepted, 2, None));
+        let guest_token = guest.guest_token.unwrap();
+        let guest_uri = format!("/rsvp/guests/{}", guest_token);
+
+        // The same email answers once per event, however it is written, and uses nothing.
+        let again = serde_json::json!({ "guest_name": "Camille", "email": "Camille@Example.org", "rsvp_status": "declined" });
+        let req = test::TestRequest::post().uri(&rsvp_uri).set_json(&again).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+        let decline = serde_json::json!({ "guest_name": "Dominique", "email": "dominique@example.org", "rsvp_status": "declined" });
+        let req = test::TestRequest::post().uri(&rsvp_uri).set_json(&decline).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri(&rsvp_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let change = serde_json::json!({ "guest_name": "Camille", "email": "camille@example.org", "rsvp_status": "tentative", "plus_ones": 1 });
+        let req = test::TestRequest::put().uri(&guest_uri).set_json(&change).to_request();
+        let guest: GuestResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!((guest.rsvp_status, guest.plus_ones), (RsvpStatus::Tentative, 1));
+        let req = test::TestRequest::get().uri(&guest_uri).to_request();
+        let guest: GuestResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(guest.rsvp_status, RsvpStatus::Tentative);
+
+        // Hosts see the guests, but not the tokens that let them answer.
+        let guests_uri = format!("/events/{}/guests", event.event_id);
+        let req = test::TestRequest::get().insert_header(bearer(&stranger_token)).uri(&guests_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&guests_uri).to_request();
+        let guests: Vec<GuestResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(guests.iter().map(|guest| guest.guest_name.as_str()).collect::<Vec<&str>>(), vec!["Camille", "Dominique"]);
+        assert!(guests.iter().all(|guest| guest.guest_token.is_none()));
+
+        // Organization invitations have no RSVP page.
+        let organization = CreateOrganization {
+            organization_name: "festival_a".to_string(),
+        };
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri("/organizations").set_json(organization).to_request();
+        let organization: OrganizationResponse = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::post().insert_header(bearer(&host_token)).uri(&format!("/organizations/{}/invitations", organization.organization_id)).set_json(serde_json::json!({})).to_request();
+        let invitation: Invitation = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::get().uri(&format!("/rsvp/{}", invitation.token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::post().uri(&format!("/rsvp/{}", invitation.token)).set_json(&rsvp).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        // The email of a guest was never verified: signing up with it takes over nothing.
+        let registration = serde_json::json!({ "person_name": "Camille", "email": "CAMILLE@example.org", "password": "correct horse" });
+        let req = test::TestRequest::post().uri("/auth/register").set_json(&registration).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let participants_uri = format!("/events/{}/participants", event.event_id);
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&participants_uri).to_request();
+        let participants: Vec<Participant> = test::call_and_read_body_json(&app, req).await;
+        assert!(participants.is_empty());
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&guests_uri).to_request();
+        let guests: Vec<GuestResponse> = test::call_and_read_body_json(&app, req).await;
+        assert!(guests.iter().all(|guest| guest.person_id.is_none()));
+        let req = test::TestRequest::get().uri(&guest_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        // Only the guest token turns the answer into a participation, and a session is needed to say whose.
+        let merge_uri = format!("{}/merge", guest_uri);
+        let req = test::TestRequest::post().uri(&merge_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+        let req = test::TestRequest::post().insert_header(bearer(&stranger_token)).uri(&format!("/rsvp/guests/{}x/merge", guest_token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let (person, token) = sign_in(&repository, &signer).await;
+        let req = test::TestRequest::post().insert_header(bearer(&token)).uri(&merge_uri).to_request();
+        let participation: Participation = test::call_and_read_body_json(&app, req).await;
+        assert_eq!((participation.person_id, participation.rsvp_status), (person.person_id.unwrap(), RsvpStatus::Tentative));
+
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&participants_uri).to_request();
+        let participants: Vec<Participant> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(participants.len(), 1);
+        assert_eq!(participants[0].person_id, person.person_id.unwrap());
+
+        let req = test::TestRequest::get().insert_header(bearer(&host_token)).uri(&guests_uri).to_request();
+        let guests: Vec<GuestResponse> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(guests[0].person_id, person.person_id);
+        assert_eq!(guests[0].plus_ones, 1);
+        assert_eq!(guests[1].person_id, None);
+
+        // Merged, the token neither changes the answer nor merges again.
+        let req = test::TestRequest::put().uri(&guest_uri).set_json(&change).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::post().insert_header(bearer(&stranger_token)).uri(&merge_uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_guest_rsvp() {
+        check_guest_rsvp(repository()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_guest_rsvp_in_postgres() {
+        let database = TestDatabase::create().await;
+        check_guest_rsvp(database.repository()).await;
     }
 
     #[actix_web::test]
     async fn test_create_delete_organization() {
-        let organization  = Organization {
-            organization_id: None,
-            organization_name : "festival_a".to_string(),
-            planner_id: None,
+        let organization  = CreateOrganization {
+            organization_name: "festival_a".to_string(),
         };
         
-        dotenv().ok();
-
-        let config_ = Config::builder()
-            .add_source(::config::Environment::default())
-            .build()
-            .unwrap();
-
-        let config: ExampleConfig = config_.try_deserialize().unwrap();
-
-        let pool = config.pg.create_pool(None, NoTls).unwrap();
+        let database = TestDatabase::create().await;
+        let repository = database.repository();
+        let signer = signer();
+        let (_, token) = sign_in(&repository, &signer).await;
         
         let app = test::init_service(
             App::new()
-                .app_data(web::Data::new(pool.clone()))
+                .app_data(repository.clone())
+                .app_data(signer.clone())
                 .service(web::resource("/organizations")
                     .route(web::post().to(create_organization))
+                )
+                .service(web::resource("/organizations/{organization_id}")
                     .route(web::delete().to(delete_organization))
                 )
         ).await;
 
         // Create the request that inserts a new organization in the table
         let req = test::TestRequest::post()
+            .insert_header(bearer(&token))
             .uri("/organizations")
             .set_json(organization)
             .to_request();
@@ -158,12 +2223,12 @@ mod tests {
         let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
 
-        let body: Organization  = test::read_body_json(resp).await;
+        let body: OrganizationResponse = test::read_body_json(resp).await;
         
         // Create the request that delete the previsouly inserted organization in the database
         let req = test::TestRequest::delete()
-            .uri("/organizations")
-            .set_json(body)
+            .insert_header(bearer(&token))
+            .uri(&format!("/organizations/{}", body.organization_id))
             .to_request();
 
         let resp = test::call_service(&app, req).await;
@@ -174,95 +2239,396 @@ mod tests {
 
 }
 
-use ::config::Config;
-use actix_web::{web, App, HttpServer};
+use std::sync::Arc;
+
+use actix_web::{guard, middleware::DefaultHeaders, web, App, HttpServer, Route};
 use actix_cors::Cors;
 use dotenv::dotenv;
 use db::handlers::{
     create_event, 
+    get_event,
     modify_event, 
     get_events, 
+    get_person_events,
+    get_person_calendar,
     delete_event,
+    get_event_overrides,
+    get_event_override,
+    create_event_override,
+    delete_event_override,
     create_plan,
+    get_plans,
+    get_plan,
     delete_plan,
-    create_person, 
+    get_persons,
+    get_person,
     modify_person,
     delete_person,
     create_affiliation,
+    get_affiliations,
+    get_affiliation,
+    modify_affiliation,
     delete_affiliation,
+    get_organization_members,
+    get_person_organizations,
+    create_participation,
+    get_participations,
+    get_participation,
+    modify_participation,
+    get_event_participants,
+    get_person_participations,
+    delete_participation,
     create_organization,
+    get_organizations,
+    get_organization,
+    modify_organization,
     delete_organization,
-    create_planner,
+    get_organization_calendar,
+    create_event_invitation,
+    get_event_invitations,
+    create_organization_invitation,
+    get_organization_invitations,
+    revoke_invitation,
+    accept_invitation,
+    get_rsvp_page,
+    create_guest_rsvp,
+    get_guest_rsvp,
+    modify_guest_rsvp,
+    merge_guest_rsvp,
+    get_event_guests,
+    get_planners,
+    get_planner,
     delete_planner,
+    import_calendar,
+    search,
+    register,
+    login,
+    logout,
+    legacy,
 };
 use tokio_postgres::NoTls;
 
-use crate::db::config::ExampleConfig;
+use crate::db::auth::TokenSigner;
+use crate::db::config::{CommandLine, ServerConfig, USAGE};
+use crate::db::errors::extractor_error;
+use crate::db::migrations;
+use crate::db::repository::{PgRepository, Repository};
+
+/// Marks the responses of the body-addressed routes kept in `handlers::legacy`.
+fn deprecated() -> DefaultHeaders {
+    DefaultHeaders::new().add(("Deprecation", "true"))
+}
+
+/// A route of `handlers::legacy`, only served while the `legacy_routes` feature is on.
+fn legacy_route(route: Route, enabled: bool) -> Route {
+    route.guard(guard::fn_guard(move |_| enabled)).wrap(deprecated())
+}
+
+fn exit_with(err: impl std::fmt::Display) -> ! {
+    eprintln!("{}", err);
+    std::process::exit(1)
+}
+
+fn to_io_error(err: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(err.to_string())
+}
+
+async fn migrate_command(pool: &deadpool_postgres::Pool, args: &[String]) -> std::io::Result<()> {
+    let mut client = pool.get().await.map_err(to_io_error)?;
+
+    match args.first().map(String::as_str) {
+        Some("up") => {
+            let ran = migrations::migrate_up(&mut client).await.map_err(to_io_error)?;
+            if ran.is_empty() {
+                println!("Database is up to date");
+            }
+            for migration in ran {
+                println!("Applied {}", migration.name);
+            }
+        }
+        Some("down") => {
+            let steps = match args.get(1) {
+                Some(steps) => steps.parse::<usize>().map_err(to_io_error)?,
+                None => 1,
+            };
+            for migration in migrations::migrate_down(&mut client, steps).await.map_err(to_io_error)? {
+                println!("Reverted {}", migration.name);
+            }
+        }
+        Some("status") => {
+            for (migration, applied) in migrations::status(&client).await.map_err(to_io_error)? {
+                println!("[{}] {}", if applied { "x" } else { " " }, migration.name);
+            }
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "usage: praecipio_server migrate <up|down [steps]|status>",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn config_check(config: &ServerConfig) -> std::io::Result<()> {
+    if config.tls.enabled {
+        config.tls.server_config().map_err(to_io_error)?;
+    }
+    config.pg.create_pool(None, NoTls).map_err(to_io_error)?;
+
+    println!("Configuration is valid");
+    println!("  listening on {}{}", config.server.bind.join(", "), if config.tls.enabled { " with TLS" } else { "" });
+    println!("  log level {}", config.log.level);
+    let features = [
+        ("legacy_routes", config.features.legacy_routes),
+        ("calendar_import", config.features.calendar_import),
+        ("search", config.features.search),
+        ("migrate_on_start", config.features.migrate_on_start),
+    ];
+    for (feature, enabled) in features {
+        println!("  {} {}", feature, if enabled { "on" } else { "off" });
+    }
+
+    Ok(())
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
 
-    let config_ = Config::builder()
-        .add_source(::config::Environment::default())
-        .build()
-        .unwrap();
+    let command_line = CommandLine::parse(std::env::args().skip(1)).unwrap_or_else(|err| exit_with(format!("{}\n{}", err, USAGE)));
+    let config = ServerConfig::load(&command_line).unwrap_or_else(|err| exit_with(err));
+
+    let command = command_line.command.iter().map(String::as_str).collect::<Vec<&str>>();
+    match command.as_slice() {
+        ["config", "check"] => return config_check(&config).or_else(|err| exit_with(err)),
+        [] | ["serve"] | ["migrate", ..] => {}
+        _ => exit_with(USAGE),
+    }
+
+    env_logger::Builder::new()
+        .filter_level(config.log.level_filter().unwrap_or(log::LevelFilter::Info))
+        .parse_default_env()
+        .init();
 
-    let config: ExampleConfig = config_.try_deserialize().unwrap();
+    let pool = config.pg.create_pool(None, NoTls).map_err(to_io_error)?;
 
-    let pool = config.pg.create_pool(None, NoTls).unwrap();
+    if let ["migrate", args @ ..] = command.as_slice() {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        return migrate_command(&pool, &args).await;
+    }
 
+    let tls = if config.tls.enabled {
+        Some(config.tls.server_config().map_err(to_io_error)?)
+    } else {
+        None
+    };
 
-    
-        // .allowed_origin("https://www.rust-lang.org")
-        // .allowed_methods(vec!["GET", "POST"])
-        // .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
-        // .allowed_header(header::CONTENT_TYPE)
-        // .max_age(3600);
+    let repository: web::Data<dyn Repository> = web::Data::from(Arc::new(PgRepository::new(pool.clone())) as Arc<dyn Repository>);
+    let signer = web::Data::new(TokenSigner::new(config.auth_secret.as_bytes()));
+    let page_settings = web::Data::new(config.pagination);
+    let features = config.features;
+    let cors_settings = web::Data::new(config.cors);
 
+    if features.migrate_on_start {
+        let mut client = pool.get().await.map_err(to_io_error)?;
+        for migration in migrations::migrate_up(&mut client).await.map_err(to_io_error)? {
+            println!("Applied migration {}", migration.name);
+        }
+    }
 
-    let server = HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
 
-        let cors = Cors::permissive();
+        // Authentication goes through the bearer header, never cookies, so credentials stay disallowed.
+        let mut cors = Cors::default()
+            .allow_any_method()
+            .allow_any_header()
+            .max_age(cors_settings.max_age);
+        if cors_settings.allows_any_origin() {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &cors_settings.allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
 
         App::new()
-            .app_data(web::Data::new(pool.clone()))
+            .app_data(repository.clone())
+            .app_data(signer.clone())
+            .app_data(page_settings.clone())
+            .app_data(web::JsonConfig::default().error_handler(extractor_error))
+            .app_data(web::PathConfig::default().error_handler(extractor_error))
+            .app_data(web::QueryConfig::default().error_handler(extractor_error))
             .wrap(cors)
+            .service(web::resource("/auth/register")
+                .route(web::post().to(register))
+            )
+            .service(web::resource("/auth/login")
+                .route(web::post().to(login))
+            )
+            .service(web::resource("/auth/logout")
+                .route(web::post().to(logout))
+            )
             .service(web::resource("/events")
                 .route(web::post().to(create_event))
-                .route(web::patch().to(modify_event))
                 .route(web::get().to(get_events))
+                .route(legacy_route(web::patch().to(legacy::modify_event), features.legacy_routes))
+                .route(legacy_route(web::delete().to(legacy::delete_event), features.legacy_routes))
+            )
+            // Registered before `/events/{event_id}`, which would otherwise match it first.
+            .service(web::resource("/events/occurrences")
+                .route(legacy_route(web::post().to(legacy::create_event_override), features.legacy_routes))
+                .route(legacy_route(web::delete().to(legacy::delete_event_override), features.legacy_routes))
+            )
+            .service(web::resource("/events/{event_id}")
+                .route(web::get().to(get_event))
+                .route(web::patch().to(modify_event))
                 .route(web::delete().to(delete_event))
             )
+            .service(web::resource("/events/{event_id}/participants")
+                .route(web::get().to(get_event_participants))
+            )
+            .service(web::resource("/events/{event_id}/invitations")
+                .route(web::get().to(get_event_invitations))
+                .route(web::post().to(create_event_invitation))
+            )
+            .service(web::resource("/events/{event_id}/guests")
+                .route(web::get().to(get_event_guests))
+            )
+            .service(web::resource("/events/{event_id}/occurrences")
+                .route(web::get().to(get_event_overrides))
+                .route(web::post().to(create_event_override))
+            )
+            .service(web::resource("/events/{event_id}/occurrences/{occurrence_starts_at}")
+                .route(web::get().to(get_event_override))
+                .route(web::delete().to(delete_event_override))
+            )
+            .service(web::resource("/participations")
+                .route(web::get().to(get_participations))
+                .route(web::post().to(create_participation))
+                .route(legacy_route(web::patch().to(legacy::modify_participation), features.legacy_routes))
+                .route(legacy_route(web::delete().to(legacy::delete_participation), features.legacy_routes))
+            )
+            .service(web::resource("/participations/{participation_id}")
+                .route(web::get().to(get_participation))
+                .route(web::patch().to(modify_participation))
+                .route(web::delete().to(delete_participation))
+            )
             .service(web::resource("/plans")
+                .route(web::get().to(get_plans))
                 .route(web::post().to(create_plan))
+                .route(legacy_route(web::delete().to(legacy::delete_plan), features.legacy_routes))
+            )
+            .service(web::resource("/plans/{plan_id}")
+                .route(web::get().to(get_plan))
                 .route(web::delete().to(delete_plan))
             )
             .service(web::resource("/users")
-                // .route(web::get().to(get_persons))
-                .route(web::post().to(create_person))
+                .route(web::get().to(get_persons))
+                .route(legacy_route(web::patch().to(legacy::modify_person), features.legacy_routes))
+                .route(legacy_route(web::delete().to(legacy::delete_person), features.legacy_routes))
+            )
+            .service(web::resource("/users/{person_id}")
+                .route(web::get().to(get_person))
                 .route(web::patch().to(modify_person))
                 .route(web::delete().to(delete_person))
             )
             .service(web::resource("/users/{person_id}/events")
-                .route(web::get().to(get_events))
+                .route(web::get().to(get_person_events))
+            )
+            .service(web::resource("/users/{person_id}/participations")
+                .route(web::get().to(get_person_participations))
+            )
+            .service(web::resource("/users/{person_id}/calendar.ics")
+                .route(web::get().to(get_person_calendar))
+            )
+            .service(web::resource("/users/{person_id}/organizations")
+                .route(web::get().to(get_person_organizations))
             )
             .service(web::resource("/planner")
-                .route(web::post().to(create_planner))
+                .route(web::get().to(get_planners))
+                .route(legacy_route(web::delete().to(legacy::delete_planner), features.legacy_routes))
+            )
+            .service(web::resource("/planner/{planner_id}")
+                .route(web::get().to(get_planner))
                 .route(web::delete().to(delete_planner))
             )
             .service(web::resource("/affiliations")
+                .route(web::get().to(get_affiliations))
                 .route(web::post().to(create_affiliation))
+                .route(legacy_route(web::patch().to(legacy::modify_affiliation), features.legacy_routes))
+                .route(legacy_route(web::delete().to(legacy::delete_affiliation), features.legacy_routes))
+            )
+            .service(web::resource("/affiliations/{affiliation_id}")
+                .route(web::get().to(get_affiliation))
+                .route(web::patch().to(modify_affiliation))
                 .route(web::delete().to(delete_affiliation))
             )
             .service(web::resource("/organizations")
+                .route(web::get().to(get_organizations))
                 .route(web::post().to(create_organization))
-                .route(web::delete().to(delete_organization))
+                .route(legacy_route(web::delete().to(legacy::delete_organization), features.legacy_routes))
             )   
-    })
-    .bind(config.server_addr.clone())?
-    .run();
-    println!("Server running at http://{}/", config.server_addr);
+            .service(web::resource("/organizations/{organization_id}")
+                .route(web::get().to(get_organization))
+                .route(web::patch().to(modify_organization))
+                .route(web::delete().to(delete_organization))
+            )
+            .service(web::resource("/organizations/{organization_id}/calendar.ics")
+                .route(web::get().to(get_organization_calendar))
+            )
+            .service(web::resource("/organizations/{organization_id}/members")
+                .route(web::get().to(get_organization_members))
+            )
+            .service(web::resource("/organizations/{organization_id}/invitations")
+                .route(web::get().to(get_organization_invitations))
+                .route(web::post().to(create_organization_invitation))
+            )
+            .service(web::resource("/invitations/{invitation_id}")
+                .route(web::delete().to(revoke_invitation))
+            )
+            .service(web::resource("/invitations/{token}/accept")
+                .route(web::post().to(accept_invitation))
+            )
+            // Public: the tokens in the path are what grant access, not a session.
+            .service(web::resource("/rsvp/{token}")
+                .route(web::get().to(get_rsvp_page))
+                .route(web::post().to(create_guest_rsvp))
+            )
+            .service(web::resource("/rsvp/guests/{guest_token}")
+                .route(web::get().to(get_guest_rsvp))
+                .route(web::put().to(modify_guest_rsvp))
+            )
+            // Not public: the session says whom the guest is merged into.
+            .service(web::resource("/rsvp/guests/{guest_token}/merge")
+                .route(web::post().to(merge_guest_rsvp))
+            )
+            .configure(|cfg| {
+                if features.calendar_import {
+                    cfg.service(web::resource("/planner/{planner_id}/import")
+                        .app_data(web::PayloadConfig::new(4 * 1024 * 1024))
+                        .route(web::post().to(import_calendar))
+                    );
+                }
+                if features.search {
+                    cfg.service(web::resource("/search")
+                        .route(web::get().to(search))
+                    );
+                }
+            })
+    });
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+
+    for address in &config.server.bind {
+        server = match &tls {
+            Some(tls) => server.bind_rustls(address, tls.clone())?,
+            None => server.bind(address)?,
+        };
+        println!("Server running at {}://{}/", if tls.is_some() { "https" } else { "http" }, address);
+    }
 
-    server.await
+    server.run().await
 }
\ No newline at end of file
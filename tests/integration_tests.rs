@@ -17,7 +17,7 @@
 //         .build()
 //         .unwrap();
 
-//     let config: ExampleConfig = config_.try_deserialize().unwrap();
+//     let config: ServerConfig = config_.try_deserialize().unwrap();
 
 //     let pool = config.pg.create_pool(None, NoTls).unwrap();
     